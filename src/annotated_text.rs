@@ -0,0 +1,280 @@
+use crate::audio::SpeechResult;
+use crate::backends::SpeechSynthesizerToAudioData;
+use crate::error::OutputError;
+use crate::ssml::Ssml;
+use std::time::Duration;
+/// A part of an [`AnnotatedText`]. Parameters that are not set fall back to the ones passed to
+/// the speak method; setting either the voice or the language replaces both.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TextSpan {
+  pub text: String,
+  pub language: Option<String>,
+  pub voice: Option<String>,
+  pub rate: Option<u8>,
+  pub pitch: Option<u8>,
+  pub volume: Option<u8>,
+  pub emphasis: bool,
+  /// Silence inserted after the span.
+  pub pause_after: Option<Duration>,
+}
+impl TextSpan {
+  #[must_use]
+  pub fn new(text: &str) -> Self {
+    TextSpan {
+      text: text.to_owned(),
+      ..TextSpan::default()
+    }
+  }
+  #[must_use]
+  pub fn language(mut self, language: &str) -> Self {
+    self.language = Some(language.to_owned());
+    self
+  }
+  #[must_use]
+  pub fn voice(mut self, voice: &str) -> Self {
+    self.voice = Some(voice.to_owned());
+    self
+  }
+  #[must_use]
+  pub fn rate(mut self, rate: u8) -> Self {
+    self.rate = Some(rate);
+    self
+  }
+  #[must_use]
+  pub fn pitch(mut self, pitch: u8) -> Self {
+    self.pitch = Some(pitch);
+    self
+  }
+  #[must_use]
+  pub fn volume(mut self, volume: u8) -> Self {
+    self.volume = Some(volume);
+    self
+  }
+  #[must_use]
+  pub fn emphasis(mut self) -> Self {
+    self.emphasis = true;
+    self
+  }
+  #[must_use]
+  pub fn pause_after(mut self, pause: Duration) -> Self {
+    self.pause_after = Some(pause);
+    self
+  }
+}
+/// Text made of spans that each have their own voice, language and prosody, so that mixed
+/// content such as a chat message in another language than its sender's name can be spoken in
+/// one call.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AnnotatedText {
+  pub spans: Vec<TextSpan>,
+}
+impl AnnotatedText {
+  #[must_use]
+  pub fn new() -> Self {
+    AnnotatedText::default()
+  }
+  #[must_use]
+  pub fn span(mut self, span: TextSpan) -> Self {
+    self.spans.push(span);
+    self
+  }
+  /// Adds a span that uses the parameters passed to the speak method.
+  #[must_use]
+  pub fn text(self, text: &str) -> Self {
+    self.span(TextSpan::new(text))
+  }
+  /// The text of all spans, which is spoken by backends that do not support markup.
+  #[must_use]
+  pub fn to_plain_text(&self) -> String {
+    self.spans.iter().map(|span| span.text.as_str()).collect()
+  }
+  /// Synthesizes the spans one after another, passing their chunks to `on_chunk` and inserting
+  /// silence for pauses.
+  pub(crate) fn speak_streaming(
+    &self,
+    synthesizer: &dyn SpeechSynthesizerToAudioData,
+    voice: Option<&str>,
+    language: Option<&str>,
+    rate: Option<u8>,
+    volume: Option<u8>,
+    pitch: Option<u8>,
+    on_chunk: &mut dyn FnMut(SpeechResult) -> bool,
+  ) -> Result<(), OutputError> {
+    let mut format = None;
+    for span in &self.spans {
+      let (voice, language) = if span.voice.is_some() || span.language.is_some() {
+        (span.voice.as_deref(), span.language.as_deref())
+      } else {
+        (voice, language)
+      };
+      let rate = span.rate.or(rate);
+      let volume = span.volume.or(volume);
+      let pitch = span.pitch.or(pitch);
+      let mut stopped = false;
+      let mut handle_chunk = |chunk: SpeechResult| {
+        format = Some((chunk.sample_format, chunk.sample_rate));
+        stopped = !on_chunk(chunk);
+        !stopped
+      };
+      if span.emphasis {
+        let ssml = Ssml::from_annotated_text(&AnnotatedText::new().span(TextSpan {
+          text: span.text.clone(),
+          emphasis: true,
+          ..TextSpan::default()
+        }));
+        synthesizer.speak_ssml_streaming(
+          voice,
+          language,
+          rate,
+          volume,
+          pitch,
+          &ssml,
+          &mut handle_chunk,
+        )?;
+      } else {
+        synthesizer.speak_streaming(
+          voice,
+          language,
+          rate,
+          volume,
+          pitch,
+          &span.text,
+          &mut handle_chunk,
+        )?;
+      }
+      if stopped {
+        return Ok(());
+      }
+      if let (Some(pause), Some((sample_format, sample_rate))) = (span.pause_after, &format) {
+        let samples = u128::from(*sample_rate) * pause.as_millis() / 1000;
+        let samples = usize::try_from(samples).map_err(OutputError::into_invalid_parameter)?;
+        let silence = SpeechResult {
+          pcm: vec![0; samples * sample_format.sample_size()],
+          sample_format: *sample_format,
+          sample_rate: *sample_rate,
+          markers: Vec::new(),
+        };
+        if !on_chunk(silence) {
+          return Ok(());
+        }
+      }
+    }
+    Ok(())
+  }
+}
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::audio::SampleFormat;
+  use std::cell::RefCell;
+  /// Records what it is asked to speak and returns 10 samples at 1 kHz for each call.
+  #[derive(Default)]
+  struct Recorder {
+    calls: RefCell<Vec<String>>,
+  }
+  impl SpeechSynthesizerToAudioData for Recorder {
+    fn supports_speech_parameters(&self) -> bool {
+      true
+    }
+    fn speak(
+      &self,
+      voice: Option<&str>,
+      language: Option<&str>,
+      rate: Option<u8>,
+      volume: Option<u8>,
+      pitch: Option<u8>,
+      text: &str,
+    ) -> Result<SpeechResult, OutputError> {
+      self.calls.borrow_mut().push(format!(
+        "{voice:?} {language:?} {rate:?} {volume:?} {pitch:?} {text}"
+      ));
+      Ok(SpeechResult {
+        pcm: vec![1; 20],
+        sample_format: SampleFormat::S16,
+        sample_rate: 1000,
+        markers: Vec::new(),
+      })
+    }
+    fn speak_ssml_streaming(
+      &self,
+      voice: Option<&str>,
+      language: Option<&str>,
+      rate: Option<u8>,
+      volume: Option<u8>,
+      pitch: Option<u8>,
+      ssml: &Ssml,
+      on_chunk: &mut dyn FnMut(SpeechResult) -> bool,
+    ) -> Result<(), OutputError> {
+      on_chunk(self.speak(voice, language, rate, volume, pitch, &ssml.to_string())?);
+      Ok(())
+    }
+  }
+  fn text() -> AnnotatedText {
+    AnnotatedText::new()
+      .text("Hello ")
+      .span(
+        TextSpan::new("Bonjour")
+          .language("fr")
+          .rate(80)
+          .pause_after(Duration::from_millis(50)),
+      )
+      .span(TextSpan::new("!").emphasis())
+  }
+  #[test]
+  fn concatenates_plain_text() {
+    assert_eq!(text().to_plain_text(), "Hello Bonjour!");
+    assert_eq!(AnnotatedText::new().to_plain_text(), "");
+  }
+  #[test]
+  fn speaks_spans_with_their_parameters() {
+    let recorder = Recorder::default();
+    let mut chunks = Vec::new();
+    text()
+      .speak_streaming(
+        &recorder,
+        Some("Alice"),
+        Some("en"),
+        Some(50),
+        Some(70),
+        Some(40),
+        &mut |chunk| {
+          chunks.push(chunk.pcm);
+          true
+        },
+      )
+      .unwrap();
+    assert_eq!(
+      *recorder.calls.borrow(),
+      [
+        "Some(\"Alice\") Some(\"en\") Some(50) Some(70) Some(40) Hello ",
+        "None Some(\"fr\") Some(80) Some(70) Some(40) Bonjour",
+        "Some(\"Alice\") Some(\"en\") Some(50) Some(70) Some(40) <speak><emphasis>!</emphasis></speak>",
+      ]
+    );
+    assert_eq!(chunks.len(), 4);
+    assert_eq!(chunks[2], vec![0; 100]);
+  }
+  #[test]
+  fn stops_when_a_chunk_is_refused() {
+    let recorder = Recorder::default();
+    text()
+      .speak_streaming(&recorder, None, None, None, None, None, &mut |_| false)
+      .unwrap();
+    assert_eq!(recorder.calls.borrow().len(), 1);
+  }
+  #[test]
+  fn converts_to_ssml() {
+    let ssml = Ssml::from_annotated_text(
+      &AnnotatedText::new()
+        .span(TextSpan::new("Hi").voice("Bob").rate(25).volume(60))
+        .span(TextSpan::new("there").pause_after(Duration::from_millis(300))),
+    );
+    assert_eq!(
+      ssml.to_string(),
+      "<speak><voice name=\"Bob\"><prosody rate=\"50%\" volume=\"60%\">Hi</prosody></voice>\
+       there<break time=\"300ms\"/></speak>"
+    );
+  }
+}
@@ -0,0 +1,111 @@
+use crate::audio::SpeechResult;
+use crate::error::OutputError;
+use crate::metadata::{Voice, VoiceFilter};
+use crate::request::{SpeechContent, SpeechRequest};
+use crate::utterance::Utterance;
+use crate::{Operation, OperationOk, OperationResult, Whisprs};
+use anyhow::anyhow;
+use tokio::sync::oneshot;
+/// Futures-based versions of the most common methods, available with the `async` feature. They
+/// send the same operations to the worker thread as their blocking counterparts, but wait for the
+/// result without blocking the executor. The feature also makes Speech Dispatcher use the
+/// asynchronous SSIP client, which handles event notifications as soon as they arrive.
+impl Whisprs {
+  async fn perform_operation_async(&self, closure: Operation) -> OperationResult {
+    let (result_tx, result_rx) = oneshot::channel();
+    self.send_operation(Box::new(move || {
+      let _result = result_tx.send(closure());
+      Ok(Box::new(()) as OperationOk)
+    }))?;
+    result_rx.await.map_err(OutputError::into_unknown)?
+  }
+  pub async fn list_voices_async(&self, filter: &VoiceFilter) -> Result<Vec<Voice>, OutputError> {
+    let operation = Whisprs::list_voices_operation(filter);
+    let result = self
+      .perform_operation_async(operation)
+      .await?
+      .downcast()
+      .map_err(|_| {
+        OutputError::into_unknown(anyhow!("Failed to downcast received return value"))
+      })?;
+    Ok(*result)
+  }
+  pub async fn speak_to_audio_data_async(
+    &self,
+    synthesizer: Option<&str>,
+    voice: Option<&str>,
+    language: Option<&str>,
+    rate: Option<u8>,
+    volume: Option<u8>,
+    pitch: Option<u8>,
+    text: &str,
+  ) -> Result<SpeechResult, OutputError> {
+    let request = Whisprs::positional_request(
+      synthesizer,
+      voice,
+      language,
+      rate,
+      volume,
+      pitch,
+      SpeechContent::Text(text.to_owned()),
+    );
+    self.speak_request_to_audio_data_async(&request).await
+  }
+  pub async fn speak_request_to_audio_data_async(
+    &self,
+    request: &SpeechRequest,
+  ) -> Result<SpeechResult, OutputError> {
+    let operation = self.speak_to_audio_data_operation(request)?;
+    let result = self
+      .perform_operation_async(operation)
+      .await?
+      .downcast()
+      .map_err(|_| {
+        OutputError::into_unknown(anyhow!("Failed to downcast received return value"))
+      })?;
+    Ok(*result)
+  }
+  pub async fn speak_to_audio_output_async(
+    &self,
+    synthesizer: Option<&str>,
+    voice: Option<&str>,
+    language: Option<&str>,
+    rate: Option<u8>,
+    volume: Option<u8>,
+    pitch: Option<u8>,
+    text: &str,
+    interrupt: bool,
+  ) -> Result<Utterance, OutputError> {
+    let mut request = Whisprs::positional_request(
+      synthesizer,
+      voice,
+      language,
+      rate,
+      volume,
+      pitch,
+      SpeechContent::Text(text.to_owned()),
+    );
+    request.interrupt = interrupt;
+    self.speak_request_to_audio_output_async(&request).await
+  }
+  pub async fn speak_request_to_audio_output_async(
+    &self,
+    request: &SpeechRequest,
+  ) -> Result<Utterance, OutputError> {
+    let (operation, utterance) = self.speak_operation(request)?;
+    self.perform_operation_async(operation).await?;
+    Ok(utterance)
+  }
+  pub async fn braille_async(&self, backend: Option<&str>, text: &str) -> Result<(), OutputError> {
+    self
+      .perform_operation_async(Whisprs::braille_operation(backend, text))
+      .await?;
+    Ok(())
+  }
+  pub async fn stop_speech_async(&self, synthesizer: Option<&str>) -> Result<(), OutputError> {
+    self
+      .perform_operation_async(self.stop_speech_operation(synthesizer))
+      .await?;
+    Ok(())
+  }
+}
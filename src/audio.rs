@@ -1,12 +1,60 @@
-#[derive(Clone, Debug)]
+use crate::error::OutputError;
+use crate::events::SpeechEventKind;
+use std::sync::mpsc;
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum SampleFormat {
   S16 = 0,
   F32 = 1,
 }
-#[derive(Debug)]
+impl SampleFormat {
+  /// The number of bytes in one sample.
+  pub(crate) fn sample_size(self) -> usize {
+    match self {
+      SampleFormat::S16 => 2,
+      SampleFormat::F32 => 4,
+    }
+  }
+}
+/// An event that happens when playback of a [`SpeechResult`] reaches `sample`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SpeechMarker {
+  pub sample: usize,
+  pub kind: SpeechEventKind,
+}
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SpeechResult {
   pub pcm: Vec<u8>,
   pub sample_format: SampleFormat,
   pub sample_rate: u32,
+  pub markers: Vec<SpeechMarker>,
+}
+impl SpeechResult {
+  /// Appends a chunk of audio in the same format, moving its markers after the existing audio.
+  pub(crate) fn append(&mut self, chunk: SpeechResult) {
+    let offset = self.pcm.len() / self.sample_format.sample_size();
+    self
+      .markers
+      .extend(chunk.markers.into_iter().map(|marker| SpeechMarker {
+        sample: marker.sample + offset,
+        ..marker
+      }));
+    self.pcm.extend(chunk.pcm);
+  }
+}
+/// Speech returned by [`crate::Whisprs::speak_to_audio_stream`] while it is being synthesized.
+/// Each item is a chunk of audio whose marker positions are relative to the start of the chunk.
+/// Dropping the stream stops synthesis.
+#[derive(Debug)]
+pub struct SpeechStream {
+  pub(crate) receiver: mpsc::Receiver<Result<SpeechResult, OutputError>>,
+}
+impl Iterator for SpeechStream {
+  type Item = Result<SpeechResult, OutputError>;
+  fn next(&mut self) -> Option<Self::Item> {
+    self.receiver.recv().ok()
+  }
 }
@@ -1,31 +1,45 @@
 use crate::audio::{SampleFormat, SpeechResult};
 use crate::backends::{
-  Backend, BrailleBackend, SpeechSynthesizerToAudioData, SpeechSynthesizerToAudioOutput,
+  Backend, BackendOptions, BrailleBackend, SpeechSynthesizerToAudioData,
+  SpeechSynthesizerToAudioOutput,
 };
 use crate::error::OutputError;
-use crate::metadata::Voice;
+use crate::language::{language_distance, normalize_language_tag};
+use crate::metadata::{Gender, Voice, VoiceQuality};
+use crate::priority::Priority;
+use crate::ssml::Ssml;
+use crate::utterance::{Utterance, UtteranceState};
 use anyhow::anyhow;
 use block2::RcBlock;
 use objc2::rc::Retained;
 use objc2_avf_audio::{
   AVAudioBuffer, AVAudioCommonFormat, AVAudioPCMBuffer, AVSpeechBoundary, AVSpeechSynthesisVoice,
-  AVSpeechSynthesisVoiceQuality, AVSpeechSynthesizer, AVSpeechUtterance,
-  AVSpeechUtteranceMaximumSpeechRate, AVSpeechUtteranceMinimumSpeechRate,
+  AVSpeechSynthesisVoiceGender, AVSpeechSynthesisVoiceQuality, AVSpeechSynthesizer,
+  AVSpeechUtterance, AVSpeechUtteranceMaximumSpeechRate, AVSpeechUtteranceMinimumSpeechRate,
 };
 use objc2_foundation::NSString;
 use std::ptr::NonNull;
 use std::sync::{mpsc, Arc, Mutex, OnceLock, RwLock};
+fn text_utterance(text: &str) -> Retained<AVSpeechUtterance> {
+  unsafe { AVSpeechUtterance::speechUtteranceWithString(&NSString::from_str(text)) }
+}
+fn ssml_utterance(ssml: &Ssml) -> Result<Retained<AVSpeechUtterance>, OutputError> {
+  unsafe {
+    AVSpeechUtterance::speechUtteranceWithSSMLRepresentation(&NSString::from_str(&ssml.to_string()))
+      .ok_or(OutputError::into_invalid_parameter(anyhow!(
+        "AVSpeechSynthesizer could not parse the SSML"
+      )))
+  }
+}
 fn set_parameters(
+  utterance: Retained<AVSpeechUtterance>,
   voice: Option<&str>,
   language: Option<&str>,
   rate: Option<u8>,
   volume: Option<u8>,
   pitch: Option<u8>,
-  text: &str,
 ) -> Result<Retained<AVSpeechUtterance>, OutputError> {
   unsafe {
-    let text = NSString::from_str(text);
-    let utterance = AVSpeechUtterance::speechUtteranceWithString(&text);
     match (voice, language) {
       (None, None) => {}
       (Some(voice_name), _) => {
@@ -37,8 +51,13 @@ fn set_parameters(
       (_, Some(language)) => {
         let voice = AVSpeechSynthesisVoice::speechVoices()
           .into_iter()
-          .find(|voice| voice.language().to_string().to_lowercase() == language)
-          .ok_or(OutputError::into_language_not_found(language))?;
+          .filter_map(|voice| {
+            language_distance(language, &voice.language().to_string())
+              .map(|distance| (distance, voice))
+          })
+          .min_by_key(|(distance, _)| *distance)
+          .ok_or(OutputError::into_language_not_found(language))?
+          .1;
         utterance.setVoice(Some(&voice));
       }
     }
@@ -62,77 +81,24 @@ fn set_parameters(
   }
 }
 pub struct AvSpeechSynthesizer {
+  default_voice: Option<String>,
   synthesizer: Mutex<Retained<AVSpeechSynthesizer>>,
 }
-impl Backend for AvSpeechSynthesizer {
-  fn new() -> Result<Self, OutputError> {
-    let result = AvSpeechSynthesizer {
-      synthesizer: unsafe { Mutex::new(AVSpeechSynthesizer::new()) },
-    };
-    Ok(result)
-  }
-  fn name(&self) -> String {
-    "AVSpeechSynthesizer".to_owned()
-  }
-  fn list_voices(&self) -> Result<Vec<Voice>, OutputError> {
-    unsafe {
-      let voices = AVSpeechSynthesisVoice::speechVoices();
-      let voices = voices
-        .into_iter()
-        .map(|voice| {
-          let languages = vec![voice.language().to_string().to_lowercase()];
-          let name = voice.identifier().to_string();
-          let display_name = voice.name().to_string();
-          let quality = voice.quality();
-          let display_name = match quality {
-            AVSpeechSynthesisVoiceQuality::Enhanced => display_name + " (Enhanced)",
-            AVSpeechSynthesisVoiceQuality::Premium => display_name + " (Premium)",
-            _ => display_name,
-          };
-          let priority: u8 = match quality {
-            AVSpeechSynthesisVoiceQuality::Premium => 1,
-            AVSpeechSynthesisVoiceQuality::Enhanced => 2,
-            _ => 3,
-          };
-          Voice {
-            synthesizer: self.speech_metadata().unwrap(),
-            display_name,
-            name,
-            languages,
-            priority,
-          }
-        })
-        .collect::<Vec<Voice>>();
-      Ok(voices)
-    }
-  }
-  fn as_speech_synthesizer_to_audio_data(&self) -> Option<&dyn SpeechSynthesizerToAudioData> {
-    Some(self)
-  }
-  fn as_speech_synthesizer_to_audio_output(&self) -> Option<&dyn SpeechSynthesizerToAudioOutput> {
-    Some(self)
-  }
-  fn as_braille_backend(&self) -> Option<&dyn BrailleBackend> {
-    None
-  }
-}
-impl SpeechSynthesizerToAudioData for AvSpeechSynthesizer {
-  fn supports_speech_parameters(&self) -> bool {
-    true
-  }
+impl AvSpeechSynthesizer {
   #[allow(clippy::cast_possible_truncation)]
   #[allow(clippy::cast_sign_loss)]
-  fn speak(
+  fn synthesize(
     &self,
+    utterance: Retained<AVSpeechUtterance>,
     voice: Option<&str>,
     language: Option<&str>,
     rate: Option<u8>,
     volume: Option<u8>,
     pitch: Option<u8>,
-    text: &str,
   ) -> Result<SpeechResult, OutputError> {
     unsafe {
-      let utterance = set_parameters(voice, language, rate, volume, pitch, text)?;
+      let voice = self.voice_or_default(voice, language);
+      let utterance = set_parameters(utterance, voice, language, rate, volume, pitch)?;
       let pcm: Arc<RwLock<Vec<u8>>> = Arc::new(RwLock::new(Vec::new()));
       let pcm2 = pcm.clone();
       let sample_format: Arc<OnceLock<SampleFormat>> = Arc::new(OnceLock::new());
@@ -213,9 +179,156 @@ impl SpeechSynthesizerToAudioData for AvSpeechSynthesizer {
         pcm,
         sample_format,
         sample_rate,
+        markers: Vec::new(),
       })
     }
   }
+  fn speak_utterance(
+    &self,
+    av_utterance: Retained<AVSpeechUtterance>,
+    voice: Option<&str>,
+    language: Option<&str>,
+    rate: Option<u8>,
+    volume: Option<u8>,
+    pitch: Option<u8>,
+    interrupt: bool,
+    utterance: &Utterance,
+  ) -> Result<(), OutputError> {
+    unsafe {
+      let voice = self.voice_or_default(voice, language);
+      let av_utterance = set_parameters(av_utterance, voice, language, rate, volume, pitch)?;
+      if interrupt {
+        self
+          .synthesizer
+          .lock()
+          .map_err(|_| {
+            OutputError::into_unknown(anyhow!("Failed to lock AVSpeechSynthesizer instance"))
+          })?
+          .stopSpeakingAtBoundary(AVSpeechBoundary::Immediate);
+      }
+      self
+        .synthesizer
+        .lock()
+        .map_err(|_| {
+          OutputError::into_unknown(anyhow!("Failed to lock AVSpeechSynthesizer instance"))
+        })?
+        .speakUtterance(&av_utterance);
+      utterance.set_state(UtteranceState::Delivered);
+      Ok(())
+    }
+  }
+  fn voice_or_default<'a>(
+    &'a self,
+    voice: Option<&'a str>,
+    language: Option<&str>,
+  ) -> Option<&'a str> {
+    match (voice, language) {
+      (None, None) => self.default_voice.as_deref(),
+      (voice, _) => voice,
+    }
+  }
+}
+impl Backend for AvSpeechSynthesizer {
+  fn new() -> Result<Self, OutputError> {
+    AvSpeechSynthesizer::new_with_options(&BackendOptions::default())
+  }
+  fn new_with_options(options: &BackendOptions) -> Result<Self, OutputError> {
+    let result = AvSpeechSynthesizer {
+      default_voice: options.default_voice.clone(),
+      synthesizer: unsafe { Mutex::new(AVSpeechSynthesizer::new()) },
+    };
+    Ok(result)
+  }
+  fn name(&self) -> String {
+    "AVSpeechSynthesizer".to_owned()
+  }
+  fn list_voices(&self) -> Result<Vec<Voice>, OutputError> {
+    unsafe {
+      let voices = AVSpeechSynthesisVoice::speechVoices();
+      let voices = voices
+        .into_iter()
+        .map(|voice| {
+          let languages = vec![normalize_language_tag(&voice.language().to_string())];
+          let name = voice.identifier().to_string();
+          let display_name = voice.name().to_string();
+          let quality = voice.quality();
+          let display_name = match quality {
+            AVSpeechSynthesisVoiceQuality::Enhanced => display_name + " (Enhanced)",
+            AVSpeechSynthesisVoiceQuality::Premium => display_name + " (Premium)",
+            _ => display_name,
+          };
+          let priority: u8 = match quality {
+            AVSpeechSynthesisVoiceQuality::Premium => 1,
+            AVSpeechSynthesisVoiceQuality::Enhanced => 2,
+            _ => 3,
+          };
+          let quality = match quality {
+            AVSpeechSynthesisVoiceQuality::Premium => VoiceQuality::Premium,
+            AVSpeechSynthesisVoiceQuality::Enhanced => VoiceQuality::Enhanced,
+            _ => VoiceQuality::Default,
+          };
+          let gender = match voice.gender() {
+            AVSpeechSynthesisVoiceGender::Male => Some(Gender::Male),
+            AVSpeechSynthesisVoiceGender::Female => Some(Gender::Female),
+            _ => None,
+          };
+          Voice {
+            synthesizer: self.speech_metadata().unwrap(),
+            id: "avspeechsynthesizer:".to_owned() + &name,
+            display_name,
+            name,
+            languages,
+            gender,
+            age: None,
+            variant: None,
+            quality: Some(quality),
+            supports_audio_data: true,
+            sample_rate: None,
+            priority,
+          }
+        })
+        .collect::<Vec<Voice>>();
+      Ok(voices)
+    }
+  }
+  fn as_speech_synthesizer_to_audio_data(&self) -> Option<&dyn SpeechSynthesizerToAudioData> {
+    Some(self)
+  }
+  fn as_speech_synthesizer_to_audio_output(&self) -> Option<&dyn SpeechSynthesizerToAudioOutput> {
+    Some(self)
+  }
+  fn as_braille_backend(&self) -> Option<&dyn BrailleBackend> {
+    None
+  }
+}
+impl SpeechSynthesizerToAudioData for AvSpeechSynthesizer {
+  fn supports_speech_parameters(&self) -> bool {
+    true
+  }
+  fn speak(
+    &self,
+    voice: Option<&str>,
+    language: Option<&str>,
+    rate: Option<u8>,
+    volume: Option<u8>,
+    pitch: Option<u8>,
+    text: &str,
+  ) -> Result<SpeechResult, OutputError> {
+    self.synthesize(text_utterance(text), voice, language, rate, volume, pitch)
+  }
+  fn speak_ssml_streaming(
+    &self,
+    voice: Option<&str>,
+    language: Option<&str>,
+    rate: Option<u8>,
+    volume: Option<u8>,
+    pitch: Option<u8>,
+    ssml: &Ssml,
+    on_chunk: &mut dyn FnMut(SpeechResult) -> bool,
+  ) -> Result<(), OutputError> {
+    on_chunk(self.synthesize(ssml_utterance(ssml)?, voice, language, rate, volume, pitch)?);
+    Ok(())
+  }
 }
 impl SpeechSynthesizerToAudioOutput for AvSpeechSynthesizer {
   fn supports_speech_parameters(&self) -> bool {
@@ -230,29 +343,93 @@ impl SpeechSynthesizerToAudioOutput for AvSpeechSynthesizer {
     pitch: Option<u8>,
     text: &str,
     interrupt: bool,
+    _priority: Priority,
+    utterance: &Utterance,
   ) -> Result<(), OutputError> {
+    self.speak_utterance(
+      text_utterance(text),
+      voice,
+      language,
+      rate,
+      volume,
+      pitch,
+      interrupt,
+      utterance,
+    )
+  }
+  fn speak_ssml(
+    &self,
+    voice: Option<&str>,
+    language: Option<&str>,
+    rate: Option<u8>,
+    volume: Option<u8>,
+    pitch: Option<u8>,
+    ssml: &Ssml,
+    interrupt: bool,
+    _priority: Priority,
+    utterance: &Utterance,
+  ) -> Result<(), OutputError> {
+    self.speak_utterance(
+      ssml_utterance(ssml)?,
+      voice,
+      language,
+      rate,
+      volume,
+      pitch,
+      interrupt,
+      utterance,
+    )
+  }
+  fn stop_speech(&self) -> Result<(), OutputError> {
     unsafe {
-      let utterance = set_parameters(voice, language, rate, volume, pitch, text)?;
-      if interrupt {
+      self
+        .synthesizer
+        .lock()
+        .map_err(|_| {
+          OutputError::into_unknown(anyhow!("Failed to lock AVSpeechSynthesizer instance"))
+        })?
+        .stopSpeakingAtBoundary(AVSpeechBoundary::Immediate);
+      Ok(())
+    }
+  }
+  /// `isSpeaking` stays true while speech is paused, so paused speech is excluded here.
+  fn is_speaking(&self) -> Result<bool, OutputError> {
+    unsafe {
+      let synthesizer = self.synthesizer.lock().map_err(|_| {
+        OutputError::into_unknown(anyhow!("Failed to lock AVSpeechSynthesizer instance"))
+      })?;
+      Ok(synthesizer.isSpeaking() && !synthesizer.isPaused())
+    }
+  }
+  fn is_paused(&self) -> Result<bool, OutputError> {
+    unsafe {
+      Ok(
         self
           .synthesizer
           .lock()
           .map_err(|_| {
             OutputError::into_unknown(anyhow!("Failed to lock AVSpeechSynthesizer instance"))
           })?
-          .stopSpeakingAtBoundary(AVSpeechBoundary::Immediate);
-      }
+          .isPaused(),
+      )
+    }
+  }
+  fn supports_pausing(&self) -> bool {
+    true
+  }
+  fn pause(&self) -> Result<(), OutputError> {
+    unsafe {
       self
         .synthesizer
         .lock()
         .map_err(|_| {
           OutputError::into_unknown(anyhow!("Failed to lock AVSpeechSynthesizer instance"))
         })?
-        .speakUtterance(&utterance);
+        .pauseSpeakingAtBoundary(AVSpeechBoundary::Immediate);
       Ok(())
     }
   }
-  fn stop_speech(&self) -> Result<(), OutputError> {
+  fn resume(&self) -> Result<(), OutputError> {
     unsafe {
       self
         .synthesizer
@@ -260,7 +437,7 @@ impl SpeechSynthesizerToAudioOutput for AvSpeechSynthesizer {
         .map_err(|_| {
           OutputError::into_unknown(anyhow!("Failed to lock AVSpeechSynthesizer instance"))
         })?
-        .stopSpeakingAtBoundary(AVSpeechBoundary::Immediate);
+        .continueSpeaking();
       Ok(())
     }
   }
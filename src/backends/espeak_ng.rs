@@ -1,25 +1,47 @@
 #![allow(non_upper_case_globals)]
-use crate::audio::{SampleFormat, SpeechResult};
+use crate::audio::{SampleFormat, SpeechMarker, SpeechResult};
 use crate::backends::{
-  Backend, BrailleBackend, SpeechSynthesizerToAudioData, SpeechSynthesizerToAudioOutput,
+  Backend, BackendOptions, BrailleBackend, SpeechSynthesizerToAudioData,
+  SpeechSynthesizerToAudioOutput,
 };
+use crate::capitals::Capitals;
 use crate::error::OutputError;
-use crate::metadata::Voice;
+use crate::events::SpeechEventKind;
+use crate::language::normalize_language_tag;
+use crate::lexicon::Lexicon;
+use crate::metadata::{AgeGroup, Gender, Voice, VoiceQuality, VoiceVariant};
+use crate::punctuation::Punctuation;
+use crate::ssml::Ssml;
 use anyhow::anyhow;
 use espeakng_sys::{
-  espeakCHARS_AUTO, espeakRATE_MAXIMUM, espeakRATE_MINIMUM, espeak_AUDIO_OUTPUT,
-  espeak_AUDIO_OUTPUT_AUDIO_OUTPUT_SYNCHRONOUS, espeak_ERROR, espeak_ERROR_EE_OK, espeak_EVENT,
-  espeak_Initialize, espeak_ListVoices, espeak_PARAMETER_espeakPITCH, espeak_PARAMETER_espeakRATE,
-  espeak_PARAMETER_espeakVOLUME, espeak_POSITION_TYPE, espeak_SetParameter,
+  espeakCHARS_AUTO, espeakPHONEMES, espeakRATE_MAXIMUM, espeakRATE_MINIMUM, espeakSSML,
+  espeak_AUDIO_OUTPUT, espeak_AUDIO_OUTPUT_AUDIO_OUTPUT_SYNCHRONOUS, espeak_ERROR,
+  espeak_ERROR_EE_OK, espeak_EVENT, espeak_EVENT_TYPE_espeakEVENT_LIST_TERMINATED,
+  espeak_EVENT_TYPE_espeakEVENT_MARK, espeak_EVENT_TYPE_espeakEVENT_SENTENCE,
+  espeak_EVENT_TYPE_espeakEVENT_WORD, espeak_GetCurrentVoice, espeak_Initialize, espeak_ListVoices,
+  espeak_PARAMETER_espeakCAPITALS, espeak_PARAMETER_espeakPITCH,
+  espeak_PARAMETER_espeakPUNCTUATION, espeak_PARAMETER_espeakRATE, espeak_PARAMETER_espeakVOLUME,
+  espeak_POSITION_TYPE, espeak_PUNCT_TYPE_espeakPUNCT_ALL, espeak_PUNCT_TYPE_espeakPUNCT_NONE,
+  espeak_PUNCT_TYPE_espeakPUNCT_SOME, espeak_SetParameter, espeak_SetPunctuationList,
   espeak_SetSynthCallback, espeak_SetVoiceByName, espeak_SetVoiceByProperties, espeak_Synth,
-  espeak_Terminate, espeak_VOICE,
+  espeak_Terminate, espeak_VOICE, wchar_t,
 };
-use std::cell::Cell;
+use std::cell::{Cell, RefCell};
 use std::ffi::{c_void, CStr, CString};
 use std::iter::once;
-use std::os::raw::{c_char, c_int, c_short};
+use std::os::raw::{c_int, c_short};
 use std::sync::{Arc, Mutex, Weak};
-static BUFFER: Mutex<Cell<Vec<u8>>> = Mutex::new(Cell::new(Vec::new()));
+/// The name, identifier, languages, gender and age of a voice or variant file.
+type EspeakVoice = (
+  String,
+  String,
+  Vec<String>,
+  Option<Gender>,
+  Option<AgeGroup>,
+);
+/// Receives the audio and the events of each buffer synthesized by eSpeak NG, with event
+/// positions in milliseconds. Returning `false` aborts synthesis.
+type ChunkHandler<'a> = dyn FnMut(&[u8], Vec<(usize, SpeechEventKind)>) -> bool + 'a;
 struct EspeakReference {
   default_voice: String,
   sample_rate: u32,
@@ -29,6 +51,8 @@ impl Drop for EspeakReference {
     unsafe { espeak_Terminate() };
   }
 }
+/// The pitch raise in Hz used for capital letters, since eSpeak NG reads values above 2 as one.
+const CAPITALS_PITCH_RAISE: c_int = 20;
 static INITIALIZE_COUNTER: Mutex<Weak<EspeakReference>> = Mutex::new(Weak::new());
 fn handle_espeak_error(error: espeak_ERROR) -> Result<(), anyhow::Error> {
   match error {
@@ -36,42 +60,69 @@ fn handle_espeak_error(error: espeak_ERROR) -> Result<(), anyhow::Error> {
     error => Err(anyhow!("eSpeak NG error: {}", error)),
   }
 }
+/// What [`EspeakNg::synthesize`] speaks.
+#[derive(Clone, Copy)]
+enum Input<'a> {
+  Text(&'a str),
+  Ssml(&'a Ssml),
+  /// Markup that spells characters, to which the lexicon does not apply.
+  Characters(&'a Ssml),
+}
 pub struct EspeakNg {
   default_voice: String,
   sample_rate: u32,
+  lexicon: RefCell<Lexicon>,
+  punctuation: RefCell<Option<Punctuation>>,
+  capitals: Cell<Option<Capitals>>,
   _reference: Arc<EspeakReference>,
 }
 impl Backend for EspeakNg {
   fn new() -> Result<Self, OutputError> {
+    EspeakNg::new_with_options(&BackendOptions::default())
+  }
+  fn new_with_options(options: &BackendOptions) -> Result<Self, OutputError> {
     let mut counter = INITIALIZE_COUNTER.lock().map_err(|_| {
       OutputError::into_unknown(anyhow!("Failed to lock eSpeak NG initialize counter"))
     })?;
     if let Some(reference) = counter.upgrade() {
       let sample_rate = reference.sample_rate;
-      let default_voice = reference.default_voice.clone();
+      let default_voice = options
+        .default_voice
+        .clone()
+        .unwrap_or(reference.default_voice.clone());
       let result = EspeakNg {
         default_voice,
         sample_rate,
+        lexicon: RefCell::new(Lexicon::new()),
+        punctuation: RefCell::new(None),
+        capitals: Cell::new(None),
         _reference: reference,
       };
       Ok(result)
     } else {
       let output: espeak_AUDIO_OUTPUT = espeak_AUDIO_OUTPUT_AUDIO_OUTPUT_SYNCHRONOUS;
-      let path_cstr = CString::new(".").map_err(OutputError::into_unknown)?;
+      let path_cstr = CString::new(options.data_path.as_deref().unwrap_or("."))
+        .map_err(OutputError::into_initialize_failed)?;
       let sample_rate: u32 = unsafe {
         espeak_Initialize(output, 0, path_cstr.as_ptr(), 0)
           .try_into()
           .map_err(OutputError::into_unknown)?
       };
-      let default_voice = "en".to_owned();
       let reference = Arc::new(EspeakReference {
-        default_voice: default_voice.clone(),
+        default_voice: "en".to_owned(),
         sample_rate,
       });
+      let default_voice = options
+        .default_voice
+        .clone()
+        .unwrap_or(reference.default_voice.clone());
       *counter = Arc::downgrade(&reference);
       let result = EspeakNg {
         default_voice,
         sample_rate,
+        lexicon: RefCell::new(Lexicon::new()),
+        punctuation: RefCell::new(None),
+        capitals: Cell::new(None),
         _reference: reference,
       };
       Ok(result)
@@ -81,6 +132,156 @@ impl Backend for EspeakNg {
     "eSpeak NG".to_owned()
   }
   fn list_voices(&self) -> Result<Vec<Voice>, OutputError> {
+    Ok(
+      EspeakNg::list_voice_files()?
+        .iter()
+        .filter(|voice| voice.2.first().is_some_and(|value| value != "variant"))
+        .map(|voice| self.voice(voice))
+        .collect(),
+    )
+  }
+  fn list_variants(&self) -> Result<Vec<VoiceVariant>, OutputError> {
+    Ok(
+      EspeakNg::list_voice_files()?
+        .into_iter()
+        .filter(|variant| variant.2.first().is_some_and(|value| value == "variant"))
+        .map(|variant| VoiceVariant {
+          synthesizer: self.speech_metadata().unwrap(),
+          id: "espeak-ng:".to_owned() + &variant.1,
+          display_name: variant.0,
+          name: variant.1.replace("!v/", ""),
+          gender: variant.3,
+          age: variant.4,
+        })
+        .collect(),
+    )
+  }
+  fn voice_with_variant(&self, voice: Option<&str>, variant: &str) -> Option<String> {
+    Some(voice.unwrap_or(&self.default_voice).to_owned() + "+" + variant)
+  }
+  fn set_lexicon(&self, lexicon: &Lexicon) -> Result<bool, OutputError> {
+    *self.lexicon.borrow_mut() = lexicon.clone();
+    Ok(
+      lexicon
+        .entries()
+        .any(|entry| entry.espeak_phonemes.is_some()),
+    )
+  }
+  fn set_punctuation(&self, punctuation: Option<&Punctuation>) -> Result<bool, OutputError> {
+    *self.punctuation.borrow_mut() = punctuation.cloned();
+    Ok(true)
+  }
+  fn set_capitals(&self, capitals: Option<Capitals>) -> Result<bool, OutputError> {
+    self.capitals.set(capitals);
+    Ok(true)
+  }
+  fn as_speech_synthesizer_to_audio_data(&self) -> Option<&dyn SpeechSynthesizerToAudioData> {
+    Some(self)
+  }
+  fn as_speech_synthesizer_to_audio_output(&self) -> Option<&dyn SpeechSynthesizerToAudioOutput> {
+    None
+  }
+  fn as_braille_backend(&self) -> Option<&dyn BrailleBackend> {
+    None
+  }
+}
+impl SpeechSynthesizerToAudioData for EspeakNg {
+  fn supports_speech_parameters(&self) -> bool {
+    true
+  }
+  fn speak(
+    &self,
+    voice: Option<&str>,
+    language: Option<&str>,
+    rate: Option<u8>,
+    volume: Option<u8>,
+    pitch: Option<u8>,
+    text: &str,
+  ) -> Result<SpeechResult, OutputError> {
+    let mut result = SpeechResult {
+      pcm: Vec::new(),
+      sample_format: SampleFormat::S16,
+      sample_rate: self.sample_rate,
+      markers: Vec::new(),
+    };
+    self.speak_streaming(voice, language, rate, volume, pitch, text, &mut |chunk| {
+      let offset = result.pcm.len() / 2;
+      result
+        .markers
+        .extend(chunk.markers.into_iter().map(|marker| SpeechMarker {
+          sample: marker.sample + offset,
+          ..marker
+        }));
+      result.pcm.extend(chunk.pcm);
+      true
+    })?;
+    Ok(result)
+  }
+  fn speak_streaming(
+    &self,
+    voice: Option<&str>,
+    language: Option<&str>,
+    rate: Option<u8>,
+    volume: Option<u8>,
+    pitch: Option<u8>,
+    text: &str,
+    on_chunk: &mut dyn FnMut(SpeechResult) -> bool,
+  ) -> Result<(), OutputError> {
+    self.synthesize(
+      voice,
+      language,
+      rate,
+      volume,
+      pitch,
+      Input::Text(text),
+      on_chunk,
+    )
+  }
+  fn speak_ssml_streaming(
+    &self,
+    voice: Option<&str>,
+    language: Option<&str>,
+    rate: Option<u8>,
+    volume: Option<u8>,
+    pitch: Option<u8>,
+    ssml: &Ssml,
+    on_chunk: &mut dyn FnMut(SpeechResult) -> bool,
+  ) -> Result<(), OutputError> {
+    self.synthesize(
+      voice,
+      language,
+      rate,
+      volume,
+      pitch,
+      Input::Ssml(ssml),
+      on_chunk,
+    )
+  }
+  /// Spells with the character mode of eSpeak NG.
+  fn spell_streaming(
+    &self,
+    voice: Option<&str>,
+    language: Option<&str>,
+    rate: Option<u8>,
+    volume: Option<u8>,
+    pitch: Option<u8>,
+    text: &str,
+    on_chunk: &mut dyn FnMut(SpeechResult) -> bool,
+  ) -> Result<(), OutputError> {
+    self.synthesize(
+      voice,
+      language,
+      rate,
+      volume,
+      pitch,
+      Input::Characters(&Ssml::characters(text)),
+      on_chunk,
+    )
+  }
+}
+impl EspeakNg {
+  /// Lists the voice and variant files of eSpeak NG, whose language is `variant` for variants.
+  fn list_voice_files() -> Result<Vec<EspeakVoice>, OutputError> {
     let _counter = INITIALIZE_COUNTER.lock().map_err(|_| {
       OutputError::into_unknown(anyhow!("Failed to lock eSpeak NG initialize counter"))
     })?;
@@ -108,6 +309,15 @@ impl Backend for EspeakNg {
         .iter()
         .flat_map(|voice| {
           let name = CStr::from_ptr((**voice).name).to_str()?.to_owned();
+          let gender = match (**voice).gender {
+            1 => Some(Gender::Male),
+            2 => Some(Gender::Female),
+            _ => None,
+          };
+          let age = match (**voice).age {
+            0 => None,
+            years => Some(AgeGroup::from_years(years)),
+          };
           let identifier = CStr::from_ptr((**voice).identifier).to_str()?.to_owned();
           let mut languages_ptr_copy = (**voice).languages;
           let mut string_start = languages_ptr_copy;
@@ -151,60 +361,69 @@ impl Backend for EspeakNg {
           let language = languages.into_iter().min_by_key(|tuple| tuple.0);
           let language = match language {
             None => vec![],
-            Some(language) => vec![language.1],
+            Some(language) => vec![normalize_language_tag(&language.1)],
           };
-          Ok::<(String, String, Vec<String>), anyhow::Error>((name, identifier, language))
+          Ok::<EspeakVoice, anyhow::Error>((name, identifier, language, gender, age))
         })
-        .collect::<Vec<(String, String, Vec<String>)>>()
+        .collect::<Vec<EspeakVoice>>()
     };
-    let variants = voices
-      .iter()
-      .filter(|voice| voice.2.first().is_some_and(|value| value == "variant"));
-    let main_voices = voices
-      .iter()
-      .filter(|voice| voice.2.first().is_some_and(|value| value != "variant"));
-    let voices = main_voices.flat_map(|voice| {
-      once(Voice {
-        synthesizer: self.speech_metadata().unwrap(),
-        display_name: voice.0.clone(),
-        name: voice.0.clone(),
-        languages: voice.2.clone(),
-        priority: 3,
-      })
-      .chain(variants.clone().map(move |variant| Voice {
-        synthesizer: self.speech_metadata().unwrap(),
-        display_name: voice.0.clone() + " (" + &variant.0 + ")",
-        name: voice.0.clone() + "+" + &variant.1.replace("!v/", ""),
-        languages: voice.2.clone(),
-        priority: 3,
-      }))
-    });
-    Ok(voices.collect::<Vec<Voice>>())
-  }
-  fn as_speech_synthesizer_to_audio_data(&self) -> Option<&dyn SpeechSynthesizerToAudioData> {
-    Some(self)
+    Ok(voices)
   }
-  fn as_speech_synthesizer_to_audio_output(&self) -> Option<&dyn SpeechSynthesizerToAudioOutput> {
-    None
-  }
-  fn as_braille_backend(&self) -> Option<&dyn BrailleBackend> {
-    None
+  fn voice(&self, voice: &EspeakVoice) -> Voice {
+    Voice {
+      synthesizer: self.speech_metadata().unwrap(),
+      id: "espeak-ng:".to_owned() + &voice.1,
+      display_name: voice.0.clone(),
+      name: voice.0.clone(),
+      languages: voice.2.clone(),
+      gender: voice.3,
+      age: voice.4,
+      variant: None,
+      quality: Some(VoiceQuality::Formant),
+      supports_audio_data: true,
+      sample_rate: Some(self.sample_rate),
+      priority: 3,
+    }
   }
-}
-impl SpeechSynthesizerToAudioData for EspeakNg {
-  fn supports_speech_parameters(&self) -> bool {
-    true
+  /// Sets the punctuation parameters of eSpeak NG, which are shared by all instances. Levels
+  /// below [`Punctuation::All`] are spoken as a punctuation list.
+  fn apply_punctuation(&self) -> Result<(), anyhow::Error> {
+    let punctuation = self.punctuation.borrow();
+    let punctuation_type = match punctuation.as_ref() {
+      None | Some(Punctuation::None) => espeak_PUNCT_TYPE_espeakPUNCT_NONE,
+      Some(Punctuation::All) => espeak_PUNCT_TYPE_espeakPUNCT_ALL,
+      Some(punctuation) => {
+        let list = punctuation
+          .characters()
+          .chars()
+          .map(|character| character as wchar_t)
+          .chain(once(0))
+          .collect::<Vec<wchar_t>>();
+        handle_espeak_error(unsafe { espeak_SetPunctuationList(list.as_ptr()) })?;
+        espeak_PUNCT_TYPE_espeakPUNCT_SOME
+      }
+    };
+    #[allow(clippy::cast_possible_wrap)]
+    handle_espeak_error(unsafe {
+      espeak_SetParameter(
+        espeak_PARAMETER_espeakPUNCTUATION,
+        punctuation_type as c_int,
+        0,
+      )
+    })
   }
   #[allow(clippy::cast_possible_truncation)]
-  fn speak(
+  #[allow(clippy::too_many_lines)]
+  fn synthesize(
     &self,
     voice: Option<&str>,
     language: Option<&str>,
     rate: Option<u8>,
     volume: Option<u8>,
     pitch: Option<u8>,
-    text: &str,
-  ) -> Result<SpeechResult, OutputError> {
+    input: Input,
+    on_chunk: &mut dyn FnMut(SpeechResult) -> bool,
+  ) -> Result<(), OutputError> {
     let _counter = INITIALIZE_COUNTER.lock().map_err(|_| {
       OutputError::into_unknown(anyhow!("Failed to lock eSpeak NG initialize counter"))
     })?;
@@ -220,7 +439,8 @@ impl SpeechSynthesizerToAudioData for EspeakNg {
           .map_err(|_| OutputError::into_voice_not_found(voice))?;
       }
       (_, Some(language)) => {
-        let language_cstr = CString::new(language).map_err(OutputError::into_unknown)?;
+        let language_cstr =
+          CString::new(language.to_lowercase()).map_err(OutputError::into_unknown)?;
         let mut voice_spec = espeak_VOICE {
           name: std::ptr::null(),
           languages: language_cstr.as_ptr(),
@@ -268,14 +488,87 @@ impl SpeechSynthesizerToAudioData for EspeakNg {
           err,
         )
       })?;
+    let capitals = match self.capitals.get() {
+      None | Some(Capitals::None) => 0,
+      Some(Capitals::Icon) => 1,
+      Some(Capitals::SayCap) => 2,
+      Some(Capitals::Pitch) => CAPITALS_PITCH_RAISE,
+    };
+    handle_espeak_error(unsafe {
+      espeak_SetParameter(espeak_PARAMETER_espeakCAPITALS, capitals, 0)
+    })
+    .map_err(|err| {
+      OutputError::into_speak_failed(
+        &self.name(),
+        voice.unwrap_or(language.unwrap_or(&self.default_voice)),
+        err,
+      )
+    })?;
+    self.apply_punctuation().map_err(|err| {
+      OutputError::into_speak_failed(
+        &self.name(),
+        voice.unwrap_or(language.unwrap_or(&self.default_voice)),
+        err,
+      )
+    })?;
+    let lexicon = self.lexicon.borrow();
+    let lexicon_language = || match language {
+      Some(language) => Some(language.to_owned()),
+      None if lexicon.has_language_entries() => current_voice_language(),
+      None => None,
+    };
+    // Matches of entries with phonemes become phoneme input in plain text and in the text of
+    // SSML elements alike, and phoneme input is only enabled when there are any.
+    let phonemes = Cell::new(false);
+    let apply_lexicon = |text: &str, language: Option<&str>| {
+      let (text, replaced) = lexicon.apply_espeak_phonemes(text, &self.name(), language);
+      phonemes.set(phonemes.get() || replaced);
+      text
+    };
+    let (text, flags) = match input {
+      Input::Text(text) => (
+        apply_lexicon(text, lexicon_language().as_deref()),
+        espeakCHARS_AUTO,
+      ),
+      Input::Ssml(ssml) => {
+        let mut ssml = ssml.clone();
+        ssml.map_text(lexicon_language().as_deref(), &apply_lexicon);
+        (ssml.to_espeak_ssml(), espeakCHARS_AUTO | espeakSSML)
+      }
+      Input::Characters(ssml) => (ssml.to_espeak_ssml(), espeakCHARS_AUTO | espeakSSML),
+    };
+    let flags = if phonemes.get() {
+      flags | espeakPHONEMES
+    } else {
+      flags
+    };
     unsafe { espeak_SetSynthCallback(Some(synth_callback)) };
+    let sample_rate = self.sample_rate;
+    let mut synthesized_samples = 0;
+    let mut handler = |pcm: &[u8], events: Vec<(usize, SpeechEventKind)>| {
+      let markers = events
+        .into_iter()
+        .map(|(audio_position, kind)| SpeechMarker {
+          sample: (audio_position * sample_rate as usize / 1000)
+            .saturating_sub(synthesized_samples),
+          kind,
+        })
+        .collect();
+      synthesized_samples += pcm.len() / 2;
+      on_chunk(SpeechResult {
+        pcm: pcm.to_vec(),
+        sample_format: SampleFormat::S16,
+        sample_rate,
+        markers,
+      })
+    };
+    let mut handler_ref: &mut ChunkHandler = &mut handler;
     let text_cstr = CString::new(text).map_err(OutputError::into_unknown)?;
     let position = 0u32;
     let position_type: espeak_POSITION_TYPE = 0;
     let end_position = 0u32;
-    let flags = espeakCHARS_AUTO;
     let identifier = std::ptr::null_mut();
-    let user_data = std::ptr::null_mut();
+    let user_data = (&raw mut handler_ref).cast::<c_void>();
     handle_espeak_error(unsafe {
       espeak_Synth(
         text_cstr.as_ptr().cast::<c_void>(),
@@ -295,34 +588,61 @@ impl SpeechSynthesizerToAudioData for EspeakNg {
         err,
       )
     })?;
-    let result = BUFFER
-      .lock()
-      .map_err(|_| OutputError::into_unknown(anyhow!("Failed to lock the eSpeak audio buffer")))?
-      .take();
-    Ok(SpeechResult {
-      pcm: result,
-      sample_format: SampleFormat::S16,
-      sample_rate: self.sample_rate,
-    })
+    Ok(())
   }
 }
-#[allow(clippy::cast_possible_truncation)]
+/// The language of the voice eSpeak NG is currently set to.
+fn current_voice_language() -> Option<String> {
+  let voice = unsafe { espeak_GetCurrentVoice() };
+  if voice.is_null() || unsafe { (*voice).languages.is_null() } {
+    return None;
+  }
+  let language = unsafe { CStr::from_ptr((*voice).languages.add(1)) };
+  language.to_str().ok().map(normalize_language_tag)
+}
 #[allow(clippy::cast_sign_loss)]
 unsafe extern "C" fn synth_callback(
   wav: *mut c_short,
   sample_count: c_int,
-  _events: *mut espeak_EVENT,
+  events: *mut espeak_EVENT,
 ) -> c_int {
-  if !wav.is_null() {
-    let sample_count: usize = sample_count.try_into().unwrap();
-    let wav_slice = std::slice::from_raw_parts_mut(wav.cast::<c_char>(), 2 * sample_count);
-    let mut wav_vec = wav_slice
+  if events.is_null() || (*events).user_data.is_null() {
+    return 0;
+  }
+  let handler = &mut *(*events).user_data.cast::<&mut ChunkHandler>();
+  let mut markers = Vec::new();
+  let mut event = events;
+  while (*event).type_ != espeak_EVENT_TYPE_espeakEVENT_LIST_TERMINATED {
+    let offset = usize::try_from((*event).text_position - 1).unwrap_or(0);
+    let length = usize::try_from((*event).length).unwrap_or(0);
+    let kind = match (*event).type_ {
+      espeak_EVENT_TYPE_espeakEVENT_WORD => Some(SpeechEventKind::Word { offset, length }),
+      espeak_EVENT_TYPE_espeakEVENT_SENTENCE => Some(SpeechEventKind::Sentence { offset, length }),
+      espeak_EVENT_TYPE_espeakEVENT_MARK if !(*event).id.name.is_null() => {
+        Some(SpeechEventKind::Mark(
+          CStr::from_ptr((*event).id.name)
+            .to_string_lossy()
+            .into_owned(),
+        ))
+      }
+      _ => None,
+    };
+    if let Some(kind) = kind {
+      let audio_position = usize::try_from((*event).audio_position).unwrap_or(0);
+      markers.push((audio_position, kind));
+    }
+    event = event.add(1);
+  }
+  let pcm = if wav.is_null() {
+    Vec::new()
+  } else {
+    std::slice::from_raw_parts(wav, usize::try_from(sample_count).unwrap_or(0))
       .iter()
-      .map(|byte| *byte as u8)
-      .collect::<Vec<u8>>();
-    let mut buffer = BUFFER.lock().unwrap().take();
-    buffer.append(&mut wav_vec);
-    BUFFER.lock().unwrap().set(buffer);
+      .flat_map(|sample| sample.to_le_bytes())
+      .collect::<Vec<u8>>()
+  };
+  if pcm.is_empty() && markers.is_empty() {
+    return 0;
   }
-  0
+  c_int::from(!handler(&pcm, markers))
 }
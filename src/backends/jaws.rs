@@ -5,6 +5,8 @@ use crate::backends::{
 };
 use crate::error::OutputError;
 use crate::metadata::Voice;
+use crate::priority::Priority;
+use crate::utterance::{Utterance, UtteranceState};
 use anyhow::anyhow;
 use windows::core::{interface, w, BSTR, GUID, HRESULT};
 use windows::Win32::Foundation::VARIANT_BOOL;
@@ -45,9 +47,16 @@ impl Backend for Jaws {
   fn list_voices(&self) -> std::result::Result<Vec<Voice>, OutputError> {
     Ok(vec![Voice {
       synthesizer: self.speech_metadata().unwrap(),
+      id: "jaws".to_owned(),
       display_name: "JAWS".to_owned(),
       name: "jaws".to_owned(),
       languages: vec![],
+      gender: None,
+      age: None,
+      variant: None,
+      quality: None,
+      supports_audio_data: false,
+      sample_rate: None,
       priority: 0,
     }])
   }
@@ -74,6 +83,8 @@ impl SpeechSynthesizerToAudioOutput for Jaws {
     _pitch: Option<u8>,
     text: &str,
     interrupt: bool,
+    _priority: Priority,
+    utterance: &Utterance,
   ) -> std::result::Result<(), OutputError> {
     let mut result = VARIANT_BOOL::default();
     unsafe {
@@ -84,6 +95,7 @@ impl SpeechSynthesizerToAudioOutput for Jaws {
         .map_err(|err| OutputError::into_speak_failed(&self.name(), "jaws", err))?;
     };
     if result.into() {
+      utterance.set_state(UtteranceState::Delivered);
       Ok(())
     } else {
       Err(OutputError::into_speak_failed(
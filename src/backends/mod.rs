@@ -12,12 +12,55 @@ pub mod sapi;
 #[cfg(target_os = "linux")]
 pub mod speech_dispatcher;
 use crate::audio::SpeechResult;
+use crate::capitals::Capitals;
+use crate::characters;
 use crate::error::OutputError;
-use crate::metadata::{BrailleBackendMetadata, SpeechSynthesizerMetadata, Voice};
+use crate::keys::KeyCombination;
+use crate::lexicon::Lexicon;
+use crate::metadata::{BrailleBackendMetadata, SpeechSynthesizerMetadata, Voice, VoiceVariant};
+use crate::priority::Priority;
+use crate::punctuation::Punctuation;
+use crate::ssml::Ssml;
+use crate::utterance::{Utterance, UtteranceState};
+/// Backend-specific initialization options. Each backend reads only the options that apply to
+/// it and ignores the rest.
+#[derive(Clone, Debug, Default)]
+pub struct BackendOptions {
+  /// The directory containing the `espeak-ng-data` directory, for eSpeak NG.
+  pub data_path: Option<String>,
+  /// The path of the socket to connect to, for Speech Dispatcher.
+  pub socket_path: Option<String>,
+  /// The voice to use when no voice or language is requested.
+  pub default_voice: Option<String>,
+}
+/// A source of speech and/or Braille output. Backends are created and used only on the worker
+/// thread of a [`crate::Whisprs`] instance, so they do not need to be `Send` or `Sync`.
+///
+/// A backend exposes its capabilities through the `as_*` methods; returning `None` from all of
+/// them is allowed but makes the backend unusable. Custom backends can be added with
+/// [`crate::Whisprs::register_backend`].
 pub trait Backend {
+  /// Creates the backend with its default options.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error if the engine or screen reader is not available or fails to initialize.
   fn new() -> Result<Self, OutputError>
   where
     Self: Sized;
+  /// Creates the backend with options passed to [`crate::WhisprsBuilder::backend_options`].
+  /// Backends that take no options do not need to override this.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error if the backend cannot be initialized with `options`.
+  fn new_with_options(_options: &BackendOptions) -> Result<Self, OutputError>
+  where
+    Self: Sized,
+  {
+    Self::new()
+  }
+  /// The unique name the backend is registered under, such as `eSpeak NG`.
   fn name(&self) -> String;
   fn speech_metadata(&self) -> Option<SpeechSynthesizerMetadata> {
     match (
@@ -25,15 +68,17 @@ pub trait Backend {
       self.as_speech_synthesizer_to_audio_output(),
     ) {
       (None, None) => None,
-      (Some(synthesizer), _) => Some(SpeechSynthesizerMetadata {
+      (Some(synthesizer), output) => Some(SpeechSynthesizerMetadata {
         name: self.name(),
         supports_speaking_to_audio_data: true,
         supports_speech_parameters: synthesizer.supports_speech_parameters(),
+        supports_pausing: output.is_none_or(SpeechSynthesizerToAudioOutput::supports_pausing),
       }),
       (None, Some(synthesizer)) => Some(SpeechSynthesizerMetadata {
         name: self.name(),
         supports_speaking_to_audio_data: false,
         supports_speech_parameters: synthesizer.supports_speech_parameters(),
+        supports_pausing: synthesizer.supports_pausing(),
       }),
     }
   }
@@ -45,13 +90,71 @@ pub trait Backend {
         priority: backend.priority(),
       })
   }
+  /// Lists the voices of the backend. Voices with a lower priority are preferred when no
+  /// synthesizer is requested explicitly.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error if the backend fails to enumerate its voices.
   fn list_voices(&self) -> Result<Vec<Voice>, OutputError>;
+  /// Variants that can be applied to every voice of the backend. Backends without variants do
+  /// not need to override this.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error if the backend fails to enumerate its variants.
+  fn list_variants(&self) -> Result<Vec<VoiceVariant>, OutputError> {
+    Ok(Vec::new())
+  }
+  /// The name to pass to the speak methods to use a variant returned by
+  /// [`Backend::list_variants`] with a voice, or with the default voice if it is `None`.
+  fn voice_with_variant(&self, _voice: Option<&str>, _variant: &str) -> Option<String> {
+    None
+  }
+  /// Gives the backend the lexicon of the [`crate::Whisprs`] instance, so that it can apply the
+  /// entries it has its own pronunciations for, such as eSpeak NG phonemes, while synthesizing.
+  /// Returns whether it does, in which case those entries are not applied to text or SSML sent
+  /// to it.
+  /// Backends without their own pronunciations do not need to override this.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error if the backend cannot use the pronunciations of the lexicon.
+  fn set_lexicon(&self, _lexicon: &Lexicon) -> Result<bool, OutputError> {
+    Ok(false)
+  }
+  /// Sets how much punctuation is spoken from now on, or goes back to the default of the
+  /// backend if `punctuation` is `None`. Returns whether the backend speaks it itself; if not,
+  /// punctuation is replaced with its names in the text sent to it.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error if the backend fails to change the setting.
+  fn set_punctuation(&self, _punctuation: Option<&Punctuation>) -> Result<bool, OutputError> {
+    Ok(false)
+  }
+  /// Sets how capital letters are indicated from now on, or goes back to the default of the
+  /// backend if `capitals` is `None`. Returns whether the backend indicates them itself.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error if the backend fails to change the setting.
+  fn set_capitals(&self, _capitals: Option<Capitals>) -> Result<bool, OutputError> {
+    Ok(false)
+  }
   fn as_speech_synthesizer_to_audio_data(&self) -> Option<&dyn SpeechSynthesizerToAudioData>;
   fn as_speech_synthesizer_to_audio_output(&self) -> Option<&dyn SpeechSynthesizerToAudioOutput>;
   fn as_braille_backend(&self) -> Option<&dyn BrailleBackend>;
 }
+/// A synthesizer that returns PCM audio, which whisp-rs plays itself when speaking to the audio
+/// output.
 pub trait SpeechSynthesizerToAudioData {
   fn supports_speech_parameters(&self) -> bool;
+  /// Synthesizes `text` and returns all of its audio at once.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error if the voice or language is not found or synthesis fails.
   fn speak(
     &self,
     voice: Option<&str>,
@@ -61,9 +164,101 @@ pub trait SpeechSynthesizerToAudioData {
     pitch: Option<u8>,
     text: &str,
   ) -> Result<SpeechResult, OutputError>;
+  /// Synthesizes speech in chunks, passing each chunk to `on_chunk` as soon as it is available.
+  /// Marker positions are relative to the start of their chunk. Synthesis stops early when
+  /// `on_chunk` returns `false`. Backends that cannot stream return the whole speech as one
+  /// chunk.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error if the voice or language is not found or synthesis fails. Stopping early
+  /// because `on_chunk` returned `false` is not an error.
+  fn speak_streaming(
+    &self,
+    voice: Option<&str>,
+    language: Option<&str>,
+    rate: Option<u8>,
+    volume: Option<u8>,
+    pitch: Option<u8>,
+    text: &str,
+    on_chunk: &mut dyn FnMut(SpeechResult) -> bool,
+  ) -> Result<(), OutputError> {
+    on_chunk(self.speak(voice, language, rate, volume, pitch, text)?);
+    Ok(())
+  }
+  /// Like `speak_streaming`, but for SSML. Backends that do not support markup speak its text
+  /// content.
+  ///
+  /// # Errors
+  ///
+  /// Like `speak_streaming`, and also if the backend cannot parse the markup.
+  fn speak_ssml_streaming(
+    &self,
+    voice: Option<&str>,
+    language: Option<&str>,
+    rate: Option<u8>,
+    volume: Option<u8>,
+    pitch: Option<u8>,
+    ssml: &Ssml,
+    on_chunk: &mut dyn FnMut(SpeechResult) -> bool,
+  ) -> Result<(), OutputError> {
+    self.speak_streaming(
+      voice,
+      language,
+      rate,
+      volume,
+      pitch,
+      &ssml.to_plain_text(),
+      on_chunk,
+    )
+  }
+  /// Like `speak_streaming`, but spells the text one character at a time. Backends without a
+  /// character mode speak the names of the characters.
+  ///
+  /// # Errors
+  ///
+  /// Like `speak_streaming`.
+  fn spell_streaming(
+    &self,
+    voice: Option<&str>,
+    language: Option<&str>,
+    rate: Option<u8>,
+    volume: Option<u8>,
+    pitch: Option<u8>,
+    text: &str,
+    on_chunk: &mut dyn FnMut(SpeechResult) -> bool,
+  ) -> Result<(), OutputError> {
+    self.speak_streaming(
+      voice,
+      language,
+      rate,
+      volume,
+      pitch,
+      &characters::spell(text, false),
+      on_chunk,
+    )
+  }
 }
+/// A synthesizer that plays speech on its own, such as a screen reader.
+///
+/// `speak` receives the [`Utterance`] handle returned to the caller. Backends that can follow the
+/// progress of their speech update its state; the others set it to
+/// [`UtteranceState::Delivered`] once the text has been handed off.
+///
+/// Backends that queue speech by [`Priority`] themselves return `true` from
+/// `supports_priorities`. For the others, whisp-rs emulates priorities by setting `interrupt`
+/// whenever a message would cancel or postpone the previous one.
 pub trait SpeechSynthesizerToAudioOutput {
   fn supports_speech_parameters(&self) -> bool;
+  fn supports_priorities(&self) -> bool {
+    false
+  }
+  /// Hands `text` to the backend to speak, after speech of higher priority if the backend
+  /// queues by priority.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error if the voice or language is not found or the backend refuses the text.
   fn speak(
     &self,
     voice: Option<&str>,
@@ -73,10 +268,162 @@ pub trait SpeechSynthesizerToAudioOutput {
     pitch: Option<u8>,
     text: &str,
     interrupt: bool,
+    priority: Priority,
+    utterance: &Utterance,
   ) -> Result<(), OutputError>;
+  /// Like `speak`, but for SSML. Backends that do not support markup speak its text content.
+  ///
+  /// # Errors
+  ///
+  /// Like `speak`, and also if the backend cannot parse the markup.
+  fn speak_ssml(
+    &self,
+    voice: Option<&str>,
+    language: Option<&str>,
+    rate: Option<u8>,
+    volume: Option<u8>,
+    pitch: Option<u8>,
+    ssml: &Ssml,
+    interrupt: bool,
+    priority: Priority,
+    utterance: &Utterance,
+  ) -> Result<(), OutputError> {
+    self.speak(
+      voice,
+      language,
+      rate,
+      volume,
+      pitch,
+      &ssml.to_plain_text(),
+      interrupt,
+      priority,
+      utterance,
+    )
+  }
+  /// Like `speak`, but spells the text one character at a time. Backends without a character
+  /// mode speak the names of the characters.
+  ///
+  /// # Errors
+  ///
+  /// Like `speak`.
+  fn spell(
+    &self,
+    voice: Option<&str>,
+    language: Option<&str>,
+    rate: Option<u8>,
+    volume: Option<u8>,
+    pitch: Option<u8>,
+    text: &str,
+    interrupt: bool,
+    priority: Priority,
+    utterance: &Utterance,
+  ) -> Result<(), OutputError> {
+    self.speak(
+      voice,
+      language,
+      rate,
+      volume,
+      pitch,
+      &characters::spell(text, false),
+      interrupt,
+      priority,
+      utterance,
+    )
+  }
+  /// Speaks a key with a name chosen by the backend. Returns `false` if the backend cannot name
+  /// the key, in which case whisp-rs speaks the name of the key as text.
+  ///
+  /// # Errors
+  ///
+  /// Like `speak`.
+  fn speak_key(
+    &self,
+    _voice: Option<&str>,
+    _language: Option<&str>,
+    _rate: Option<u8>,
+    _volume: Option<u8>,
+    _pitch: Option<u8>,
+    _key: &KeyCombination,
+    _interrupt: bool,
+    _priority: Priority,
+    _utterance: &Utterance,
+  ) -> Result<bool, OutputError> {
+    Ok(false)
+  }
+  /// Stops all speech of the backend.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error if the backend fails to stop its speech.
   fn stop_speech(&self) -> Result<(), OutputError>;
+  /// Whether `pause` and `resume` are implemented. Speech of synthesizers that return audio data
+  /// can always be paused, since whisp-rs plays it itself.
+  fn supports_pausing(&self) -> bool {
+    false
+  }
+  /// Pauses all speech of the backend. Only called if `supports_pausing` returns `true`.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error if the backend fails to pause its speech.
+  fn pause(&self) -> Result<(), OutputError> {
+    Ok(())
+  }
+  /// Resumes speech paused with `pause`. Only called if `supports_pausing` returns `true`.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error if the backend fails to resume its speech.
+  fn resume(&self) -> Result<(), OutputError> {
+    Ok(())
+  }
+  /// Cancels one utterance. Backends that cannot cancel a single utterance stop all of their
+  /// speech instead.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error if the backend fails to stop the speech.
+  fn cancel(&self, utterance: &Utterance) -> Result<(), OutputError> {
+    self.stop_speech()?;
+    utterance.set_state(UtteranceState::Cancelled);
+    Ok(())
+  }
+  /// Whether the backend is currently speaking, not counting paused speech. Backends that cannot
+  /// tell return `false`.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error if the backend fails to report its state.
+  fn is_speaking(&self) -> Result<bool, OutputError> {
+    Ok(false)
+  }
+  /// Whether the backend has paused speech that is waiting to be resumed. Backends that cannot
+  /// tell return `false`.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error if the backend fails to report its state.
+  fn is_paused(&self) -> Result<bool, OutputError> {
+    Ok(false)
+  }
+  /// Called regularly on the worker thread while it is idle, so that the backend can process
+  /// notifications about its speech.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error if reading the notifications fails, for example because the connection to
+  /// the backend was lost.
+  fn poll(&self) -> Result<(), OutputError> {
+    Ok(())
+  }
 }
+/// A backend that can show messages on a Braille display.
 pub trait BrailleBackend {
   fn priority(&self) -> u8;
+  /// Shows `text` on the Braille display.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error if the text cannot be sent to the display.
   fn braille(&self, text: &str) -> Result<(), OutputError>;
 }
@@ -8,6 +8,8 @@ use crate::backends::{
 };
 use crate::error::OutputError;
 use crate::metadata::Voice;
+use crate::priority::Priority;
+use crate::utterance::{Utterance, UtteranceState};
 use windows::core::{Result, HSTRING};
 use windows::Win32::Foundation::WIN32_ERROR;
 fn to_result(error: u32) -> Result<()> {
@@ -28,9 +30,16 @@ impl Backend for Nvda {
   fn list_voices(&self) -> std::result::Result<Vec<Voice>, OutputError> {
     Ok(vec![Voice {
       synthesizer: self.speech_metadata().unwrap(),
+      id: "nvda".to_owned(),
       display_name: "NVDA".to_owned(),
       name: "nvda".to_owned(),
       languages: vec![],
+      gender: None,
+      age: None,
+      variant: None,
+      quality: None,
+      supports_audio_data: false,
+      sample_rate: None,
       priority: 0,
     }])
   }
@@ -57,6 +66,8 @@ impl SpeechSynthesizerToAudioOutput for Nvda {
     _pitch: Option<u8>,
     text: &str,
     interrupt: bool,
+    _priority: Priority,
+    utterance: &Utterance,
   ) -> std::result::Result<(), OutputError> {
     unsafe {
       if interrupt {
@@ -66,6 +77,7 @@ impl SpeechSynthesizerToAudioOutput for Nvda {
       let text = HSTRING::from(text);
       to_result(nvdaController_speakText(text.as_ptr()))
         .map_err(|err| OutputError::into_speak_failed(&self.name(), "nvda", err))?;
+      utterance.set_state(UtteranceState::Delivered);
       Ok(())
     }
   }
@@ -1,21 +1,28 @@
 use crate::audio::{SampleFormat, SpeechResult};
 use crate::backends::{
-  Backend, BrailleBackend, SpeechSynthesizerToAudioData, SpeechSynthesizerToAudioOutput,
+  Backend, BackendOptions, BrailleBackend, SpeechSynthesizerToAudioData,
+  SpeechSynthesizerToAudioOutput,
 };
 use crate::error::OutputError;
-use crate::metadata::Voice;
+use crate::language::{language_distance, normalize_language_tag};
+use crate::metadata::{Gender, Voice, VoiceQuality};
 use rodio::{Decoder, Source};
 use std::io::Cursor;
 use windows::core::{Interface, HSTRING};
-use windows::Media::SpeechSynthesis::SpeechSynthesizer;
+use windows::Media::SpeechSynthesis::{SpeechSynthesizer, VoiceGender};
 use windows::Storage::Streams::{Buffer, InputStreamOptions};
 use windows::Win32::System::WinRT::IMemoryBufferByteAccess;
 pub struct OneCore {
+  default_voice: Option<String>,
   synthesizer: SpeechSynthesizer,
 }
 impl Backend for OneCore {
   fn new() -> std::result::Result<Self, OutputError> {
+    OneCore::new_with_options(&BackendOptions::default())
+  }
+  fn new_with_options(options: &BackendOptions) -> std::result::Result<Self, OutputError> {
     Ok(OneCore {
+      default_voice: options.default_voice.clone(),
       synthesizer: SpeechSynthesizer::new().map_err(OutputError::into_unknown)?,
     })
   }
@@ -29,12 +36,24 @@ impl Backend for OneCore {
       .flat_map(|voice| {
         let display_name = voice.DisplayName()?.to_string();
         let name = voice.Id()?.to_string();
-        let languages = vec![voice.Language()?.to_string().to_lowercase()];
+        let languages = vec![normalize_language_tag(&voice.Language()?.to_string())];
+        let gender = match voice.Gender()? {
+          VoiceGender::Male => Some(Gender::Male),
+          VoiceGender::Female => Some(Gender::Female),
+          _ => None,
+        };
         Ok::<Voice, anyhow::Error>(Voice {
           synthesizer: self.speech_metadata().unwrap(),
+          id: "onecore:".to_owned() + &name,
           display_name,
           name,
           languages,
+          gender,
+          age: None,
+          variant: None,
+          quality: Some(VoiceQuality::Default),
+          supports_audio_data: true,
+          sample_rate: None,
           priority: 1,
         })
       })
@@ -65,6 +84,10 @@ impl SpeechSynthesizerToAudioData for OneCore {
     pitch: Option<u8>,
     text: &str,
   ) -> std::result::Result<SpeechResult, OutputError> {
+    let voice_name = match (voice_name, language) {
+      (None, None) => self.default_voice.as_deref(),
+      (voice_name, _) => voice_name,
+    };
     let voice = match (voice_name, language) {
       (None, None) => SpeechSynthesizer::DefaultVoice()
         .map_err(|err| OutputError::into_speak_failed(&self.name(), "default", err))?,
@@ -73,11 +96,18 @@ impl SpeechSynthesizerToAudioData for OneCore {
         .into_iter()
         .find(|voice| voice.Id().unwrap() == voice_name)
         .ok_or(OutputError::into_voice_not_found(voice_name))?,
-      (None, Some(language)) => SpeechSynthesizer::AllVoices()
-        .map_err(|err| OutputError::into_speak_failed(&self.name(), language, err))?
-        .into_iter()
-        .find(|voice| voice.Language().unwrap().to_string().to_lowercase() == language)
-        .ok_or(OutputError::into_language_not_found(language))?,
+      (None, Some(language)) => {
+        SpeechSynthesizer::AllVoices()
+          .map_err(|err| OutputError::into_speak_failed(&self.name(), language, err))?
+          .into_iter()
+          .filter_map(|voice| {
+            let distance = language_distance(language, &voice.Language().ok()?.to_string())?;
+            Some((distance, voice))
+          })
+          .min_by_key(|(distance, _)| *distance)
+          .ok_or(OutputError::into_language_not_found(language))?
+          .1
+      }
     };
     self.synthesizer.SetVoice(&voice).map_err(|err| {
       OutputError::into_speak_failed(
@@ -180,6 +210,7 @@ impl SpeechSynthesizerToAudioData for OneCore {
       pcm,
       sample_format: SampleFormat::S16,
       sample_rate,
+      markers: Vec::new(),
     })
   }
 }
@@ -1,26 +1,36 @@
 use crate::audio::{SampleFormat, SpeechResult};
 use crate::backends::{
-  Backend, BrailleBackend, SpeechSynthesizerToAudioData, SpeechSynthesizerToAudioOutput,
+  Backend, BackendOptions, BrailleBackend, SpeechSynthesizerToAudioData,
+  SpeechSynthesizerToAudioOutput,
 };
 use crate::error::OutputError;
-use crate::metadata::Voice;
+use crate::language::{best_language_distance, normalize_language_tag};
+use crate::metadata::{AgeGroup, Gender, Voice};
+use crate::priority::Priority;
+use crate::ssml::Ssml;
+use crate::utterance::{Utterance, UtteranceState};
 use anyhow::anyhow;
-use quick_xml::events::BytesText;
-use quick_xml::writer::Writer;
+use quick_xml::escape::escape;
+use std::cell::Cell;
 use std::collections::HashSet;
 use std::ffi::c_void;
-use std::io::Cursor;
-use windows::core::{w, GUID, PWSTR};
-use windows::Win32::Globalization::LCIDToLocaleName;
+use windows::core::{w, GUID, HSTRING, PWSTR};
+use windows::Win32::Globalization::{LCIDToLocaleName, LocaleNameToLCID};
 use windows::Win32::Media::Audio::{WAVEFORMATEX, WAVE_FORMAT_PCM};
 use windows::Win32::Media::Speech::{
   ISpObjectToken, ISpObjectTokenCategory, ISpStream, ISpVoice, SpObjectToken,
   SpObjectTokenCategory, SpStream, SpVoice, SPCAT_VOICES, SPF_ASYNC, SPF_IS_XML, SPF_PARSE_SAPI,
-  SPF_PURGEBEFORESPEAK,
+  SPF_PURGEBEFORESPEAK, SPRS_IS_SPEAKING, SPVOICESTATUS,
 };
 use windows::Win32::System::Com::{CoCreateInstance, CLSCTX_ALL, STREAM_SEEK_SET};
 use windows::Win32::System::SystemServices::LOCALE_NAME_MAX_LENGTH;
 use windows::Win32::UI::Shell::SHCreateMemStream;
+fn locale_name_to_lcid(language: &str) -> Option<u32> {
+  let lcid = unsafe { LocaleNameToLCID(&HSTRING::from(language), 0) };
+  (lcid != 0).then_some(lcid)
+}
+/// Selects the voice and sets the rate and volume, returning `xml` wrapped in the requested
+/// pitch.
 #[allow(clippy::too_many_lines)]
 fn set_parameters(
   synthesizer: &ISpVoice,
@@ -31,7 +41,7 @@ fn set_parameters(
   rate: Option<u8>,
   volume: Option<u8>,
   pitch: Option<u8>,
-  text: &str,
+  xml: &str,
 ) -> std::result::Result<String, OutputError> {
   unsafe {
     let voice_token = match (voice, language) {
@@ -78,7 +88,7 @@ fn set_parameters(
         tokens
           .into_iter()
           .flatten()
-          .find(|token| {
+          .filter_map(|token| {
             let closure = || {
               let attributes = token.OpenKey(w!("Attributes"))?;
               let lcid = attributes.GetStringValue(w!("Language"));
@@ -97,16 +107,18 @@ fn set_parameters(
                     let length = LCIDToLocaleName(lcid, Some(&mut name_vector), 0);
                     name_vector
                       .set_len((length - 1).try_into().map_err(OutputError::into_unknown)?);
-                    Ok::<String, anyhow::Error>(String::from_utf16(&name_vector)?.to_lowercase())
+                    Ok::<String, anyhow::Error>(String::from_utf16(&name_vector)?)
                   })
                   .collect::<Vec<String>>(),
                 _ => vec![],
               };
-              Ok::<bool, anyhow::Error>(languages.iter().any(|name| name == language))
+              Ok::<Option<u8>, anyhow::Error>(best_language_distance(language, &languages))
             };
-            closure().unwrap_or(false)
+            closure().ok().flatten().map(|distance| (distance, token))
           })
+          .min_by_key(|(distance, _)| *distance)
           .ok_or(OutputError::into_language_not_found(language))?
+          .1
       }
       (Some(voice), _) => {
         let token: ISpObjectToken = CoCreateInstance(&SpObjectToken, None, CLSCTX_ALL)
@@ -147,40 +159,41 @@ fn set_parameters(
       .try_into()
       .map_err(OutputError::into_unknown)?;
     let pitch = (pitch / 5) - 10;
-    let pitch = pitch.to_string();
-    let mut writer = Writer::new(Cursor::new(Vec::new()));
-    writer
-      .create_element("pitch")
-      .with_attribute(("absmiddle", pitch.as_str()))
-      .write_text_content(BytesText::new(text))
-      .map_err(OutputError::into_unknown)?;
-    let xml_vector = writer.into_inner().into_inner();
-    String::from_utf8(xml_vector).map_err(OutputError::into_unknown)
+    Ok(format!("<pitch absmiddle=\"{pitch}\">{xml}</pitch>"))
   }
 }
 pub struct Sapi {
   default_voice: String,
   stream_synthesizer: ISpVoice,
   playback_synthesizer: ISpVoice,
+  /// Whether speech was paused. SAPI reports paused speech as still speaking.
+  paused: Cell<bool>,
 }
 impl Backend for Sapi {
   fn new() -> std::result::Result<Self, OutputError> {
+    Sapi::new_with_options(&BackendOptions::default())
+  }
+  fn new_with_options(options: &BackendOptions) -> std::result::Result<Self, OutputError> {
     unsafe {
       let stream_synthesizer: ISpVoice =
         CoCreateInstance(&SpVoice, None, CLSCTX_ALL).map_err(OutputError::into_unknown)?;
       let playback_synthesizer: ISpVoice =
         CoCreateInstance(&SpVoice, None, CLSCTX_ALL).map_err(OutputError::into_unknown)?;
-      let default_voice = playback_synthesizer
-        .GetVoice()
-        .map_err(OutputError::into_unknown)?
-        .GetId()
-        .map_err(OutputError::into_unknown)?
-        .to_string()
-        .map_err(OutputError::into_unknown)?;
+      let default_voice = match &options.default_voice {
+        Some(default_voice) => default_voice.clone(),
+        None => playback_synthesizer
+          .GetVoice()
+          .map_err(OutputError::into_unknown)?
+          .GetId()
+          .map_err(OutputError::into_unknown)?
+          .to_string()
+          .map_err(OutputError::into_unknown)?,
+      };
       Ok(Sapi {
         default_voice,
         stream_synthesizer,
         playback_synthesizer,
+        paused: Cell::new(false),
       })
     }
   }
@@ -239,17 +252,45 @@ impl Backend for Sapi {
                 ];
                 let length = LCIDToLocaleName(lcid, Some(&mut name_vector), 0);
                 name_vector.set_len((length - 1).try_into().map_err(OutputError::into_unknown)?);
-                Ok::<String, anyhow::Error>(String::from_utf16(&name_vector)?.to_lowercase())
+                Ok::<String, anyhow::Error>(normalize_language_tag(&String::from_utf16(
+                  &name_vector,
+                )?))
               })
               .filter(|language| seen.insert(language.clone()))
               .collect::<Vec<String>>(),
             _ => vec![],
           };
+          let gender = match attributes.GetStringValue(w!("Gender")) {
+            Ok(gender) => match gender.to_string()?.as_str() {
+              "Male" => Some(Gender::Male),
+              "Female" => Some(Gender::Female),
+              "Neutral" => Some(Gender::Neutral),
+              _ => None,
+            },
+            _ => None,
+          };
+          let age = match attributes.GetStringValue(w!("Age")) {
+            Ok(age) => match age.to_string()?.as_str() {
+              "Child" => Some(AgeGroup::Child),
+              "Teen" => Some(AgeGroup::Teen),
+              "Adult" => Some(AgeGroup::Adult),
+              "Senior" => Some(AgeGroup::Senior),
+              _ => None,
+            },
+            _ => None,
+          };
           Ok::<Voice, anyhow::Error>(Voice {
             synthesizer: self.speech_metadata().unwrap(),
+            id: "sapi:".to_owned() + &name,
             display_name,
             name,
             languages,
+            gender,
+            age,
+            variant: None,
+            quality: None,
+            supports_audio_data: true,
+            sample_rate: Some(44100),
             priority: 2,
           })
         })
@@ -271,7 +312,6 @@ impl SpeechSynthesizerToAudioData for Sapi {
   fn supports_speech_parameters(&self) -> bool {
     true
   }
-  #[allow(clippy::cast_sign_loss)]
   fn speak(
     &self,
     voice: Option<&str>,
@@ -280,6 +320,135 @@ impl SpeechSynthesizerToAudioData for Sapi {
     volume: Option<u8>,
     pitch: Option<u8>,
     text: &str,
+  ) -> std::result::Result<SpeechResult, OutputError> {
+    self.synthesize(voice, language, rate, volume, pitch, &escape(text))
+  }
+  fn speak_ssml_streaming(
+    &self,
+    voice: Option<&str>,
+    language: Option<&str>,
+    rate: Option<u8>,
+    volume: Option<u8>,
+    pitch: Option<u8>,
+    ssml: &Ssml,
+    on_chunk: &mut dyn FnMut(SpeechResult) -> bool,
+  ) -> std::result::Result<(), OutputError> {
+    let xml = ssml.to_sapi_xml(&locale_name_to_lcid);
+    on_chunk(self.synthesize(voice, language, rate, volume, pitch, &xml)?);
+    Ok(())
+  }
+}
+impl SpeechSynthesizerToAudioOutput for Sapi {
+  fn supports_speech_parameters(&self) -> bool {
+    true
+  }
+  fn speak(
+    &self,
+    voice: Option<&str>,
+    language: Option<&str>,
+    rate: Option<u8>,
+    volume: Option<u8>,
+    pitch: Option<u8>,
+    text: &str,
+    interrupt: bool,
+    _priority: Priority,
+    utterance: &Utterance,
+  ) -> std::result::Result<(), OutputError> {
+    self.speak_xml(
+      voice,
+      language,
+      rate,
+      volume,
+      pitch,
+      &escape(text),
+      interrupt,
+      utterance,
+    )
+  }
+  fn speak_ssml(
+    &self,
+    voice: Option<&str>,
+    language: Option<&str>,
+    rate: Option<u8>,
+    volume: Option<u8>,
+    pitch: Option<u8>,
+    ssml: &Ssml,
+    interrupt: bool,
+    _priority: Priority,
+    utterance: &Utterance,
+  ) -> std::result::Result<(), OutputError> {
+    self.speak_xml(
+      voice,
+      language,
+      rate,
+      volume,
+      pitch,
+      &ssml.to_sapi_xml(&locale_name_to_lcid),
+      interrupt,
+      utterance,
+    )
+  }
+  fn stop_speech(&self) -> std::result::Result<(), OutputError> {
+    unsafe {
+      self
+        .playback_synthesizer
+        .Speak(None, SPF_PURGEBEFORESPEAK.0 as u32, None)
+        .map_err(|err| OutputError::into_stop_speech_failed(&self.name(), err))?;
+      Ok(())
+    }
+  }
+  fn is_speaking(&self) -> std::result::Result<bool, OutputError> {
+    Ok(!self.paused.get() && self.has_speech()?)
+  }
+  fn is_paused(&self) -> std::result::Result<bool, OutputError> {
+    Ok(self.paused.get() && self.has_speech()?)
+  }
+  fn supports_pausing(&self) -> bool {
+    true
+  }
+  fn pause(&self) -> std::result::Result<(), OutputError> {
+    unsafe {
+      self
+        .playback_synthesizer
+        .Pause()
+        .map_err(|err| OutputError::into_pause_failed(&self.name(), err))?;
+      self.paused.set(true);
+      Ok(())
+    }
+  }
+  fn resume(&self) -> std::result::Result<(), OutputError> {
+    unsafe {
+      self
+        .playback_synthesizer
+        .Resume()
+        .map_err(|err| OutputError::into_pause_failed(&self.name(), err))?;
+      self.paused.set(false);
+      Ok(())
+    }
+  }
+}
+impl Sapi {
+  /// Whether the playback voice has speech that is not done, whether it is paused or not.
+  #[allow(clippy::cast_sign_loss)]
+  fn has_speech(&self) -> std::result::Result<bool, OutputError> {
+    unsafe {
+      let mut status = SPVOICESTATUS::default();
+      self
+        .playback_synthesizer
+        .GetStatus(&mut status, std::ptr::null_mut())
+        .map_err(OutputError::into_unknown)?;
+      Ok(status.dwRunningState == SPRS_IS_SPEAKING.0 as u32)
+    }
+  }
+  #[allow(clippy::cast_sign_loss)]
+  fn synthesize(
+    &self,
+    voice: Option<&str>,
+    language: Option<&str>,
+    rate: Option<u8>,
+    volume: Option<u8>,
+    pitch: Option<u8>,
+    xml: &str,
   ) -> std::result::Result<SpeechResult, OutputError> {
     unsafe {
       let audio_stream = SHCreateMemStream(None).ok_or(OutputError::into_unknown(anyhow!(
@@ -321,7 +490,7 @@ impl SpeechSynthesizerToAudioData for Sapi {
         rate,
         volume,
         pitch,
-        text,
+        xml,
       )?;
       let mut xml = xml_string
         .encode_utf16()
@@ -365,24 +534,21 @@ impl SpeechSynthesizerToAudioData for Sapi {
         pcm,
         sample_format: SampleFormat::S16,
         sample_rate: 44100,
+        markers: Vec::new(),
       })
     }
   }
-}
-impl SpeechSynthesizerToAudioOutput for Sapi {
-  fn supports_speech_parameters(&self) -> bool {
-    true
-  }
   #[allow(clippy::cast_sign_loss)]
-  fn speak(
+  fn speak_xml(
     &self,
     voice: Option<&str>,
     language: Option<&str>,
     rate: Option<u8>,
     volume: Option<u8>,
     pitch: Option<u8>,
-    text: &str,
+    xml: &str,
     interrupt: bool,
+    utterance: &Utterance,
   ) -> std::result::Result<(), OutputError> {
     unsafe {
       let xml_string = set_parameters(
@@ -394,7 +560,7 @@ impl SpeechSynthesizerToAudioOutput for Sapi {
         rate,
         volume,
         pitch,
-        text,
+        xml,
       )?;
       let mut xml = xml_string
         .encode_utf16()
@@ -415,15 +581,7 @@ impl SpeechSynthesizerToAudioOutput for Sapi {
             err,
           )
         })?;
-      Ok(())
-    }
-  }
-  fn stop_speech(&self) -> std::result::Result<(), OutputError> {
-    unsafe {
-      self
-        .playback_synthesizer
-        .Speak(None, SPF_PURGEBEFORESPEAK.0 as u32, None)
-        .map_err(|err| OutputError::into_stop_speech_failed(&self.name(), err))?;
+      utterance.set_state(UtteranceState::Delivered);
       Ok(())
     }
   }
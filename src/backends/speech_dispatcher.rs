@@ -1,77 +1,715 @@
 use crate::backends::{
-  Backend, BrailleBackend, SpeechSynthesizerToAudioData, SpeechSynthesizerToAudioOutput,
+  Backend, BackendOptions, BrailleBackend, SpeechSynthesizerToAudioData,
+  SpeechSynthesizerToAudioOutput,
 };
+use crate::capitals::Capitals;
 use crate::error::OutputError;
+use crate::events::SpeechEventKind;
+use crate::keys::{Key, KeyCombination};
+use crate::language::{best_language_distance, normalize_language_tag};
 use crate::metadata::Voice;
+use crate::priority::Priority;
+use crate::punctuation::Punctuation;
+use crate::ssml::Ssml;
+use crate::utterance::{Utterance, UtteranceState};
+use anyhow::anyhow;
+#[cfg(feature = "async")]
+use ssip_client_async::async_std::AsyncClient;
+#[cfg(not(feature = "async"))]
+use ssip_client_async::{fifo, QueuedClient};
 use ssip_client_async::{
-  fifo, Client, ClientName, ClientScope, MessageScope, OK_CANCELED, OK_GET, OK_LANGUAGE_SET,
-  OK_OUTPUT_MODULES_LIST_SENT, OK_OUTPUT_MODULE_SET, OK_PITCH_SET, OK_RATE_SET, OK_VOICE_SET,
-  OK_VOLUME_SET,
+  CapitalLettersRecognitionMode, ClientError, ClientName, ClientResult, ClientScope, KeyName,
+  MessageId, MessageScope, NotificationType, PunctuationMode, Request, ReturnCode, StatusLine,
+  SynthesisVoice, EVENT_BEGIN, EVENT_CANCELED, EVENT_END, EVENT_INDEX_MARK, EVENT_PAUSED,
+  EVENT_RESUMED, OK_CANCELED, OK_CAP_LET_RECOGN_SET, OK_CLIENT_NAME_SET, OK_GET, OK_LANGUAGE_SET,
+  OK_MESSAGE_QUEUED, OK_NOTIFICATION_SET, OK_OUTPUT_MODULES_LIST_SENT, OK_OUTPUT_MODULE_SET,
+  OK_PAUSED, OK_PITCH_SET, OK_PRIORITY_SET, OK_PUNCTUATION_SET, OK_RATE_SET, OK_RECEIVING_DATA,
+  OK_RESUMED, OK_SPELLING_SET, OK_SSML_MODE_SET, OK_VOICES_LIST_SENT, OK_VOICE_SET, OK_VOLUME_SET,
 };
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::io;
+#[cfg(any(not(feature = "async"), test))]
+use std::io::BufRead;
+#[cfg(not(feature = "async"))]
+use std::io::BufReader;
+#[cfg(feature = "async")]
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+/// How long to wait for the answer to a request before giving up on the server.
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+fn to_ssip_priority(priority: Priority) -> ssip_client_async::Priority {
+  match priority {
+    Priority::Important => ssip_client_async::Priority::Important,
+    Priority::Message => ssip_client_async::Priority::Message,
+    Priority::Text => ssip_client_async::Priority::Text,
+    Priority::Notification => ssip_client_async::Priority::Notification,
+    Priority::Progress => ssip_client_async::Priority::Progress,
+  }
+}
+/// The SSIP name of a key, if Speech Dispatcher can speak it. Keys pressed with modifiers are
+/// named by whisp-rs, since SSIP only names single keys.
+fn key_name(key: &KeyCombination) -> Option<KeyName> {
+  if !key.modifiers.is_empty() {
+    return None;
+  }
+  Some(match key.key {
+    Key::Character(' ') | Key::Space => KeyName::Space,
+    Key::Character('_') => KeyName::Underscore,
+    Key::Character('"') => KeyName::DoubleQuote,
+    Key::Enter => KeyName::Enter,
+    Key::Tab => KeyName::Tab,
+    Key::Escape => KeyName::Escape,
+    Key::Backspace => KeyName::Backspace,
+    Key::Delete => KeyName::Delete,
+    Key::Insert => KeyName::Insert,
+    Key::Home => KeyName::Home,
+    Key::End => KeyName::End,
+    Key::PageUp => KeyName::Prior,
+    Key::PageDown => KeyName::Next,
+    Key::Up => KeyName::Up,
+    Key::Down => KeyName::Down,
+    Key::Left => KeyName::Left,
+    Key::Right => KeyName::Right,
+    Key::Function(number) => match number {
+      1 => KeyName::F1,
+      2 => KeyName::F2,
+      3 => KeyName::F3,
+      4 => KeyName::F4,
+      5 => KeyName::F5,
+      6 => KeyName::F6,
+      7 => KeyName::F7,
+      8 => KeyName::F8,
+      9 => KeyName::F9,
+      10 => KeyName::F10,
+      11 => KeyName::F11,
+      12 => KeyName::F12,
+      13 => KeyName::F13,
+      14 => KeyName::F14,
+      15 => KeyName::F15,
+      16 => KeyName::F16,
+      17 => KeyName::F17,
+      18 => KeyName::F18,
+      19 => KeyName::F19,
+      20 => KeyName::F20,
+      21 => KeyName::F21,
+      22 => KeyName::F22,
+      23 => KeyName::F23,
+      24 => KeyName::F24,
+      _ => return None,
+    },
+    Key::Menu => KeyName::Menu,
+    Key::Pause => KeyName::Pause,
+    Key::PrintScreen => KeyName::Print,
+    Key::NumLock => KeyName::NumLock,
+    Key::ScrollLock => KeyName::ScrollLock,
+    Key::Character(_) | Key::CapsLock => return None,
+  })
+}
+/// What a message sent to Speech Dispatcher contains.
+#[derive(Clone, Copy)]
+enum Message<'a> {
+  Text(&'a str),
+  Ssml(&'a str),
+  /// Text spoken in spelling mode.
+  Spelled(&'a str),
+  Character(char),
+  Key(&'a KeyName),
+}
+/// An answer or event notification from the server: the code of its status line and the lines of
+/// data before it.
+struct Answer {
+  code: ReturnCode,
+  lines: Vec<String>,
+}
+impl Answer {
+  /// The only line of data of the answer, such as the value asked for with `GET`.
+  fn value(self) -> ClientResult<String> {
+    let mut lines = self.lines.into_iter();
+    match (lines.next(), lines.next()) {
+      (None, _) => Err(ClientError::TooFewLines),
+      (Some(value), None) => Ok(value),
+      (Some(_), Some(_)) => Err(ClientError::TooManyLines),
+    }
+  }
+}
+/// Reads answers from a clone of the socket of the client. [`ssip_client_async::Client`] returns
+/// answers as responses, which drop the identifier of a queued message, and it discards event
+/// notifications while it waits for such an identifier. A line cut off by a read timeout is kept
+/// until the rest of it arrives.
+struct Answers<R> {
+  input: R,
+  line: Vec<u8>,
+  lines: Vec<String>,
+}
+impl<R> Answers<R> {
+  fn new(input: R) -> Self {
+    Answers {
+      input,
+      line: Vec::new(),
+      lines: Vec::new(),
+    }
+  }
+  /// Handles a line that was read completely, and returns the answer it ends if it is a status
+  /// line. Error statuses are returned as [`ClientError::Ssip`].
+  fn complete_line(&mut self) -> ClientResult<Option<Answer>> {
+    let line = String::from_utf8(std::mem::take(&mut self.line))
+      .map_err(|_| ClientError::invalid_data("invalid UTF-8 in answer"))?;
+    let line = line.trim_end_matches(['\r', '\n']);
+    let code = line
+      .get(..3)
+      .and_then(|code| code.parse::<ReturnCode>().ok())
+      .ok_or_else(|| ClientError::invalid_data("invalid status code in answer"))?;
+    let text = line.get(4..).unwrap_or_default();
+    match line.get(3..4) {
+      Some("-") => {
+        self.lines.push(text.to_owned());
+        Ok(None)
+      }
+      Some(" ") if (300..700).contains(&code) => {
+        self.lines.clear();
+        Err(ClientError::Ssip(StatusLine {
+          code,
+          message: text.trim_start_matches("ERR ").to_owned(),
+        }))
+      }
+      Some(" ") => Ok(Some(Answer {
+        code,
+        lines: std::mem::take(&mut self.lines),
+      })),
+      _ => Err(ClientError::invalid_data("invalid line in answer")),
+    }
+  }
+}
+#[cfg(any(not(feature = "async"), test))]
+impl<R: BufRead> Answers<R> {
+  /// Reads the next answer, or returns `None` if it did not arrive before the socket timed out.
+  fn read(&mut self) -> ClientResult<Option<Answer>> {
+    loop {
+      match self.input.read_until(b'\n', &mut self.line) {
+        Ok(0) => {
+          return Err(ClientError::io_error(
+            io::ErrorKind::UnexpectedEof,
+            "connection closed by Speech Dispatcher",
+          ))
+        }
+        Ok(_) if self.line.ends_with(b"\n") => {
+          if let Some(answer) = self.complete_line()? {
+            return Ok(Some(answer));
+          }
+        }
+        Ok(_) => {}
+        Err(error)
+          if matches!(
+            error.kind(),
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+          ) =>
+        {
+          return Ok(None)
+        }
+        Err(error) => return Err(ClientError::Io(error)),
+      }
+    }
+  }
+}
+#[cfg(feature = "async")]
+impl<R: async_std::io::BufRead + Unpin> Answers<R> {
+  async fn read_async(&mut self) -> ClientResult<Answer> {
+    use async_std::io::prelude::BufReadExt;
+    loop {
+      if self.input.read_until(b'\n', &mut self.line).await? == 0 {
+        return Err(ClientError::io_error(
+          io::ErrorKind::UnexpectedEof,
+          "connection closed by Speech Dispatcher",
+        ));
+      }
+      if let Some(answer) = self.complete_line()? {
+        return Ok(answer);
+      }
+    }
+  }
+}
+/// The messages of the client that are not done yet.
+#[derive(Default)]
+struct Messages {
+  /// The utterance of the message being sent, until the server answers with its identifier.
+  queued: Option<Utterance>,
+  utterances: HashMap<MessageId, Utterance>,
+}
+impl Messages {
+  /// Updates the utterance an event notification refers to, and starts tracking the queued
+  /// message once its identifier arrives. Returns answers that are not events.
+  fn handle(&mut self, answer: Answer) -> Option<Answer> {
+    let (state, kind) = match answer.code {
+      OK_MESSAGE_QUEUED => {
+        let message_id = answer.lines.first().and_then(|id| id.parse().ok());
+        if let (Some(message_id), Some(utterance)) = (message_id, self.queued.take()) {
+          self.utterances.insert(message_id, utterance);
+        }
+        return Some(answer);
+      }
+      EVENT_BEGIN | EVENT_RESUMED => (Some(UtteranceState::Speaking), None),
+      EVENT_END => (Some(UtteranceState::Finished), None),
+      EVENT_CANCELED => (Some(UtteranceState::Cancelled), None),
+      EVENT_PAUSED => (None, None),
+      EVENT_INDEX_MARK => (
+        None,
+        answer.lines.get(2).cloned().map(SpeechEventKind::Mark),
+      ),
+      _ => return Some(answer),
+    };
+    let message_id = answer
+      .lines
+      .first()
+      .and_then(|id| id.parse::<MessageId>().ok());
+    let utterance = match message_id {
+      Some(message_id) if state.is_some_and(UtteranceState::is_done) => {
+        self.utterances.remove(&message_id)
+      }
+      Some(message_id) => self.utterances.get(&message_id).cloned(),
+      None => None,
+    };
+    if let Some(utterance) = utterance {
+      if let Some(kind) = kind {
+        utterance.emit(kind);
+      }
+      if let Some(state) = state {
+        utterance.set_state(state);
+      }
+    }
+    None
+  }
+}
+fn lock_messages(messages: &Mutex<Messages>) -> MutexGuard<'_, Messages> {
+  messages.lock().unwrap_or_else(PoisonError::into_inner)
+}
+/// The connection to the server. Answers are read on the worker thread while it waits for them,
+/// and event notifications that arrive in between are read when the backend is polled.
+#[cfg(not(feature = "async"))]
+struct Connection {
+  client: RefCell<QueuedClient<fifo::UnixStream>>,
+  answers: RefCell<Answers<BufReader<fifo::UnixStream>>>,
+  messages: Arc<Mutex<Messages>>,
+}
+#[cfg(not(feature = "async"))]
+impl Connection {
+  fn new(options: &BackendOptions, messages: Arc<Mutex<Messages>>) -> io::Result<Self> {
+    let mut builder = fifo::Builder::new();
+    if let Some(socket_path) = &options.socket_path {
+      builder.path(socket_path);
+    }
+    let client = QueuedClient::new(builder.timeout(READ_TIMEOUT).build()?);
+    let input = client.input_source().try_clone()?;
+    Ok(Connection {
+      client: RefCell::new(client),
+      answers: RefCell::new(Answers::new(BufReader::new(input))),
+      messages,
+    })
+  }
+  fn send(&self, request: Request) -> ClientResult<()> {
+    let mut client = self.client.borrow_mut();
+    client.push(request);
+    client.send_next()?;
+    Ok(())
+  }
+  /// Returns the next answer that is not an event notification, or `None` if it did not arrive
+  /// in time. Event notifications are handled on the way.
+  fn receive(&self) -> ClientResult<Option<Answer>> {
+    loop {
+      let Some(answer) = self.answers.borrow_mut().read()? else {
+        return Ok(None);
+      };
+      if let Some(answer) = lock_messages(&self.messages).handle(answer) {
+        return Ok(Some(answer));
+      }
+    }
+  }
+  /// Handles the event notifications that arrived since the last answer, without waiting for
+  /// more.
+  fn poll(&self) -> ClientResult<()> {
+    let mut answers = self.answers.borrow_mut();
+    answers.input.get_ref().set_nonblocking(true)?;
+    let result = loop {
+      match answers.read() {
+        Ok(Some(answer)) => {
+          let _answer = lock_messages(&self.messages).handle(answer);
+        }
+        Ok(None) => break Ok(()),
+        Err(error) => break Err(error),
+      }
+    };
+    answers.input.get_ref().set_nonblocking(false)?;
+    result
+  }
+}
+/// The connection to the server, driven by tasks of the asynchronous SSIP client. Event
+/// notifications are handled as soon as they arrive, and other answers are handed to the worker
+/// thread.
+#[cfg(feature = "async")]
+struct Connection {
+  stream: async_std::os::unix::net::UnixStream,
+  requests: async_std::channel::Sender<Request>,
+  answers: mpsc::Receiver<ClientResult<Answer>>,
+}
+#[cfg(feature = "async")]
+impl Connection {
+  fn new(options: &BackendOptions, messages: Arc<Mutex<Messages>>) -> io::Result<Self> {
+    let socket_path = match &options.socket_path {
+      Some(socket_path) => socket_path.into(),
+      None => std::env::var_os("XDG_RUNTIME_DIR")
+        .map(|runtime_dir| {
+          std::path::PathBuf::from(runtime_dir).join("speech-dispatcher/speechd.sock")
+        })
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "unix socket not found"))?,
+    };
+    let stream = async_std::os::unix::net::UnixStream::from(
+      std::os::unix::net::UnixStream::connect(socket_path)?,
+    );
+    let (requests, request_receiver) = async_std::channel::unbounded::<Request>();
+    let (answer_sender, answers) = mpsc::channel();
+    // The client only writes requests. Its answers are read with `Answers`, because the client
+    // drops message identifiers as well.
+    let mut client = AsyncClient::new(async_std::io::empty(), stream.clone());
+    let write_error_sender = answer_sender.clone();
+    async_std::task::spawn(async move {
+      while let Ok(request) = request_receiver.recv().await {
+        if let Err(error) = client.send(request).await {
+          let _result = write_error_sender.send(Err(error));
+          break;
+        }
+      }
+    });
+    let mut reader = Answers::new(async_std::io::BufReader::new(stream.clone()));
+    async_std::task::spawn(async move {
+      loop {
+        let answer = match reader.read_async().await {
+          Ok(answer) => match lock_messages(&messages).handle(answer) {
+            Some(answer) => Ok(answer),
+            None => continue,
+          },
+          Err(error) => Err(error),
+        };
+        let closed = matches!(answer, Err(ClientError::Io(_)));
+        if answer_sender.send(answer).is_err() || closed {
+          break;
+        }
+      }
+    });
+    Ok(Connection {
+      stream,
+      requests,
+      answers,
+    })
+  }
+  fn send(&self, request: Request) -> ClientResult<()> {
+    self.requests.try_send(request).map_err(|_| {
+      ClientError::io_error(
+        io::ErrorKind::BrokenPipe,
+        "connection to Speech Dispatcher closed",
+      )
+    })
+  }
+  /// Returns the next answer that is not an event notification, or `None` if it did not arrive
+  /// in time.
+  fn receive(&self) -> ClientResult<Option<Answer>> {
+    match self.answers.recv_timeout(READ_TIMEOUT) {
+      Ok(answer) => answer.map(Some),
+      Err(mpsc::RecvTimeoutError::Timeout) => Ok(None),
+      Err(mpsc::RecvTimeoutError::Disconnected) => Err(ClientError::io_error(
+        io::ErrorKind::BrokenPipe,
+        "connection to Speech Dispatcher closed",
+      )),
+    }
+  }
+  /// Event notifications are handled by the reading task as they arrive.
+  #[allow(clippy::unnecessary_wraps)]
+  #[allow(clippy::unused_self)]
+  fn poll(&self) -> ClientResult<()> {
+    Ok(())
+  }
+}
+#[cfg(feature = "async")]
+impl Drop for Connection {
+  /// Ends the reading task, which would otherwise wait for answers until the server exits.
+  fn drop(&mut self) {
+    let _result = self.stream.shutdown(std::net::Shutdown::Both);
+  }
+}
 pub struct SpeechDispatcher {
   default_output_module: String,
   default_language: String,
-  client: RefCell<Client<fifo::UnixStream>>,
+  default_voice: Option<String>,
+  connection: Connection,
+  messages: Arc<Mutex<Messages>>,
+  paused: Cell<bool>,
+  punctuation: RefCell<Option<Punctuation>>,
+  capitals: Cell<Option<Capitals>>,
+}
+impl SpeechDispatcher {
+  /// Sends a request and returns its answer, which must have the status `expected`. Event
+  /// notifications that arrive before the answer are handled on the way.
+  fn request(&self, request: Request, expected: ReturnCode) -> Result<Answer, anyhow::Error> {
+    self.connection.send(request)?;
+    let answer = self
+      .connection
+      .receive()?
+      .ok_or_else(|| anyhow!("Speech Dispatcher did not answer in time"))?;
+    if answer.code != expected {
+      return Err(ClientError::UnexpectedStatus(answer.code).into());
+    }
+    Ok(answer)
+  }
+  fn messages(&self) -> MutexGuard<'_, Messages> {
+    lock_messages(&self.messages)
+  }
+  fn set(&self, request: Request, expected: ReturnCode) -> Result<(), anyhow::Error> {
+    self.request(request, expected)?;
+    Ok(())
+  }
+  /// Sends a request for each tracked message, so that messages of other clients are not
+  /// affected. Messages that are already done when the request arrives are skipped by the server.
+  fn set_for_messages(
+    &self,
+    request: fn(MessageScope) -> Request,
+    expected: ReturnCode,
+  ) -> Result<(), anyhow::Error> {
+    let message_ids: Vec<MessageId> = self.messages().utterances.keys().copied().collect();
+    for message_id in message_ids {
+      match self.set(request(MessageScope::Message(message_id)), expected) {
+        Err(error) if matches!(error.downcast_ref(), Some(ClientError::Ssip(_))) => {}
+        result => result?,
+      }
+    }
+    Ok(())
+  }
+  fn get(&self, request: Request) -> Result<String, anyhow::Error> {
+    Ok(self.request(request, OK_GET)?.value()?)
+  }
+  /// Sends a request that queues the message of `utterance`, which is tracked from then on.
+  fn queue(&self, request: Request, utterance: &Utterance) -> Result<(), anyhow::Error> {
+    self.messages().queued = Some(utterance.clone());
+    let result = self.set(request, OK_MESSAGE_QUEUED);
+    if self.messages().queued.take().is_some() && result.is_ok() {
+      return Err(anyhow!(
+        "Speech Dispatcher did not send a message identifier"
+      ));
+    }
+    result
+  }
+  /// Sends a message with the given speech parameters.
+  #[allow(clippy::cast_possible_wrap)]
+  #[allow(clippy::too_many_lines)]
+  fn send_message(
+    &self,
+    voice: Option<&str>,
+    language: Option<&str>,
+    rate: Option<u8>,
+    volume: Option<u8>,
+    pitch: Option<u8>,
+    message: Message,
+    interrupt: bool,
+    priority: Priority,
+    utterance: &Utterance,
+  ) -> std::result::Result<(), OutputError> {
+    let voice = match (voice, language) {
+      (None, None) => self.default_voice.clone(),
+      (Some(voice), _) => Some(voice.to_owned()),
+      (_, Some(language)) => Some(
+        self
+          .list_voices()?
+          .into_iter()
+          .filter_map(|voice| {
+            best_language_distance(language, &voice.languages).map(|distance| (distance, voice))
+          })
+          .min_by_key(|(distance, _)| *distance)
+          .ok_or(OutputError::into_language_not_found(language))?
+          .1
+          .name,
+      ),
+    };
+    let speak_failed = |err| {
+      OutputError::into_speak_failed(
+        &self.name(),
+        voice.as_deref().unwrap_or(&self.default_output_module),
+        err,
+      )
+    };
+    match voice {
+      None => {
+        self
+          .set(
+            Request::SetOutputModule(ClientScope::Current, self.default_output_module.clone()),
+            OK_OUTPUT_MODULE_SET,
+          )
+          .map_err(speak_failed)?;
+        self
+          .set(
+            Request::SetLanguage(ClientScope::Current, self.default_language.clone()),
+            OK_LANGUAGE_SET,
+          )
+          .map_err(speak_failed)?;
+      }
+      Some(ref voice) => {
+        let mut split = voice.split('/');
+        let output_module = split.next().unwrap();
+        let voice = split.next().unwrap();
+        self
+          .set(
+            Request::SetOutputModule(ClientScope::Current, output_module.to_owned()),
+            OK_OUTPUT_MODULE_SET,
+          )
+          .map_err(speak_failed)?;
+        self
+          .set(
+            Request::SetSynthesisVoice(ClientScope::Current, voice.to_owned()),
+            OK_VOICE_SET,
+          )
+          .map_err(speak_failed)?;
+      }
+    }
+    let rate = rate.unwrap_or(50) as i8;
+    let rate = (rate * 2) - 100;
+    self
+      .set(Request::SetRate(ClientScope::Current, rate), OK_RATE_SET)
+      .map_err(speak_failed)?;
+    let pitch = pitch.unwrap_or(50) as i8;
+    let pitch = (pitch * 2) - 100;
+    self
+      .set(Request::SetPitch(ClientScope::Current, pitch), OK_PITCH_SET)
+      .map_err(speak_failed)?;
+    let volume = volume.unwrap_or(50) as i8;
+    let volume = (volume * 2) - 100;
+    self
+      .set(
+        Request::SetVolume(ClientScope::Current, volume),
+        OK_VOLUME_SET,
+      )
+      .map_err(speak_failed)?;
+    if interrupt {
+      self.stop_speech()?;
+    }
+    self
+      .set(
+        Request::SetPriority(to_ssip_priority(priority)),
+        OK_PRIORITY_SET,
+      )
+      .map_err(speak_failed)?;
+    self
+      .set(
+        Request::SetSsmlMode(matches!(message, Message::Ssml(_))),
+        OK_SSML_MODE_SET,
+      )
+      .map_err(speak_failed)?;
+    let spelled = matches!(message, Message::Spelled(_));
+    if spelled {
+      self
+        .set(
+          Request::SetSpelling(ClientScope::Current, true),
+          OK_SPELLING_SET,
+        )
+        .map_err(speak_failed)?;
+    }
+    let request = match message {
+      Message::Character(character) => Request::SpeakChar(character),
+      Message::Key(key) => Request::SpeakKey(key.clone()),
+      Message::Text(text) | Message::Ssml(text) | Message::Spelled(text) => {
+        self
+          .set(Request::Speak, OK_RECEIVING_DATA)
+          .map_err(speak_failed)?;
+        Request::SendLines(text.lines().map(std::borrow::ToOwned::to_owned).collect())
+      }
+    };
+    let queued = self.queue(request, utterance).map_err(speak_failed);
+    if spelled {
+      self
+        .set(
+          Request::SetSpelling(ClientScope::Current, false),
+          OK_SPELLING_SET,
+        )
+        .map_err(speak_failed)?;
+    }
+    queued
+  }
 }
 impl Backend for SpeechDispatcher {
   fn new() -> Result<Self, OutputError> {
-    let mut client = fifo::Builder::new()
-      .build()
+    SpeechDispatcher::new_with_options(&BackendOptions::default())
+  }
+  fn new_with_options(options: &BackendOptions) -> Result<Self, OutputError> {
+    let messages = Arc::new(Mutex::new(Messages::default()));
+    let connection =
+      Connection::new(options, messages.clone()).map_err(OutputError::into_unknown)?;
+    let mut result = SpeechDispatcher {
+      default_output_module: String::new(),
+      default_language: String::new(),
+      default_voice: options.default_voice.clone(),
+      connection,
+      messages,
+      paused: Cell::new(false),
+      punctuation: RefCell::new(None),
+      capitals: Cell::new(None),
+    };
+    result
+      .set(
+        Request::SetName(ClientName::new("", "whisp-rs")),
+        OK_CLIENT_NAME_SET,
+      )
       .map_err(OutputError::into_unknown)?;
-    client
-      .set_client_name(ClientName::new("", "whisp-rs"))
-      .map_err(OutputError::into_unknown)?
-      .check_client_name_set()
+    result
+      .set(
+        Request::SetNotification(NotificationType::All, true),
+        OK_NOTIFICATION_SET,
+      )
       .map_err(OutputError::into_unknown)?;
-    let default_output_module = client
-      .get_output_module()
-      .map_err(OutputError::into_unknown)?
-      .receive_string(OK_GET)
+    result.default_output_module = result
+      .get(Request::GetOutputModule)
       .map_err(OutputError::into_unknown)?;
-    let default_language = client
-      .get_language()
-      .map_err(OutputError::into_unknown)?
-      .receive_string(OK_GET)
+    result.default_language = result
+      .get(Request::GetLanguage)
       .map_err(OutputError::into_unknown)?;
-    Ok(SpeechDispatcher {
-      default_output_module,
-      default_language,
-      client: RefCell::new(client),
-    })
+    Ok(result)
   }
   fn name(&self) -> String {
     "Speech Dispatcher".to_owned()
   }
   fn list_voices(&self) -> Result<Vec<Voice>, OutputError> {
-    let mut client = self.client.borrow_mut();
-    let voices = client
-      .list_output_modules()
-      .map_err(OutputError::into_unknown)?
-      .receive_lines(OK_OUTPUT_MODULES_LIST_SENT)
+    let modules = self
+      .request(Request::ListOutputModules, OK_OUTPUT_MODULES_LIST_SENT)
       .map_err(OutputError::into_unknown)?
+      .lines;
+    let voices = modules
       .into_iter()
       .flat_map(|module| {
-        client
-          .set_output_module(ClientScope::Current, &module)?
-          .check_status(OK_OUTPUT_MODULE_SET)?;
-        client
-          .list_synthesis_voices()?
-          .receive_synthesis_voices()?
-          .into_iter()
+        self.set(
+          Request::SetOutputModule(ClientScope::Current, module.clone()),
+          OK_OUTPUT_MODULE_SET,
+        )?;
+        self
+          .request(Request::ListSynthesisVoices, OK_VOICES_LIST_SENT)?
+          .lines
+          .iter()
           .map(|voice| {
+            let voice = voice.parse::<SynthesisVoice>()?;
             let name = voice.name;
             let languages = match voice.language {
-              Some(language) => vec![language.to_lowercase().replace('_', "-")],
+              Some(language) => vec![normalize_language_tag(&language)],
               None => Vec::new(),
             };
             let display_name = name.clone() + " (" + &module + ")";
             let name = module.clone() + "/" + &name;
             Ok(Voice {
               synthesizer: self.speech_metadata().unwrap(),
+              id: "speech-dispatcher:".to_owned() + &name,
               display_name,
               name,
               languages,
+              gender: None,
+              age: None,
+              variant: voice.dialect,
+              quality: None,
+              supports_audio_data: false,
+              sample_rate: None,
               priority: 1,
             })
           })
@@ -81,6 +719,59 @@ impl Backend for SpeechDispatcher {
       .collect();
     Ok(voices)
   }
+  /// Custom character sets have no SSIP equivalent, so Speech Dispatcher speaks no punctuation
+  /// and the characters are replaced in the text instead.
+  fn set_punctuation(&self, punctuation: Option<&Punctuation>) -> Result<bool, OutputError> {
+    let native = !matches!(punctuation, Some(Punctuation::Custom(_)));
+    let punctuation = if native {
+      punctuation.cloned()
+    } else {
+      Some(Punctuation::None)
+    };
+    if *self.punctuation.borrow() == punctuation {
+      return Ok(native);
+    }
+    let mode = match punctuation {
+      None | Some(Punctuation::None | Punctuation::Custom(_)) => PunctuationMode::None,
+      Some(Punctuation::Some) => PunctuationMode::Some,
+      Some(Punctuation::Most) => PunctuationMode::Most,
+      Some(Punctuation::All) => PunctuationMode::All,
+    };
+    self
+      .set(
+        Request::SetPunctuationMode(ClientScope::Current, mode),
+        OK_PUNCTUATION_SET,
+      )
+      .map_err(OutputError::into_unknown)?;
+    *self.punctuation.borrow_mut() = punctuation;
+    Ok(native)
+  }
+  /// Speech Dispatcher has no pitch mode, so capitals are not indicated by it in that mode and
+  /// their pitch is raised with annotated spans instead.
+  fn set_capitals(&self, capitals: Option<Capitals>) -> Result<bool, OutputError> {
+    let native = capitals != Some(Capitals::Pitch);
+    let capitals = if native {
+      capitals
+    } else {
+      Some(Capitals::None)
+    };
+    if self.capitals.get() == capitals {
+      return Ok(native);
+    }
+    let mode = match capitals {
+      None | Some(Capitals::None | Capitals::Pitch) => CapitalLettersRecognitionMode::None,
+      Some(Capitals::SayCap) => CapitalLettersRecognitionMode::Spell,
+      Some(Capitals::Icon) => CapitalLettersRecognitionMode::Icon,
+    };
+    self
+      .set(
+        Request::SetCapitalLettersRecognitionMode(ClientScope::Current, mode),
+        OK_CAP_LET_RECOGN_SET,
+      )
+      .map_err(OutputError::into_unknown)?;
+    self.capitals.set(capitals);
+    Ok(native)
+  }
   fn as_speech_synthesizer_to_audio_data(&self) -> Option<&dyn SpeechSynthesizerToAudioData> {
     None
   }
@@ -95,8 +786,9 @@ impl SpeechSynthesizerToAudioOutput for SpeechDispatcher {
   fn supports_speech_parameters(&self) -> bool {
     true
   }
-  #[allow(clippy::cast_possible_wrap)]
-  #[allow(clippy::too_many_lines)]
+  fn supports_priorities(&self) -> bool {
+    true
+  }
   fn speak(
     &self,
     voice: Option<&str>,
@@ -106,166 +798,255 @@ impl SpeechSynthesizerToAudioOutput for SpeechDispatcher {
     pitch: Option<u8>,
     text: &str,
     interrupt: bool,
+    priority: Priority,
+    utterance: &Utterance,
   ) -> std::result::Result<(), OutputError> {
-    let voice = match (voice, language) {
-      (None, None) => None,
-      (Some(voice), _) => Some(voice.to_owned()),
-      (_, Some(language)) => Some(
-        self
-          .list_voices()?
-          .into_iter()
-          .find(|voice| voice.languages.iter().any(|name| name == language))
-          .ok_or(OutputError::into_language_not_found(language))?
-          .name,
-      ),
+    self.send_message(
+      voice,
+      language,
+      rate,
+      volume,
+      pitch,
+      Message::Text(text),
+      interrupt,
+      priority,
+      utterance,
+    )
+  }
+  fn speak_ssml(
+    &self,
+    voice: Option<&str>,
+    language: Option<&str>,
+    rate: Option<u8>,
+    volume: Option<u8>,
+    pitch: Option<u8>,
+    ssml: &Ssml,
+    interrupt: bool,
+    priority: Priority,
+    utterance: &Utterance,
+  ) -> std::result::Result<(), OutputError> {
+    self.send_message(
+      voice,
+      language,
+      rate,
+      volume,
+      pitch,
+      Message::Ssml(&ssml.to_string()),
+      interrupt,
+      priority,
+      utterance,
+    )
+  }
+  /// Speaks keys SSIP can name with `KEY`, so that Speech Dispatcher names them in the language
+  /// of the voice.
+  fn speak_key(
+    &self,
+    voice: Option<&str>,
+    language: Option<&str>,
+    rate: Option<u8>,
+    volume: Option<u8>,
+    pitch: Option<u8>,
+    key: &KeyCombination,
+    interrupt: bool,
+    priority: Priority,
+    utterance: &Utterance,
+  ) -> std::result::Result<bool, OutputError> {
+    let Some(key) = key_name(key) else {
+      return Ok(false);
     };
-    let mut client = self.client.borrow_mut();
-    match voice {
-      None => {
-        client
-          .set_output_module(ClientScope::Current, &self.default_output_module)
-          .map_err(|err| {
-            OutputError::into_speak_failed(&self.name(), &self.default_output_module, err)
-          })?
-          .check_status(OK_OUTPUT_MODULE_SET)
-          .map_err(|err| {
-            OutputError::into_speak_failed(&self.name(), &self.default_output_module, err)
-          })?;
-        client
-          .set_language(ClientScope::Current, &self.default_language)
-          .map_err(|err| OutputError::into_speak_failed(&self.name(), &self.default_language, err))?
-          .check_status(OK_LANGUAGE_SET)
-          .map_err(|err| {
-            OutputError::into_speak_failed(&self.name(), &self.default_language, err)
-          })?;
-      }
-      Some(ref voice) => {
-        let mut split = voice.split('/');
-        let output_module = split.next().unwrap();
-        let voice = split.next().unwrap();
-        client
-          .set_output_module(ClientScope::Current, output_module)
-          .map_err(|err| OutputError::into_speak_failed(&self.name(), output_module, err))?
-          .check_status(OK_OUTPUT_MODULE_SET)
-          .map_err(|err| OutputError::into_speak_failed(&self.name(), output_module, err))?;
-        client
-          .set_synthesis_voice(ClientScope::Current, voice)
-          .map_err(|err| OutputError::into_speak_failed(&self.name(), voice, err))?
-          .check_status(OK_VOICE_SET)
-          .map_err(|err| OutputError::into_speak_failed(&self.name(), voice, err))?;
-      }
+    self.send_message(
+      voice,
+      language,
+      rate,
+      volume,
+      pitch,
+      Message::Key(&key),
+      interrupt,
+      priority,
+      utterance,
+    )?;
+    Ok(true)
+  }
+  /// Speaks single characters with `CHAR` and other text in spelling mode.
+  fn spell(
+    &self,
+    voice: Option<&str>,
+    language: Option<&str>,
+    rate: Option<u8>,
+    volume: Option<u8>,
+    pitch: Option<u8>,
+    text: &str,
+    interrupt: bool,
+    priority: Priority,
+    utterance: &Utterance,
+  ) -> std::result::Result<(), OutputError> {
+    let mut chars = text.chars();
+    let message = match (chars.next(), chars.next()) {
+      (Some(character), None) => Message::Character(character),
+      _ => Message::Spelled(text),
+    };
+    self.send_message(
+      voice, language, rate, volume, pitch, message, interrupt, priority, utterance,
+    )
+  }
+  fn stop_speech(&self) -> std::result::Result<(), OutputError> {
+    self
+      .set_for_messages(Request::Cancel, OK_CANCELED)
+      .map_err(|err| OutputError::into_stop_speech_failed(&self.name(), err))?;
+    for (_, utterance) in self.messages().utterances.drain() {
+      utterance.set_state(UtteranceState::Cancelled);
     }
-    let rate = rate.unwrap_or(50) as i8;
-    let rate = (rate * 2) - 100;
-    client
-      .set_rate(ClientScope::Current, rate)
-      .map_err(|err| {
-        OutputError::into_speak_failed(
-          &self.name(),
-          voice.as_deref().unwrap_or(&self.default_output_module),
-          err,
-        )
-      })?
-      .check_status(OK_RATE_SET)
-      .map_err(|err| {
-        OutputError::into_speak_failed(
-          &self.name(),
-          voice.as_deref().unwrap_or(&self.default_output_module),
-          err,
-        )
-      })?;
-    let pitch = pitch.unwrap_or(50) as i8;
-    let pitch = (pitch * 2) - 100;
-    client
-      .set_pitch(ClientScope::Current, pitch)
-      .map_err(|err| {
-        OutputError::into_speak_failed(
-          &self.name(),
-          voice.as_deref().unwrap_or(&self.default_output_module),
-          err,
-        )
-      })?
-      .check_status(OK_PITCH_SET)
-      .map_err(|err| {
-        OutputError::into_speak_failed(
-          &self.name(),
-          voice.as_deref().unwrap_or(&self.default_output_module),
-          err,
-        )
-      })?;
-    let volume = volume.unwrap_or(50) as i8;
-    let volume = (volume * 2) - 100;
-    client
-      .set_volume(ClientScope::Current, volume)
-      .map_err(|err| {
-        OutputError::into_speak_failed(
-          &self.name(),
-          voice.as_deref().unwrap_or(&self.default_output_module),
-          err,
-        )
-      })?
-      .check_status(OK_VOLUME_SET)
-      .map_err(|err| {
-        OutputError::into_speak_failed(
-          &self.name(),
-          voice.as_deref().unwrap_or(&self.default_output_module),
-          err,
+    Ok(())
+  }
+  fn supports_pausing(&self) -> bool {
+    true
+  }
+  fn pause(&self) -> std::result::Result<(), OutputError> {
+    self
+      .set_for_messages(Request::Pause, OK_PAUSED)
+      .map_err(|err| OutputError::into_pause_failed(&self.name(), err))?;
+    self.paused.set(true);
+    Ok(())
+  }
+  fn resume(&self) -> std::result::Result<(), OutputError> {
+    self
+      .set_for_messages(Request::Resume, OK_RESUMED)
+      .map_err(|err| OutputError::into_pause_failed(&self.name(), err))?;
+    self.paused.set(false);
+    Ok(())
+  }
+  fn cancel(&self, utterance: &Utterance) -> std::result::Result<(), OutputError> {
+    let message_id = self
+      .messages()
+      .utterances
+      .iter()
+      .find(|(_, value)| value.id() == utterance.id())
+      .map(|(message_id, _)| *message_id);
+    if let Some(message_id) = message_id {
+      self
+        .set(
+          Request::Cancel(MessageScope::Message(message_id)),
+          OK_CANCELED,
         )
-      })?;
-    if interrupt {
-      client
-        .cancel(MessageScope::Last)
-        .map_err(|err| OutputError::into_stop_speech_failed(&self.name(), err))?
-        .check_status(OK_CANCELED)
         .map_err(|err| OutputError::into_stop_speech_failed(&self.name(), err))?;
     }
-    let lines = text
-      .lines()
-      .map(std::borrow::ToOwned::to_owned)
-      .collect::<Vec<String>>();
-    client
-      .speak()
-      .map_err(|err| {
-        OutputError::into_speak_failed(
-          &self.name(),
-          voice.as_deref().unwrap_or(&self.default_output_module),
-          err,
-        )
-      })?
-      .check_receiving_data()
-      .map_err(|err| {
-        OutputError::into_speak_failed(
-          &self.name(),
-          voice.as_deref().unwrap_or(&self.default_output_module),
-          err,
-        )
-      })?
-      .send_lines(&lines)
-      .map_err(|err| {
-        OutputError::into_speak_failed(
-          &self.name(),
-          voice.as_deref().unwrap_or(&self.default_output_module),
-          err,
-        )
-      })?
-      .receive_message_id()
-      .map_err(|err| {
-        OutputError::into_speak_failed(
-          &self.name(),
-          voice.as_deref().unwrap_or(&self.default_output_module),
-          err,
-        )
-      })?;
     Ok(())
   }
-  fn stop_speech(&self) -> std::result::Result<(), OutputError> {
-    self
-      .client
-      .borrow_mut()
-      .cancel(MessageScope::Last)
-      .map_err(|err| OutputError::into_stop_speech_failed(&self.name(), err))?
-      .check_status(OK_CANCELED)
-      .map_err(|err| OutputError::into_stop_speech_failed(&self.name(), err))?;
-    Ok(())
+  fn is_speaking(&self) -> std::result::Result<bool, OutputError> {
+    Ok(!self.paused.get() && !self.messages().utterances.is_empty())
+  }
+  fn is_paused(&self) -> std::result::Result<bool, OutputError> {
+    Ok(self.paused.get() && !self.messages().utterances.is_empty())
+  }
+  /// Handles the event notifications the server sent since the last request, without waiting
+  /// for more.
+  fn poll(&self) -> std::result::Result<(), OutputError> {
+    if self.messages().utterances.is_empty() {
+      return Ok(());
+    }
+    self.connection.poll().map_err(OutputError::into_unknown)
+  }
+}
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::events::EventBus;
+  use std::collections::VecDeque;
+  use std::io::{BufReader, Read, Write};
+  use std::sync::Arc;
+  /// Returns its chunks one read at a time, and fails with `WouldBlock` for an empty chunk.
+  struct Chunks(VecDeque<&'static [u8]>);
+  impl Read for Chunks {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+      match self.0.pop_front() {
+        Some([]) => Err(io::ErrorKind::WouldBlock.into()),
+        Some(chunk) => {
+          buffer[..chunk.len()].copy_from_slice(chunk);
+          Ok(chunk.len())
+        }
+        None => Ok(0),
+      }
+    }
+  }
+  #[test]
+  fn handles_events_that_arrive_before_the_message_identifier() {
+    let events = Arc::new(EventBus::new());
+    let first = Utterance::pending(1, &events);
+    let second = Utterance::pending(2, &events);
+    first.set_state(UtteranceState::Speaking);
+    let mut messages = Messages::default();
+    messages.utterances.insert(7, first.clone());
+    messages.queued = Some(second.clone());
+    let mut answers = Answers::new(
+      "702-7\r\n702-3\r\n702 OK END\r\n225-8\r\n225 OK MESSAGE QUEUED\r\n701-8\r\n701-3\r\n701 OK BEGIN\r\n"
+        .as_bytes(),
+    );
+    let answer = answers.read().unwrap().unwrap();
+    assert!(messages.handle(answer).is_none());
+    assert_eq!(first.state(), UtteranceState::Finished);
+    let answer = answers.read().unwrap().unwrap();
+    assert_eq!(
+      messages.handle(answer).map(|answer| answer.code),
+      Some(OK_MESSAGE_QUEUED)
+    );
+    assert_eq!(second.state(), UtteranceState::Queued);
+    let answer = answers.read().unwrap().unwrap();
+    assert!(messages.handle(answer).is_none());
+    assert_eq!(second.state(), UtteranceState::Speaking);
+    assert_eq!(messages.utterances.keys().collect::<Vec<_>>(), [&8]);
+  }
+  #[test]
+  fn keeps_lines_cut_off_by_timeouts() {
+    let mut answers = Answers::new(BufReader::new(Chunks(VecDeque::from([
+      b"251-espeak".as_slice(),
+      b"",
+      b"-ng\r\n251 OK GET\r\n409 ERR RATE",
+      b"",
+      b" TOO HIGH\r\n",
+    ]))));
+    assert!(answers.read().unwrap().is_none());
+    assert_eq!(
+      answers.read().unwrap().unwrap().value().unwrap(),
+      "espeak-ng"
+    );
+    assert!(answers.read().unwrap().is_none());
+    match answers.read() {
+      Err(ClientError::Ssip(status)) => {
+        assert_eq!(status.code, 409);
+        assert_eq!(status.message, "RATE TOO HIGH");
+      }
+      _ => panic!("expected an SSIP error"),
+    }
+  }
+  #[test]
+  fn handles_events_while_waiting_for_an_answer() {
+    let socket_path = std::env::temp_dir().join(format!("whisp-rs-{}.sock", std::process::id()));
+    let _result = std::fs::remove_file(&socket_path);
+    let listener = std::os::unix::net::UnixListener::bind(&socket_path).unwrap();
+    let server = std::thread::spawn(move || {
+      let (stream, _) = listener.accept().unwrap();
+      let mut request = String::new();
+      BufReader::new(&stream).read_line(&mut request).unwrap();
+      assert_eq!(request, "SPEAK\r\n");
+      (&stream)
+        .write_all(b"701-5\r\n701-3\r\n701 BEGIN\r\n230 OK RECEIVING DATA\r\n")
+        .unwrap();
+    });
+    let options = BackendOptions {
+      socket_path: Some(socket_path.to_string_lossy().into_owned()),
+      ..BackendOptions::default()
+    };
+    let messages = Arc::new(Mutex::new(Messages::default()));
+    let utterance = Utterance::pending(1, &Arc::new(EventBus::new()));
+    lock_messages(&messages)
+      .utterances
+      .insert(5, utterance.clone());
+    let connection = Connection::new(&options, messages).unwrap();
+    connection.send(Request::Speak).unwrap();
+    let answer = connection.receive().unwrap().unwrap();
+    assert_eq!(answer.code, OK_RECEIVING_DATA);
+    assert_eq!(utterance.state(), UtteranceState::Speaking);
+    server.join().unwrap();
+    std::fs::remove_file(socket_path).unwrap();
   }
 }
@@ -0,0 +1,179 @@
+#[cfg(target_os = "macos")]
+use crate::backends::av_speech_synthesizer::AvSpeechSynthesizer;
+use crate::backends::espeak_ng::EspeakNg;
+#[cfg(windows)]
+use crate::backends::jaws::Jaws;
+#[cfg(windows)]
+use crate::backends::nvda::Nvda;
+#[cfg(windows)]
+use crate::backends::one_core::OneCore;
+#[cfg(windows)]
+use crate::backends::sapi::Sapi;
+#[cfg(target_os = "linux")]
+use crate::backends::speech_dispatcher::SpeechDispatcher;
+use crate::backends::{Backend, BackendOptions};
+use crate::error::OutputError;
+use crate::lexicon::Lexicon;
+#[cfg(feature = "profiles")]
+use crate::profile::SpeechProfiles;
+use crate::Whisprs;
+use std::collections::{HashMap, HashSet};
+#[cfg(feature = "profiles")]
+use std::path::{Path, PathBuf};
+pub(crate) type BackendConstructor =
+  Box<dyn FnOnce(&BackendOptions) -> Result<Box<dyn Backend>, OutputError> + Send>;
+fn constructor<T>() -> BackendConstructor
+where
+  T: Backend + 'static,
+{
+  Box::new(|options| T::new_with_options(options).map(|value| Box::new(value) as Box<dyn Backend>))
+}
+/// The outcome of initializing one backend while building a [`Whisprs`] instance.
+#[derive(Debug)]
+pub struct BackendInitialization {
+  pub name: String,
+  pub result: Result<(), OutputError>,
+}
+/// Chooses which backends a [`Whisprs`] instance starts with and how they are configured.
+///
+/// [`WhisprsBuilder::new`] starts with every backend available on the current platform, in the
+/// same order as [`Whisprs::new`].
+pub struct WhisprsBuilder {
+  pub(crate) backends: Vec<(String, BackendConstructor)>,
+  pub(crate) disabled: HashSet<String>,
+  pub(crate) priorities: HashMap<String, u8>,
+  pub(crate) options: HashMap<String, BackendOptions>,
+  pub(crate) headless: bool,
+  pub(crate) lexicon: Lexicon,
+  #[cfg(feature = "language-detection")]
+  pub(crate) language_detection: bool,
+  #[cfg(feature = "profiles")]
+  pub(crate) profiles: Option<SpeechProfiles>,
+  #[cfg(feature = "profiles")]
+  pub(crate) profiles_file: Option<PathBuf>,
+}
+impl WhisprsBuilder {
+  #[must_use]
+  pub fn new() -> Self {
+    let mut backends: Vec<(String, BackendConstructor)> = Vec::new();
+    backends.push(("eSpeak NG".to_owned(), constructor::<EspeakNg>()));
+    #[cfg(windows)]
+    {
+      backends.push(("SAPI 5".to_owned(), constructor::<Sapi>()));
+      backends.push(("OneCore".to_owned(), constructor::<OneCore>()));
+      backends.push(("JAWS".to_owned(), constructor::<Jaws>()));
+      backends.push(("NVDA".to_owned(), constructor::<Nvda>()));
+    }
+    #[cfg(target_os = "linux")]
+    {
+      backends.push((
+        "Speech Dispatcher".to_owned(),
+        constructor::<SpeechDispatcher>(),
+      ));
+    }
+    #[cfg(target_os = "macos")]
+    {
+      backends.push((
+        "AVSpeechSynthesizer".to_owned(),
+        constructor::<AvSpeechSynthesizer>(),
+      ));
+    }
+    WhisprsBuilder {
+      backends,
+      disabled: HashSet::new(),
+      priorities: HashMap::new(),
+      options: HashMap::new(),
+      headless: false,
+      lexicon: Lexicon::new(),
+      #[cfg(feature = "language-detection")]
+      language_detection: false,
+      #[cfg(feature = "profiles")]
+      profiles: None,
+      #[cfg(feature = "profiles")]
+      profiles_file: None,
+    }
+  }
+  /// Adds a custom backend, which is created with [`Backend::new_with_options`] when the instance
+  /// is built. `name` must match the name the backend reports, or its initialization fails, since
+  /// options and priorities are looked up by it.
+  #[must_use]
+  pub fn backend<T>(mut self, name: &str) -> Self
+  where
+    T: Backend + 'static,
+  {
+    self.backends.retain(|backend| backend.0 != name);
+    self.backends.push((name.to_owned(), constructor::<T>()));
+    self
+  }
+  #[must_use]
+  pub fn enable_backend(mut self, name: &str) -> Self {
+    self.disabled.remove(name);
+    self
+  }
+  #[must_use]
+  pub fn disable_backend(mut self, name: &str) -> Self {
+    self.disabled.insert(name.to_owned());
+    self
+  }
+  /// Overrides the priority of every voice of a backend and, if it supports Braille, of the
+  /// backend itself. Lower priorities are preferred.
+  #[must_use]
+  pub fn backend_priority(mut self, name: &str, priority: u8) -> Self {
+    self.priorities.insert(name.to_owned(), priority);
+    self
+  }
+  #[must_use]
+  pub fn backend_options(mut self, name: &str, options: BackendOptions) -> Self {
+    self.options.insert(name.to_owned(), options);
+    self
+  }
+  /// In headless mode, the audio output device is only opened the first time speech is played
+  /// by whisp-rs itself, instead of when the instance is built. This allows speaking to audio
+  /// data, Braille and screen readers on systems without a sound card.
+  #[must_use]
+  pub fn headless(mut self, headless: bool) -> Self {
+    self.headless = headless;
+    self
+  }
+  /// When enabled, text spoken without an explicit voice or language is split into sentences
+  /// whose language is detected, and each sentence is spoken with the preferred voice of the
+  /// chosen synthesizer for its language. The chosen languages are reported with
+  /// [`crate::events::SpeechEventKind::LanguageDetected`] events.
+  #[cfg(feature = "language-detection")]
+  #[must_use]
+  pub fn language_detection(mut self, enabled: bool) -> Self {
+    self.language_detection = enabled;
+    self
+  }
+  /// Starts with the given speech profiles, which can be selected by name with
+  /// [`crate::request::SpeechRequest::profile`].
+  #[cfg(feature = "profiles")]
+  #[must_use]
+  pub fn profiles(mut self, profiles: SpeechProfiles) -> Self {
+    self.profiles = Some(profiles);
+    self
+  }
+  /// Loads speech profiles from a TOML or JSON file when the instance is built, and reloads them
+  /// whenever the file changes. Replaces profiles set with [`WhisprsBuilder::profiles`].
+  #[cfg(feature = "profiles")]
+  #[must_use]
+  pub fn profiles_file(mut self, path: impl AsRef<Path>) -> Self {
+    self.profiles_file = Some(path.as_ref().to_owned());
+    self
+  }
+  /// Starts with a pronunciation lexicon, which can be replaced later with
+  /// [`Whisprs::set_lexicon`].
+  #[must_use]
+  pub fn lexicon(mut self, lexicon: Lexicon) -> Self {
+    self.lexicon = lexicon;
+    self
+  }
+  pub fn build(self) -> Result<Whisprs, OutputError> {
+    Whisprs::from_builder(self)
+  }
+}
+impl Default for WhisprsBuilder {
+  fn default() -> Self {
+    WhisprsBuilder::new()
+  }
+}
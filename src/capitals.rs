@@ -0,0 +1,289 @@
+use crate::annotated_text::{AnnotatedText, TextSpan};
+use crate::audio::{SampleFormat, SpeechResult};
+use crate::backends::SpeechSynthesizerToAudioData;
+use crate::error::OutputError;
+use crate::ssml::Ssml;
+use anyhow::anyhow;
+use std::f32::consts::TAU;
+/// How much [`Capitals::Pitch`] raises the pitch of capitalized words, on the scale of the
+/// pitch parameter.
+const PITCH_RAISE: u8 = 20;
+const ICON_FREQUENCY: f32 = 1200.0;
+const ICON_MILLISECONDS: u32 = 40;
+const ICON_AMPLITUDE: f32 = 0.25;
+/// How capital letters are indicated. eSpeak NG and Speech Dispatcher do it themselves, except
+/// that Speech Dispatcher has no pitch mode. For the other backends, pitch is raised with
+/// annotated spans, "cap" is inserted in the text, and the sound icon is mixed into the audio of
+/// backends that return audio data; backends that play their own audio say "cap" instead.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Capitals {
+  None,
+  /// Raises the pitch of capitalized words.
+  Pitch,
+  /// Says "cap" before capitalized words.
+  SayCap,
+  /// Plays a short sound before capitalized words.
+  Icon,
+}
+/// Splits text into words with their trailing whitespace, and whether each starts with a
+/// capital letter.
+fn words(text: &str) -> impl Iterator<Item = (bool, &str)> {
+  text
+    .split_inclusive(char::is_whitespace)
+    .map(|word| (word.chars().next().is_some_and(char::is_uppercase), word))
+}
+/// Inserts "cap" before capitalized words.
+pub(crate) fn say_cap(text: &str) -> String {
+  words(text)
+    .map(|(capital, word)| {
+      if capital {
+        format!("cap {word}")
+      } else {
+        word.to_owned()
+      }
+    })
+    .collect()
+}
+/// Splits spans at capitalized words and raises their pitch above the one they would have had,
+/// which is `pitch` for spans without their own.
+pub(crate) fn raise_pitch(text: &AnnotatedText, pitch: Option<u8>) -> AnnotatedText {
+  let mut spans = Vec::new();
+  for span in &text.spans {
+    let mut runs: Vec<(bool, String)> = Vec::new();
+    for (capital, word) in words(&span.text) {
+      match runs.last_mut() {
+        Some((last_capital, run)) if *last_capital == capital => run.push_str(word),
+        _ => runs.push((capital, word.to_owned())),
+      }
+    }
+    let last = runs.len().saturating_sub(1);
+    for (index, (capital, run)) in runs.into_iter().enumerate() {
+      let raised = span
+        .pitch
+        .or(pitch)
+        .unwrap_or(50)
+        .saturating_add(PITCH_RAISE)
+        .min(100);
+      spans.push(TextSpan {
+        text: run,
+        pitch: if capital { Some(raised) } else { span.pitch },
+        pause_after: if index == last {
+          span.pause_after
+        } else {
+          None
+        },
+        ..span.clone()
+      });
+    }
+  }
+  AnnotatedText { spans }
+}
+/// A short tone in the format of a chunk of speech.
+fn icon(format: SampleFormat, sample_rate: u32) -> SpeechResult {
+  let samples = sample_rate * ICON_MILLISECONDS / 1000;
+  #[allow(clippy::cast_precision_loss)]
+  let pcm = (0..samples)
+    .flat_map(|index| {
+      let time = index as f32 / sample_rate as f32;
+      let envelope = 1.0 - (2.0 * index as f32 / samples as f32 - 1.0).abs();
+      let sample = (TAU * ICON_FREQUENCY * time).sin() * envelope * ICON_AMPLITUDE;
+      match format {
+        #[allow(clippy::cast_possible_truncation)]
+        SampleFormat::S16 => ((sample * 32767.0) as i16).to_le_bytes().to_vec(),
+        SampleFormat::F32 => sample.to_le_bytes().to_vec(),
+      }
+    })
+    .collect();
+  SpeechResult {
+    pcm,
+    sample_format: format,
+    sample_rate,
+    markers: Vec::new(),
+  }
+}
+/// Wraps a synthesizer to play a sound icon before capitalized words, by synthesizing the text
+/// between them separately.
+pub(crate) struct CapitalIcons<'a> {
+  pub(crate) synthesizer: &'a dyn SpeechSynthesizerToAudioData,
+}
+impl SpeechSynthesizerToAudioData for CapitalIcons<'_> {
+  fn supports_speech_parameters(&self) -> bool {
+    self.synthesizer.supports_speech_parameters()
+  }
+  fn speak(
+    &self,
+    voice: Option<&str>,
+    language: Option<&str>,
+    rate: Option<u8>,
+    volume: Option<u8>,
+    pitch: Option<u8>,
+    text: &str,
+  ) -> Result<SpeechResult, OutputError> {
+    let mut result: Option<SpeechResult> = None;
+    self.speak_streaming(voice, language, rate, volume, pitch, text, &mut |chunk| {
+      match &mut result {
+        Some(result) => result.append(chunk),
+        None => result = Some(chunk),
+      }
+      true
+    })?;
+    result.ok_or_else(|| OutputError::into_unknown(anyhow!("No audio was synthesized")))
+  }
+  fn speak_streaming(
+    &self,
+    voice: Option<&str>,
+    language: Option<&str>,
+    rate: Option<u8>,
+    volume: Option<u8>,
+    pitch: Option<u8>,
+    text: &str,
+    on_chunk: &mut dyn FnMut(SpeechResult) -> bool,
+  ) -> Result<(), OutputError> {
+    let mut pieces: Vec<(bool, String)> = Vec::new();
+    for (capital, word) in words(text) {
+      match pieces.last_mut() {
+        Some((_, piece)) if !capital => piece.push_str(word),
+        _ => pieces.push((capital, word.to_owned())),
+      }
+    }
+    for (capital, piece) in pieces {
+      let mut pending_icon = capital;
+      let mut stopped = false;
+      self.synthesizer.speak_streaming(
+        voice,
+        language,
+        rate,
+        volume,
+        pitch,
+        &piece,
+        &mut |chunk| {
+          if pending_icon {
+            pending_icon = false;
+            if !on_chunk(icon(chunk.sample_format, chunk.sample_rate)) {
+              stopped = true;
+              return false;
+            }
+          }
+          stopped = !on_chunk(chunk);
+          !stopped
+        },
+      )?;
+      if stopped {
+        break;
+      }
+    }
+    Ok(())
+  }
+  fn speak_ssml_streaming(
+    &self,
+    voice: Option<&str>,
+    language: Option<&str>,
+    rate: Option<u8>,
+    volume: Option<u8>,
+    pitch: Option<u8>,
+    ssml: &Ssml,
+    on_chunk: &mut dyn FnMut(SpeechResult) -> bool,
+  ) -> Result<(), OutputError> {
+    self
+      .synthesizer
+      .speak_ssml_streaming(voice, language, rate, volume, pitch, ssml, on_chunk)
+  }
+}
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::cell::RefCell;
+  use std::time::Duration;
+  /// Records the text it is asked to speak and returns 10 samples at 1 kHz for each call.
+  #[derive(Default)]
+  struct Recorder {
+    calls: RefCell<Vec<String>>,
+  }
+  impl SpeechSynthesizerToAudioData for Recorder {
+    fn supports_speech_parameters(&self) -> bool {
+      true
+    }
+    fn speak(
+      &self,
+      _voice: Option<&str>,
+      _language: Option<&str>,
+      _rate: Option<u8>,
+      _volume: Option<u8>,
+      _pitch: Option<u8>,
+      text: &str,
+    ) -> Result<SpeechResult, OutputError> {
+      self.calls.borrow_mut().push(text.to_owned());
+      Ok(SpeechResult {
+        pcm: vec![1; 20],
+        sample_format: SampleFormat::S16,
+        sample_rate: 1000,
+        markers: Vec::new(),
+      })
+    }
+  }
+  #[test]
+  fn says_cap_before_capitalized_words() {
+    assert_eq!(say_cap("Hello big World"), "cap Hello big cap World");
+    assert_eq!(say_cap("  Éclair\tok"), "  cap Éclair\tok");
+    assert_eq!(say_cap("lower 1A"), "lower 1A");
+    assert_eq!(say_cap(""), "");
+  }
+  #[test]
+  fn raises_pitch_of_capitalized_runs() {
+    let text = AnnotatedText::new()
+      .span(
+        TextSpan::new("Hi there Big Bob ok")
+          .rate(70)
+          .pause_after(Duration::from_millis(30)),
+      )
+      .span(TextSpan::new("Top").pitch(90));
+    let spans = raise_pitch(&text, Some(40))
+      .spans
+      .into_iter()
+      .map(|span| (span.text, span.pitch, span.rate, span.pause_after))
+      .collect::<Vec<_>>();
+    assert_eq!(
+      spans,
+      [
+        ("Hi ".to_owned(), Some(60), Some(70), None),
+        ("there ".to_owned(), None, Some(70), None),
+        ("Big Bob ".to_owned(), Some(60), Some(70), None),
+        (
+          "ok".to_owned(),
+          None,
+          Some(70),
+          Some(Duration::from_millis(30))
+        ),
+        ("Top".to_owned(), Some(100), None, None),
+      ]
+    );
+    assert_eq!(
+      raise_pitch(&AnnotatedText::new().text("A"), None).spans[0].pitch,
+      Some(70)
+    );
+  }
+  #[test]
+  fn plays_icons_before_capitalized_words() {
+    let recorder = Recorder::default();
+    let icons = CapitalIcons {
+      synthesizer: &recorder,
+    };
+    let result = icons
+      .speak(None, None, None, None, None, "see Ann and Bo")
+      .unwrap();
+    assert_eq!(*recorder.calls.borrow(), ["see ", "Ann and ", "Bo"]);
+    // Two icons of 40 samples and three chunks of 10 samples, all 16-bit.
+    assert_eq!(result.pcm.len(), (2 * 40 + 3 * 10) * 2);
+    let mut chunks = 0;
+    icons
+      .speak_streaming(None, None, None, None, None, "A B", &mut |_| {
+        chunks += 1;
+        chunks < 2
+      })
+      .unwrap();
+    // Stopping on the audio of "A " skips synthesizing the rest.
+    assert_eq!(chunks, 2);
+    assert_eq!(recorder.calls.borrow().len(), 4);
+  }
+}
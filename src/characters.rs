@@ -0,0 +1,63 @@
+use crate::punctuation;
+const LETTERS: &str = "abcdefghijklmnopqrstuvwxyz";
+/// The NATO phonetic alphabet, used when spelling phonetically.
+const PHONETIC: [&str; 26] = [
+  "alpha", "bravo", "charlie", "delta", "echo", "foxtrot", "golf", "hotel", "india", "juliett",
+  "kilo", "lima", "mike", "november", "oscar", "papa", "quebec", "romeo", "sierra", "tango",
+  "uniform", "victor", "whiskey", "x-ray", "yankee", "zulu",
+];
+/// The English name of a character, spoken by backends that have no character mode. Letters
+/// are written in upper case, which engines read as letters rather than words.
+fn name(character: char, phonetic: bool) -> String {
+  match character {
+    'a'..='z' | 'A'..='Z' if phonetic => {
+      let index = LETTERS
+        .find(character.to_ascii_lowercase())
+        .unwrap_or_default();
+      PHONETIC[index].to_owned()
+    }
+    ' ' => "space".to_owned(),
+    '\t' => "tab".to_owned(),
+    '\n' | '\r' => "new line".to_owned(),
+    character => punctuation::name(character).map_or_else(
+      || character.to_uppercase().collect(),
+      std::borrow::ToOwned::to_owned,
+    ),
+  }
+}
+/// Text that spells `text` one character at a time, with pauses between the characters.
+pub(crate) fn spell(text: &str, phonetic: bool) -> String {
+  let mut characters = text.chars().peekable();
+  let mut output = String::new();
+  while let Some(character) = characters.next() {
+    if character == '\r' && characters.peek() == Some(&'\n') {
+      continue;
+    }
+    if !output.is_empty() {
+      output.push_str(", ");
+    }
+    output.push_str(&name(character, phonetic));
+  }
+  output
+}
+#[cfg(test)]
+mod tests {
+  use super::*;
+  #[test]
+  fn spells_character_names() {
+    assert_eq!(spell("a1 .", false), "A, 1, space, dot");
+    assert_eq!(spell("\t", false), "tab");
+    assert_eq!(spell("", false), "");
+  }
+  #[test]
+  fn spells_letters_phonetically() {
+    assert_eq!(spell("Xy!", true), "x-ray, yankee, exclamation");
+    assert_eq!(spell("é", true), "É");
+  }
+  #[test]
+  fn spells_line_breaks_once() {
+    assert_eq!(spell("a\r\nb", false), "A, new line, B");
+    assert_eq!(spell("a\n\rb", false), "A, new line, new line, B");
+    assert_eq!(spell("\r", false), "new line");
+  }
+}
@@ -9,10 +9,16 @@ pub enum OutputError {
   SpeechNotSupported(String),
   #[error("The backend {0} does not support Braille")]
   BrailleNotSupported(String),
+  #[error("The backend {0} does not support pausing speech")]
+  PausingNotSupported(String),
   #[error("No voice was found with the name {0}")]
   VoiceNotFound(String),
+  #[error("No voice variant was found with the name {0}")]
+  VariantNotFound(String),
   #[error("No voice was found with the language {0}")]
   LanguageNotFound(String),
+  #[error("No speech profile was found with the name {0}")]
+  ProfileNotFound(String),
   #[error("No voices were found")]
   NoVoices,
   #[error("No Braille backends were found")]
@@ -36,11 +42,18 @@ pub enum OutputError {
     backend: String,
     error: anyhow::Error,
   },
+  #[error("Failed to pause or resume speech of the requested backend {backend}: {error}")]
+  PauseFailed {
+    backend: String,
+    error: anyhow::Error,
+  },
   #[error("Failed to Braille message with the requested backend {backend}: {error}")]
   BrailleFailed {
     backend: String,
     error: anyhow::Error,
   },
+  #[error("No audio output device is available: {0}")]
+  NoAudioOutput(anyhow::Error),
   #[error("Failed to initialize whisp-rs: {0}")]
   InitializeFailed(anyhow::Error),
   #[error("Invalid parameter: {0}")]
@@ -66,10 +79,22 @@ impl OutputError {
     OutputError::BrailleNotSupported(backend.to_owned())
   }
   #[must_use]
+  pub fn into_pausing_not_supported(backend: &str) -> Self {
+    OutputError::PausingNotSupported(backend.to_owned())
+  }
+  #[must_use]
   pub fn into_voice_not_found(voice: &str) -> Self {
     OutputError::VoiceNotFound(voice.to_owned())
   }
   #[must_use]
+  pub fn into_variant_not_found(variant: &str) -> Self {
+    OutputError::VariantNotFound(variant.to_owned())
+  }
+  #[must_use]
+  pub fn into_profile_not_found(profile: &str) -> Self {
+    OutputError::ProfileNotFound(profile.to_owned())
+  }
+  #[must_use]
   pub fn into_language_not_found(language: &str) -> Self {
     OutputError::LanguageNotFound(language.to_owned())
   }
@@ -95,6 +120,16 @@ impl OutputError {
     }
   }
   #[must_use]
+  pub fn into_pause_failed<T>(backend: &str, error: T) -> Self
+  where
+    T: Into<anyhow::Error>,
+  {
+    OutputError::PauseFailed {
+      backend: backend.to_owned(),
+      error: error.into(),
+    }
+  }
+  #[must_use]
   pub fn into_braille_failed<T>(backend: &str, error: T) -> Self
   where
     T: Into<anyhow::Error>,
@@ -105,6 +140,13 @@ impl OutputError {
     }
   }
   #[must_use]
+  pub fn into_no_audio_output<T>(error: T) -> Self
+  where
+    T: Into<anyhow::Error>,
+  {
+    OutputError::NoAudioOutput(error.into())
+  }
+  #[must_use]
   pub fn into_initialize_failed<T>(error: T) -> Self
   where
     T: Into<anyhow::Error>,
@@ -0,0 +1,82 @@
+use std::sync::{mpsc, Arc, Mutex, PoisonError};
+use std::thread;
+/// Something that happened while an utterance was spoken. Offsets and lengths are counted in
+/// characters of the text sent to the synthesizer, that is after lexicon entries, punctuation and
+/// capital letter emulation are applied. They only match the text passed to the speak method when
+/// none of these changed it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SpeechEventKind {
+  Begin,
+  End,
+  Cancel,
+  Word {
+    offset: usize,
+    length: usize,
+  },
+  Sentence {
+    offset: usize,
+    length: usize,
+  },
+  Mark(String),
+  /// Part of the text was detected to be in `language`, an ISO 639-1 code, and is spoken with a
+  /// voice for it. Sent before speech starts, with the `language-detection` feature. Unlike other
+  /// events, its offset and length refer to the text passed to the speak method.
+  LanguageDetected {
+    offset: usize,
+    length: usize,
+    language: String,
+  },
+  /// Speech or Braille requested without waiting for the worker thread failed with this error.
+  /// Sent after `Cancel` if the speech failed.
+  Error(String),
+}
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SpeechEvent {
+  pub utterance: u64,
+  pub synthesizer: String,
+  pub kind: SpeechEventKind,
+}
+type Subscriber = Box<dyn FnMut(&SpeechEvent) -> bool + Send>;
+/// Delivers speech events to subscribers on a dedicated thread, so that neither the audio thread
+/// nor the worker thread ever waits for a subscriber. The thread ends when the bus is dropped.
+pub(crate) struct EventBus {
+  sender: mpsc::Sender<SpeechEvent>,
+  subscribers: Arc<Mutex<Vec<Subscriber>>>,
+}
+impl EventBus {
+  pub(crate) fn new() -> Self {
+    let (sender, receiver) = mpsc::channel::<SpeechEvent>();
+    let subscribers: Arc<Mutex<Vec<Subscriber>>> = Arc::new(Mutex::new(Vec::new()));
+    let thread_subscribers = subscribers.clone();
+    thread::spawn(move || {
+      for event in receiver {
+        thread_subscribers
+          .lock()
+          .unwrap_or_else(PoisonError::into_inner)
+          .retain_mut(|subscriber| subscriber(&event));
+      }
+    });
+    EventBus {
+      sender,
+      subscribers,
+    }
+  }
+  pub(crate) fn send(&self, event: SpeechEvent) {
+    let _result = self.sender.send(event);
+  }
+  /// Adds a subscriber, which is removed as soon as it returns `false`.
+  pub(crate) fn subscribe(&self, subscriber: Subscriber) {
+    self
+      .subscribers
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner)
+      .push(subscriber);
+  }
+}
+impl std::fmt::Debug for EventBus {
+  fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    formatter.debug_struct("EventBus").finish_non_exhaustive()
+  }
+}
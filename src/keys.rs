@@ -0,0 +1,368 @@
+use crate::language::language_distance;
+use crate::punctuation;
+use std::collections::HashMap;
+const GERMAN: [(&str, &str); 27] = [
+  ("control", "Steuerung"),
+  ("shift", "Umschalt"),
+  ("alt", "Alt"),
+  ("super", "Super"),
+  ("space", "Leertaste"),
+  ("enter", "Eingabe"),
+  ("tab", "Tab"),
+  ("escape", "Escape"),
+  ("backspace", "Rücktaste"),
+  ("delete", "Entfernen"),
+  ("insert", "Einfügen"),
+  ("home", "Pos1"),
+  ("end", "Ende"),
+  ("prior", "Bild auf"),
+  ("next", "Bild ab"),
+  ("up", "Pfeil nach oben"),
+  ("down", "Pfeil nach unten"),
+  ("left", "Pfeil nach links"),
+  ("right", "Pfeil nach rechts"),
+  ("menu", "Kontextmenü"),
+  ("pause", "Pause"),
+  ("print", "Druck"),
+  ("shift-lock", "Feststelltaste"),
+  ("num-lock", "Num-Taste"),
+  ("scroll-lock", "Rollen"),
+  ("underscore", "Unterstrich"),
+  ("double-quote", "Anführungszeichen"),
+];
+const SPANISH: [(&str, &str); 27] = [
+  ("control", "control"),
+  ("shift", "mayúsculas"),
+  ("alt", "alt"),
+  ("super", "super"),
+  ("space", "espacio"),
+  ("enter", "intro"),
+  ("tab", "tabulador"),
+  ("escape", "escape"),
+  ("backspace", "retroceso"),
+  ("delete", "suprimir"),
+  ("insert", "insertar"),
+  ("home", "inicio"),
+  ("end", "fin"),
+  ("prior", "página anterior"),
+  ("next", "página siguiente"),
+  ("up", "flecha arriba"),
+  ("down", "flecha abajo"),
+  ("left", "flecha izquierda"),
+  ("right", "flecha derecha"),
+  ("menu", "menú"),
+  ("pause", "pausa"),
+  ("print", "imprimir pantalla"),
+  ("shift-lock", "bloqueo de mayúsculas"),
+  ("num-lock", "bloqueo numérico"),
+  ("scroll-lock", "bloqueo de desplazamiento"),
+  ("underscore", "guion bajo"),
+  ("double-quote", "comillas"),
+];
+const FRENCH: [(&str, &str); 27] = [
+  ("control", "contrôle"),
+  ("shift", "majuscule"),
+  ("alt", "alt"),
+  ("super", "super"),
+  ("space", "espace"),
+  ("enter", "entrée"),
+  ("tab", "tabulation"),
+  ("escape", "échappement"),
+  ("backspace", "retour arrière"),
+  ("delete", "supprimer"),
+  ("insert", "insérer"),
+  ("home", "début"),
+  ("end", "fin"),
+  ("prior", "page précédente"),
+  ("next", "page suivante"),
+  ("up", "flèche haut"),
+  ("down", "flèche bas"),
+  ("left", "flèche gauche"),
+  ("right", "flèche droite"),
+  ("menu", "menu contextuel"),
+  ("pause", "pause"),
+  ("print", "impression écran"),
+  ("shift-lock", "verrouillage majuscules"),
+  ("num-lock", "verrouillage numérique"),
+  ("scroll-lock", "arrêt défilement"),
+  ("underscore", "souligné"),
+  ("double-quote", "guillemet"),
+];
+const ITALIAN: [(&str, &str); 27] = [
+  ("control", "control"),
+  ("shift", "maiuscolo"),
+  ("alt", "alt"),
+  ("super", "super"),
+  ("space", "spazio"),
+  ("enter", "invio"),
+  ("tab", "tab"),
+  ("escape", "esc"),
+  ("backspace", "backspace"),
+  ("delete", "canc"),
+  ("insert", "ins"),
+  ("home", "inizio"),
+  ("end", "fine"),
+  ("prior", "pagina su"),
+  ("next", "pagina giù"),
+  ("up", "freccia su"),
+  ("down", "freccia giù"),
+  ("left", "freccia sinistra"),
+  ("right", "freccia destra"),
+  ("menu", "menu contestuale"),
+  ("pause", "pausa"),
+  ("print", "stamp"),
+  ("shift-lock", "blocco maiuscole"),
+  ("num-lock", "blocco numeri"),
+  ("scroll-lock", "blocco scorrimento"),
+  ("underscore", "trattino basso"),
+  ("double-quote", "virgolette"),
+];
+const PORTUGUESE: [(&str, &str); 27] = [
+  ("control", "control"),
+  ("shift", "shift"),
+  ("alt", "alt"),
+  ("super", "super"),
+  ("space", "espaço"),
+  ("enter", "enter"),
+  ("tab", "tab"),
+  ("escape", "escape"),
+  ("backspace", "retrocesso"),
+  ("delete", "apagar"),
+  ("insert", "inserir"),
+  ("home", "início"),
+  ("end", "fim"),
+  ("prior", "página acima"),
+  ("next", "página abaixo"),
+  ("up", "seta para cima"),
+  ("down", "seta para baixo"),
+  ("left", "seta para a esquerda"),
+  ("right", "seta para a direita"),
+  ("menu", "menu de contexto"),
+  ("pause", "pausa"),
+  ("print", "print screen"),
+  ("shift-lock", "caps lock"),
+  ("num-lock", "num lock"),
+  ("scroll-lock", "scroll lock"),
+  ("underscore", "sublinhado"),
+  ("double-quote", "aspas"),
+];
+/// The built-in names of keys in languages other than English, keyed by their SSIP names.
+const NAMES: [(&str, &[(&str, &str)]); 5] = [
+  ("de", &GERMAN),
+  ("es", &SPANISH),
+  ("fr", &FRENCH),
+  ("it", &ITALIAN),
+  ("pt", &PORTUGUESE),
+];
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Modifier {
+  Control,
+  Shift,
+  Alt,
+  Super,
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Key {
+  /// A key that types a character, such as a letter, digit or punctuation.
+  Character(char),
+  Space,
+  Enter,
+  Tab,
+  Escape,
+  Backspace,
+  Delete,
+  Insert,
+  Home,
+  End,
+  PageUp,
+  PageDown,
+  Up,
+  Down,
+  Left,
+  Right,
+  /// A function key, from F1 to F24.
+  Function(u8),
+  Menu,
+  Pause,
+  PrintScreen,
+  CapsLock,
+  NumLock,
+  ScrollLock,
+}
+/// A key pressed with modifiers, such as control+shift+A.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct KeyCombination {
+  pub modifiers: Vec<Modifier>,
+  pub key: Key,
+}
+impl Modifier {
+  fn ssip_name(self) -> &'static str {
+    match self {
+      Modifier::Control => "control",
+      Modifier::Shift => "shift",
+      Modifier::Alt => "alt",
+      Modifier::Super => "super",
+    }
+  }
+  fn english_name(self) -> &'static str {
+    self.ssip_name()
+  }
+}
+impl Key {
+  /// The name of the key in SSIP, which is also the key of its name in key name tables.
+  fn ssip_name(self) -> String {
+    match self {
+      Key::Character(' ') | Key::Space => "space",
+      Key::Character('_') => "underscore",
+      Key::Character('"') => "double-quote",
+      Key::Character(character) => return character.to_lowercase().collect(),
+      Key::Enter => "enter",
+      Key::Tab => "tab",
+      Key::Escape => "escape",
+      Key::Backspace => "backspace",
+      Key::Delete => "delete",
+      Key::Insert => "insert",
+      Key::Home => "home",
+      Key::End => "end",
+      Key::PageUp => "prior",
+      Key::PageDown => "next",
+      Key::Up => "up",
+      Key::Down => "down",
+      Key::Left => "left",
+      Key::Right => "right",
+      Key::Function(number) => return format!("f{number}"),
+      Key::Menu => "menu",
+      Key::Pause => "pause",
+      Key::PrintScreen => "print",
+      Key::CapsLock => "shift-lock",
+      Key::NumLock => "num-lock",
+      Key::ScrollLock => "scroll-lock",
+    }
+    .to_owned()
+  }
+  fn english_name(self) -> String {
+    match self {
+      Key::Character(' ') | Key::Space => "space",
+      Key::Character(character) => {
+        return punctuation::name(character).map_or_else(
+          || character.to_uppercase().collect(),
+          std::borrow::ToOwned::to_owned,
+        )
+      }
+      Key::Enter => "enter",
+      Key::Tab => "tab",
+      Key::Escape => "escape",
+      Key::Backspace => "backspace",
+      Key::Delete => "delete",
+      Key::Insert => "insert",
+      Key::Home => "home",
+      Key::End => "end",
+      Key::PageUp => "page up",
+      Key::PageDown => "page down",
+      Key::Up => "up arrow",
+      Key::Down => "down arrow",
+      Key::Left => "left arrow",
+      Key::Right => "right arrow",
+      Key::Function(number) => return format!("F{number}"),
+      Key::Menu => "menu",
+      Key::Pause => "pause",
+      Key::PrintScreen => "print screen",
+      Key::CapsLock => "caps lock",
+      Key::NumLock => "num lock",
+      Key::ScrollLock => "scroll lock",
+    }
+    .to_owned()
+  }
+}
+impl KeyCombination {
+  #[must_use]
+  pub fn new(key: Key) -> Self {
+    KeyCombination {
+      modifiers: Vec::new(),
+      key,
+    }
+  }
+  #[must_use]
+  pub fn modifier(mut self, modifier: Modifier) -> Self {
+    self.modifiers.push(modifier);
+    self
+  }
+  /// The text spoken for the key. Each name is taken from `names`, which maps SSIP key names to
+  /// names in the language of the speech, then from the built-in names for the closest match of
+  /// `language`, and is English otherwise.
+  pub(crate) fn render(&self, names: &HashMap<String, String>, language: Option<&str>) -> String {
+    let built_in = language.and_then(|language| {
+      NAMES
+        .iter()
+        .filter_map(|(tag, names)| {
+          language_distance(language, tag).map(|distance| (distance, *names))
+        })
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, names)| names)
+    });
+    let name = |ssip_name: &str| {
+      names.get(ssip_name).cloned().or_else(|| {
+        built_in?
+          .iter()
+          .find(|(key, _)| *key == ssip_name)
+          .map(|(_, name)| (*name).to_owned())
+      })
+    };
+    self
+      .modifiers
+      .iter()
+      .map(|modifier| name(modifier.ssip_name()).unwrap_or(modifier.english_name().to_owned()))
+      .chain([name(&self.key.ssip_name()).unwrap_or_else(|| self.key.english_name())])
+      .collect::<Vec<String>>()
+      .join(" ")
+  }
+}
+#[cfg(test)]
+mod tests {
+  use super::*;
+  #[test]
+  fn renders_english_names_without_a_table() {
+    let key = KeyCombination::new(Key::PageUp)
+      .modifier(Modifier::Control)
+      .modifier(Modifier::Shift);
+    assert_eq!(key.render(&HashMap::new(), None), "control shift page up");
+    assert_eq!(
+      KeyCombination::new(Key::Character('a')).render(&HashMap::new(), Some("en")),
+      "A"
+    );
+    assert_eq!(
+      KeyCombination::new(Key::Character('/')).render(&HashMap::new(), None),
+      "slash"
+    );
+    assert_eq!(
+      KeyCombination::new(Key::Function(5)).render(&HashMap::new(), None),
+      "F5"
+    );
+  }
+  #[test]
+  fn renders_built_in_names_for_the_closest_language() {
+    let key = KeyCombination::new(Key::Delete).modifier(Modifier::Control);
+    assert_eq!(
+      key.render(&HashMap::new(), Some("de-AT")),
+      "Steuerung Entfernen"
+    );
+    assert_eq!(
+      key.render(&HashMap::new(), Some("fr")),
+      "contrôle supprimer"
+    );
+    assert_eq!(
+      KeyCombination::new(Key::Character('_')).render(&HashMap::new(), Some("es")),
+      "guion bajo"
+    );
+  }
+  #[test]
+  fn prefers_names_from_the_table() {
+    let names = HashMap::from([("delete".to_owned(), "Entf".to_owned())]);
+    let key = KeyCombination::new(Key::Delete).modifier(Modifier::Control);
+    assert_eq!(key.render(&names, Some("de")), "Steuerung Entf");
+    assert_eq!(key.render(&names, Some("nl")), "control Entf");
+  }
+}
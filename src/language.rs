@@ -0,0 +1,128 @@
+struct LanguageTag {
+  language: String,
+  script: Option<String>,
+  region: Option<String>,
+  rest: Vec<String>,
+}
+impl LanguageTag {
+  fn parse(tag: &str) -> Self {
+    let mut subtags = tag
+      .split(['-', '_'])
+      .filter(|subtag| !subtag.is_empty())
+      .map(str::to_lowercase)
+      .peekable();
+    let language = subtags.next().unwrap_or_default();
+    let script = subtags.next_if(|subtag| is_script(subtag));
+    let region = subtags.next_if(|subtag| is_region(subtag));
+    LanguageTag {
+      language,
+      script,
+      region,
+      rest: subtags.collect(),
+    }
+  }
+}
+fn is_script(subtag: &str) -> bool {
+  subtag.len() == 4
+    && subtag
+      .chars()
+      .all(|character| character.is_ascii_alphabetic())
+}
+fn is_region(subtag: &str) -> bool {
+  (subtag.len() == 2
+    && subtag
+      .chars()
+      .all(|character| character.is_ascii_alphabetic()))
+    || (subtag.len() == 3 && subtag.chars().all(|character| character.is_ascii_digit()))
+}
+/// Converts a language tag to the canonical BCP 47 form, such as `en_GB` or `en-gb` to `en-GB`
+/// and `zh-hans-cn` to `zh-Hans-CN`.
+#[must_use]
+pub fn normalize_language_tag(tag: &str) -> String {
+  let tag = LanguageTag::parse(tag);
+  let script = tag.script.map(|script| {
+    let mut characters = script.chars();
+    characters
+      .next()
+      .map(|first| first.to_ascii_uppercase().to_string() + characters.as_str())
+      .unwrap_or_default()
+  });
+  let region = tag.region.map(|region| region.to_ascii_uppercase());
+  [Some(tag.language), script, region]
+    .into_iter()
+    .flatten()
+    .chain(tag.rest)
+    .collect::<Vec<String>>()
+    .join("-")
+}
+/// Ranks how well an available language serves a requested one. Lower values are better, `0`
+/// is an exact match, and `None` means that the primary languages differ. A missing script or
+/// region is preferred to a different one, so `en-US` falls back to `en` before `en-GB`, and
+/// `pt` matches `pt-BR`.
+#[must_use]
+pub fn language_distance(requested: &str, available: &str) -> Option<u8> {
+  let requested = LanguageTag::parse(requested);
+  let available = LanguageTag::parse(available);
+  if requested.language != available.language {
+    return None;
+  }
+  let script = match (requested.script, available.script) {
+    (requested, available) if requested == available => 0,
+    (Some(_), None) | (None, Some(_)) => 1,
+    _ => 4,
+  };
+  let region = match (requested.region, available.region) {
+    (requested, available) if requested == available => 0,
+    (Some(_), None) => 1,
+    (None, Some(_)) => 2,
+    _ => 3,
+  };
+  let rest = u8::from(requested.rest != available.rest);
+  Some(script + region + rest)
+}
+/// The distance of the best matching language in `languages`, if any is compatible.
+pub(crate) fn best_language_distance<'a>(
+  requested: &str,
+  languages: impl IntoIterator<Item = &'a String>,
+) -> Option<u8> {
+  languages
+    .into_iter()
+    .filter_map(|language| language_distance(requested, language))
+    .min()
+}
+#[cfg(test)]
+mod tests {
+  use super::*;
+  #[test]
+  fn normalizes_tags() {
+    assert_eq!(normalize_language_tag("en_GB"), "en-GB");
+    assert_eq!(normalize_language_tag("EN-gb"), "en-GB");
+    assert_eq!(normalize_language_tag("zh-hans-cn"), "zh-Hans-CN");
+    assert_eq!(normalize_language_tag("es-419"), "es-419");
+    assert_eq!(normalize_language_tag("de-CH-1996"), "de-CH-1996");
+    assert_eq!(normalize_language_tag("sr_latn"), "sr-Latn");
+    assert_eq!(normalize_language_tag("-fr--"), "fr");
+    assert_eq!(normalize_language_tag(""), "");
+  }
+  #[test]
+  fn ranks_language_distance() {
+    assert_eq!(language_distance("en-US", "en_us"), Some(0));
+    assert_eq!(language_distance("en-US", "en"), Some(1));
+    assert_eq!(language_distance("en-US", "en-GB"), Some(3));
+    assert_eq!(language_distance("pt", "pt-BR"), Some(2));
+    assert_eq!(language_distance("zh-Hant", "zh"), Some(1));
+    assert_eq!(language_distance("zh-Hant-TW", "zh-Hans-TW"), Some(4));
+    assert_eq!(language_distance("de-CH-1996", "de-CH"), Some(1));
+    assert_eq!(language_distance("en", "de"), None);
+    assert_eq!(language_distance("en", ""), None);
+  }
+  #[test]
+  fn prefers_a_missing_region_to_a_different_one() {
+    let distance = |available| language_distance("en-US", available).unwrap();
+    assert!(distance("en") < distance("en-GB"));
+    assert!(distance("en-US") < distance("en"));
+    let languages = ["fr".to_owned(), "en-GB".to_owned(), "en".to_owned()];
+    assert_eq!(best_language_distance("en-US", &languages), Some(1));
+    assert_eq!(best_language_distance("it", &languages), None);
+  }
+}
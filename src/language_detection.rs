@@ -0,0 +1,214 @@
+use crate::annotated_text::{AnnotatedText, TextSpan};
+use crate::metadata::Voice;
+use unicode_segmentation::UnicodeSegmentation;
+use whatlang::{Info, Lang};
+/// A run of text that was detected to be in one language and is spoken with a voice for it.
+pub(crate) struct DetectedLanguage {
+  pub(crate) offset: usize,
+  pub(crate) length: usize,
+  pub(crate) language: String,
+}
+/// Converts a detected language to the ISO 639-1 code that backends use for voice languages.
+fn language_code(language: Lang) -> &'static str {
+  match language {
+    Lang::Epo => "eo",
+    Lang::Eng => "en",
+    Lang::Rus => "ru",
+    Lang::Cmn => "zh",
+    Lang::Spa => "es",
+    Lang::Por => "pt",
+    Lang::Ita => "it",
+    Lang::Ben => "bn",
+    Lang::Fra => "fr",
+    Lang::Deu => "de",
+    Lang::Ukr => "uk",
+    Lang::Kat => "ka",
+    Lang::Ara => "ar",
+    Lang::Hin => "hi",
+    Lang::Jpn => "ja",
+    Lang::Heb => "he",
+    Lang::Yid => "yi",
+    Lang::Pol => "pl",
+    Lang::Amh => "am",
+    Lang::Jav => "jv",
+    Lang::Kor => "ko",
+    Lang::Nob => "nb",
+    Lang::Dan => "da",
+    Lang::Swe => "sv",
+    Lang::Fin => "fi",
+    Lang::Tur => "tr",
+    Lang::Nld => "nl",
+    Lang::Hun => "hu",
+    Lang::Ces => "cs",
+    Lang::Ell => "el",
+    Lang::Bul => "bg",
+    Lang::Bel => "be",
+    Lang::Mar => "mr",
+    Lang::Kan => "kn",
+    Lang::Ron => "ro",
+    Lang::Slv => "sl",
+    Lang::Hrv => "hr",
+    Lang::Srp => "sr",
+    Lang::Mkd => "mk",
+    Lang::Lit => "lt",
+    Lang::Lav => "lv",
+    Lang::Est => "et",
+    Lang::Tam => "ta",
+    Lang::Vie => "vi",
+    Lang::Urd => "ur",
+    Lang::Tha => "th",
+    Lang::Guj => "gu",
+    Lang::Uzb => "uz",
+    Lang::Pan => "pa",
+    Lang::Aze => "az",
+    Lang::Ind => "id",
+    Lang::Tel => "te",
+    Lang::Pes => "fa",
+    Lang::Mal => "ml",
+    Lang::Ori => "or",
+    Lang::Mya => "my",
+    Lang::Nep => "ne",
+    Lang::Sin => "si",
+    Lang::Khm => "km",
+    Lang::Tuk => "tk",
+    Lang::Aka => "ak",
+    Lang::Zul => "zu",
+    Lang::Sna => "sn",
+    Lang::Afr => "af",
+    Lang::Lat => "la",
+    Lang::Slk => "sk",
+    Lang::Cat => "ca",
+    Lang::Tgl => "tl",
+    Lang::Hye => "hy",
+  }
+}
+/// Finds the preferred voice for a language, accepting voices for any region of it.
+fn find_voice<'a>(language: &str, voices: &'a [Voice]) -> Option<&'a Voice> {
+  voices.iter().find(|voice| {
+    voice.languages.iter().any(|name| {
+      name == language
+        || name
+          .strip_prefix(language)
+          .is_some_and(|region| region.starts_with(['-', '_']))
+    })
+  })
+}
+/// Splits text into sentences, detects the language of each one and assigns it the preferred
+/// voice for that language from `voices`. Sentences whose language cannot be detected reliably
+/// keep the voice of the previous sentence, and those without a matching voice use the default
+/// voice.
+pub(crate) fn annotate_languages(
+  text: &str,
+  voices: &[Voice],
+) -> (AnnotatedText, Vec<DetectedLanguage>) {
+  let mut annotated_text = AnnotatedText::new();
+  let mut detected = Vec::new();
+  let mut current: Option<(&str, &Voice)> = None;
+  let mut span = String::new();
+  let mut span_offset = 0;
+  let mut offset = 0;
+  let mut flush = |span: &mut String, span_offset: usize, current: Option<(&str, &Voice)>| {
+    if span.is_empty() {
+      return;
+    }
+    let text = std::mem::take(span);
+    let length = text.chars().count();
+    annotated_text.spans.push(match current {
+      None => TextSpan::new(&text),
+      Some((language, voice)) => {
+        detected.push(DetectedLanguage {
+          offset: span_offset,
+          length,
+          language: language.to_owned(),
+        });
+        TextSpan::new(&text).language(language).voice(&voice.name)
+      }
+    });
+  };
+  for sentence in text.split_sentence_bounds() {
+    let choice = match whatlang::detect(sentence).filter(Info::is_reliable) {
+      Some(info) => {
+        let language = language_code(info.lang());
+        find_voice(language, voices).map(|voice| (language, voice))
+      }
+      None => current,
+    };
+    if choice.map(|(language, _)| language) != current.map(|(language, _)| language) {
+      flush(&mut span, span_offset, current);
+      span_offset = offset;
+      current = choice;
+    }
+    span.push_str(sentence);
+    offset += sentence.chars().count();
+  }
+  flush(&mut span, span_offset, current);
+  (annotated_text, detected)
+}
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::metadata::SpeechSynthesizerMetadata;
+  fn voice(name: &str, language: &str) -> Voice {
+    Voice {
+      synthesizer: SpeechSynthesizerMetadata {
+        name: "Test".to_owned(),
+        supports_speaking_to_audio_data: true,
+        supports_speech_parameters: true,
+        supports_pausing: true,
+      },
+      id: name.to_owned(),
+      display_name: name.to_owned(),
+      name: name.to_owned(),
+      languages: vec![language.to_owned()],
+      gender: None,
+      age: None,
+      variant: None,
+      quality: None,
+      supports_audio_data: true,
+      sample_rate: None,
+      priority: 0,
+    }
+  }
+  const ENGLISH: &str = "The weather is lovely today and we are going for a long walk. ";
+  const GERMAN: &str = "Die Straßen der schönen Stadt sind heute wirklich sehr ruhig und leer.";
+  #[test]
+  fn finds_voices_for_any_region() {
+    let voices = [voice("British", "en-GB"), voice("Swiss", "de_CH")];
+    assert_eq!(find_voice("en", &voices).unwrap().name, "British");
+    assert_eq!(find_voice("de", &voices).unwrap().name, "Swiss");
+    assert!(find_voice("e", &voices).is_none());
+    assert!(find_voice("fr", &voices).is_none());
+  }
+  #[test]
+  fn annotates_sentences_with_their_language() {
+    let voices = [voice("Alice", "en-US"), voice("Hans", "de")];
+    let text = format!("{ENGLISH}{GERMAN}");
+    let (annotated, detected) = annotate_languages(&text, &voices);
+    assert_eq!(annotated.to_plain_text(), text);
+    assert_eq!(
+      annotated.spans,
+      [
+        TextSpan::new(ENGLISH).language("en").voice("Alice"),
+        TextSpan::new(GERMAN).language("de").voice("Hans"),
+      ]
+    );
+    let english_length = ENGLISH.chars().count();
+    assert_eq!(detected.len(), 2);
+    assert_eq!(
+      (detected[0].offset, detected[0].length),
+      (0, english_length)
+    );
+    assert_eq!(detected[1].offset, english_length);
+    assert_eq!(detected[1].length, GERMAN.chars().count());
+    assert_eq!(detected[1].language, "de");
+  }
+  #[test]
+  fn uses_the_default_voice_without_a_match() {
+    let voices = [voice("Hans", "de")];
+    let text = format!("{ENGLISH}Ok. {GERMAN}");
+    let (annotated, detected) = annotate_languages(&text, &voices);
+    assert_eq!(annotated.spans[0], TextSpan::new(&format!("{ENGLISH}Ok. ")));
+    assert_eq!(detected.len(), 1);
+    assert_eq!(detected[0].offset, ENGLISH.chars().count() + 4);
+  }
+}
@@ -0,0 +1,241 @@
+use crate::error::OutputError;
+use crate::language::language_distance;
+use regex::{NoExpand, Regex, RegexBuilder};
+/// What a [`LexiconEntry`] matches.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LexiconPattern {
+  /// The text anywhere, even inside words.
+  Literal(String),
+  /// The text as a whole word.
+  Word(String),
+  /// A regular expression. The replacement can refer to its groups with `$1` or `${name}`.
+  Regex(String),
+}
+/// A replacement applied to text before it is synthesized, such as the spelling of a player name
+/// that engines pronounce correctly.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LexiconEntry {
+  pub pattern: LexiconPattern,
+  pub replacement: String,
+  /// The pronunciation in the phoneme mnemonics of eSpeak NG, which eSpeak NG uses instead of
+  /// the replacement in both plain text and SSML. The phonemes are spoken as a word of their own,
+  /// so an entry matching part of a word splits it, and like other entries, it does not match
+  /// text interrupted by SSML elements.
+  pub espeak_phonemes: Option<String>,
+  pub case_sensitive: bool,
+  /// Restricts the entry to text in a language compatible with this BCP 47 tag.
+  pub language: Option<String>,
+  /// Restricts the entry to one synthesizer.
+  pub synthesizer: Option<String>,
+}
+impl LexiconEntry {
+  fn new(pattern: LexiconPattern, replacement: &str) -> Self {
+    LexiconEntry {
+      pattern,
+      replacement: replacement.to_owned(),
+      espeak_phonemes: None,
+      case_sensitive: false,
+      language: None,
+      synthesizer: None,
+    }
+  }
+  #[must_use]
+  pub fn literal(text: &str, replacement: &str) -> Self {
+    LexiconEntry::new(LexiconPattern::Literal(text.to_owned()), replacement)
+  }
+  #[must_use]
+  pub fn word(word: &str, replacement: &str) -> Self {
+    LexiconEntry::new(LexiconPattern::Word(word.to_owned()), replacement)
+  }
+  #[must_use]
+  pub fn regex(regex: &str, replacement: &str) -> Self {
+    LexiconEntry::new(LexiconPattern::Regex(regex.to_owned()), replacement)
+  }
+  #[must_use]
+  pub fn espeak_phonemes(mut self, phonemes: &str) -> Self {
+    self.espeak_phonemes = Some(phonemes.to_owned());
+    self
+  }
+  #[must_use]
+  pub fn case_sensitive(mut self) -> Self {
+    self.case_sensitive = true;
+    self
+  }
+  #[must_use]
+  pub fn language(mut self, language: &str) -> Self {
+    self.language = Some(language.to_owned());
+    self
+  }
+  #[must_use]
+  pub fn synthesizer(mut self, synthesizer: &str) -> Self {
+    self.synthesizer = Some(synthesizer.to_owned());
+    self
+  }
+  fn compile(&self) -> Result<Regex, OutputError> {
+    let pattern = match &self.pattern {
+      LexiconPattern::Literal(text) => regex::escape(text),
+      LexiconPattern::Word(word) => format!(r"\b{}\b", regex::escape(word)),
+      LexiconPattern::Regex(regex) => regex.clone(),
+    };
+    RegexBuilder::new(&pattern)
+      .case_insensitive(!self.case_sensitive)
+      .build()
+      .map_err(OutputError::into_invalid_parameter)
+  }
+  fn applies_to(&self, synthesizer: &str, language: Option<&str>) -> bool {
+    self
+      .synthesizer
+      .as_ref()
+      .is_none_or(|name| name == synthesizer)
+      && self.language.as_ref().is_none_or(|entry_language| {
+        language.is_some_and(|language| language_distance(entry_language, language).is_some())
+      })
+  }
+}
+/// A list of [`LexiconEntry`]s, applied in order to all text spoken by a [`crate::Whisprs`]
+/// instance. Offsets of speech events refer to the text after the entries are applied.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(
+  feature = "serde",
+  derive(serde::Serialize, serde::Deserialize),
+  serde(try_from = "Vec<LexiconEntry>", into = "Vec<LexiconEntry>")
+)]
+pub struct Lexicon {
+  entries: Vec<(LexiconEntry, Regex)>,
+}
+impl Lexicon {
+  #[must_use]
+  pub fn new() -> Self {
+    Lexicon::default()
+  }
+  /// Adds an entry after the existing ones, failing if its regular expression is invalid.
+  pub fn add(&mut self, entry: LexiconEntry) -> Result<(), OutputError> {
+    let regex = entry.compile()?;
+    self.entries.push((entry, regex));
+    Ok(())
+  }
+  pub fn entries(&self) -> impl Iterator<Item = &LexiconEntry> {
+    self.entries.iter().map(|(entry, _)| entry)
+  }
+  #[must_use]
+  pub fn is_empty(&self) -> bool {
+    self.entries.is_empty()
+  }
+  /// Whether any entry only applies to some languages, in which case the language of the text
+  /// has to be known.
+  pub(crate) fn has_language_entries(&self) -> bool {
+    self.entries().any(|entry| entry.language.is_some())
+  }
+  /// Applies the entries for a synthesizer and language. Entries with eSpeak NG phonemes are
+  /// skipped if `skip_phonemes` is set, because the backend applies them itself.
+  pub(crate) fn apply(
+    &self,
+    text: &str,
+    synthesizer: &str,
+    language: Option<&str>,
+    skip_phonemes: bool,
+  ) -> String {
+    self.replace(text, synthesizer, language, &|entry| {
+      !skip_phonemes || entry.espeak_phonemes.is_none()
+    })
+  }
+  fn replace(
+    &self,
+    text: &str,
+    synthesizer: &str,
+    language: Option<&str>,
+    include: &dyn Fn(&LexiconEntry) -> bool,
+  ) -> String {
+    let mut text = text.to_owned();
+    for (entry, regex) in &self.entries {
+      if !entry.applies_to(synthesizer, language) || !include(entry) {
+        continue;
+      }
+      text = match entry.pattern {
+        LexiconPattern::Regex(_) => regex.replace_all(&text, entry.replacement.as_str()),
+        _ => regex.replace_all(&text, NoExpand(&entry.replacement)),
+      }
+      .into_owned();
+    }
+    text
+  }
+  /// Replaces the matches of entries with eSpeak NG phonemes by phoneme input, and returns
+  /// whether anything was replaced.
+  pub(crate) fn apply_espeak_phonemes(
+    &self,
+    text: &str,
+    synthesizer: &str,
+    language: Option<&str>,
+  ) -> (String, bool) {
+    let mut text = text.to_owned();
+    let mut replaced = false;
+    for (entry, regex) in &self.entries {
+      let Some(phonemes) = &entry.espeak_phonemes else {
+        continue;
+      };
+      if !entry.applies_to(synthesizer, language) || !regex.is_match(&text) {
+        continue;
+      }
+      replaced = true;
+      text = regex
+        .replace_all(&text, NoExpand(&format!(" [[{phonemes}]] ")))
+        .into_owned();
+    }
+    (text, replaced)
+  }
+}
+impl TryFrom<Vec<LexiconEntry>> for Lexicon {
+  type Error = OutputError;
+  fn try_from(entries: Vec<LexiconEntry>) -> Result<Self, Self::Error> {
+    let mut lexicon = Lexicon::new();
+    for entry in entries {
+      lexicon.add(entry)?;
+    }
+    Ok(lexicon)
+  }
+}
+impl From<Lexicon> for Vec<LexiconEntry> {
+  fn from(lexicon: Lexicon) -> Self {
+    lexicon
+      .entries
+      .into_iter()
+      .map(|(entry, _)| entry)
+      .collect()
+  }
+}
+#[cfg(test)]
+mod tests {
+  use super::*;
+  fn lexicon() -> Lexicon {
+    Lexicon::try_from(vec![
+      LexiconEntry::word("Notch", "Notsh").espeak_phonemes("n'0tS"),
+      LexiconEntry::literal("gg", "good game"),
+    ])
+    .unwrap()
+  }
+  #[test]
+  fn skips_phoneme_entries_for_backends_applying_them() {
+    let lexicon = lexicon();
+    assert_eq!(
+      lexicon.apply("gg Notch", "x", None, false),
+      "good game Notsh"
+    );
+    assert_eq!(
+      lexicon.apply("gg Notch", "x", None, true),
+      "good game Notch"
+    );
+  }
+  #[test]
+  fn replaces_phoneme_entries_with_phoneme_input() {
+    assert_eq!(
+      lexicon().apply_espeak_phonemes("hi Notch", "x", None),
+      ("hi  [[n'0tS]] ".to_owned(), true)
+    );
+    assert_eq!(
+      lexicon().apply_espeak_phonemes("hi", "x", None),
+      ("hi".to_owned(), false)
+    );
+  }
+}
@@ -1,133 +1,542 @@
 #![deny(clippy::all)]
 #![warn(clippy::pedantic)]
+pub mod annotated_text;
 pub mod audio;
-mod backends;
+pub mod backends;
+pub mod builder;
+pub mod capitals;
+mod characters;
 //mod c_api;
+#[cfg(feature = "async")]
+mod asynchronous;
 pub mod error;
+pub mod events;
+pub mod keys;
+pub mod language;
+#[cfg(feature = "language-detection")]
+mod language_detection;
 //mod jni;
+pub mod lexicon;
 pub mod metadata;
-use crate::audio::SpeechResult;
-#[cfg(target_os = "macos")]
-use crate::backends::av_speech_synthesizer::AvSpeechSynthesizer;
-use crate::backends::espeak_ng::EspeakNg;
-#[cfg(windows)]
-use crate::backends::jaws::Jaws;
-#[cfg(windows)]
-use crate::backends::nvda::Nvda;
-#[cfg(windows)]
-use crate::backends::one_core::OneCore;
-#[cfg(windows)]
-use crate::backends::sapi::Sapi;
-#[cfg(target_os = "linux")]
-use crate::backends::speech_dispatcher::SpeechDispatcher;
-use crate::backends::{Backend, BrailleBackend};
+mod player;
+pub mod priority;
+#[cfg(feature = "profiles")]
+pub mod profile;
+pub mod punctuation;
+pub mod request;
+pub mod ssml;
+pub mod text_format;
+pub mod utterance;
+use crate::annotated_text::AnnotatedText;
+use crate::audio::{SpeechResult, SpeechStream};
+use crate::backends::{
+  Backend, BackendOptions, BrailleBackend, SpeechSynthesizerToAudioData,
+  SpeechSynthesizerToAudioOutput,
+};
+use crate::builder::BackendInitialization;
+pub use crate::builder::WhisprsBuilder;
+use crate::capitals::{CapitalIcons, Capitals};
 use crate::error::OutputError;
-use crate::metadata::{BrailleBackendMetadata, SpeechSynthesizerMetadata, Voice};
+use crate::events::{EventBus, SpeechEvent, SpeechEventKind};
+use crate::keys::KeyCombination;
+use crate::language::language_distance;
+use crate::lexicon::Lexicon;
+use crate::metadata::{
+  BrailleBackendMetadata, SpeechSynthesizerMetadata, Voice, VoiceFilter, VoiceVariant,
+};
+use crate::player::{Player, PlayerSource};
+use crate::priority::{Preemption, Priority};
+#[cfg(feature = "profiles")]
+use crate::profile::ProfileStore;
+use crate::punctuation::Punctuation;
+use crate::request::{SpeechContent, SpeechRequest};
+use crate::ssml::Ssml;
+use crate::text_format::{MinecraftText, TextFormat};
+use crate::utterance::{Utterance, UtteranceState};
 use anyhow::anyhow;
-use rodio::{buffer::SamplesBuffer, OutputStream, Sink};
+use rodio::{OutputStream, Sink};
 use std::any::Any;
 use std::cell::{Cell, OnceCell, RefCell};
-use std::collections::HashMap;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{mpsc, Arc, Mutex};
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex, PoisonError, RwLock};
 use std::thread;
+use std::time::{Duration, Instant};
 thread_local! {
   static BACKENDS: RefCell<HashMap<String, Box<dyn Backend>>> = RefCell::new(HashMap::new());
+  static PRIORITIES: RefCell<HashMap<String, u8>> = RefCell::new(HashMap::new());
+  static LAST_MESSAGE_PRIORITIES: RefCell<HashMap<String, Priority>> = RefCell::new(HashMap::new());
+  static LEXICON: RefCell<Lexicon> = RefCell::new(Lexicon::new());
+  static NATIVE_LEXICON_BACKENDS: RefCell<HashSet<String>> = RefCell::new(HashSet::new());
+  static PUNCTUATION: RefCell<Option<Punctuation>> = const { RefCell::new(None) };
+  static CAPITALS: Cell<Option<Capitals>> = const { Cell::new(None) };
   static OUTPUT_STREAM: OnceCell<OutputStream> = const {OnceCell::new() };
   static SINK: OnceCell<Sink> = const { OnceCell::new() };
+  #[cfg(feature = "language-detection")]
+  static LANGUAGE_DETECTION: Cell<bool> = const { Cell::new(false) };
 }
-fn stop_audio() -> Result<(), OutputError> {
-  SINK.with(|cell| {
-    cell
-      .get()
-      .ok_or(OutputError::into_unknown(anyhow!("SINK contains nothing")))?
-      .stop();
-    Ok(())
-  })
+fn open_audio_output() -> Result<(), OutputError> {
+  if SINK.with(|cell| cell.get().is_some()) {
+    return Ok(());
+  }
+  let (output_stream, output_stream_handle) =
+    OutputStream::try_default().map_err(OutputError::into_no_audio_output)?;
+  let sink = Sink::try_new(&output_stream_handle).map_err(OutputError::into_no_audio_output)?;
+  let _result = OUTPUT_STREAM.with(|cell| cell.set(output_stream));
+  let _result = SINK.with(|cell| cell.set(sink));
+  Ok(())
 }
-fn play_audio(result: &SpeechResult) -> Result<(), OutputError> {
-  let buffer = result
-    .pcm
-    .chunks_exact(2)
-    .map(|chunk| i16::from_le_bytes([chunk[0], chunk[1]]))
-    .collect::<Vec<i16>>();
-  let source = SamplesBuffer::new(1, result.sample_rate, buffer);
-  SINK.with(|cell| {
-    cell
-      .get()
-      .ok_or(OutputError::into_unknown(anyhow!("SINK contains nothing")))?
-      .append(source);
-    Ok(())
-  })
+fn play_audio(
+  player: &Arc<Player>,
+  utterance: &Utterance,
+  result: &SpeechResult,
+  priority: Priority,
+) -> Result<(), OutputError> {
+  open_audio_output()?;
+  if player.enqueue(utterance, result, priority) {
+    SINK.with(|cell| {
+      cell
+        .get()
+        .ok_or(OutputError::into_unknown(anyhow!("SINK contains nothing")))?
+        .append(PlayerSource {
+          player: player.clone(),
+        });
+      Ok::<(), OutputError>(())
+    })?;
+  }
+  Ok(())
 }
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
 type OperationOk = Box<dyn Any + Send + Sync>;
 type OperationResult = Result<OperationOk, OutputError>;
 type Operation = Box<dyn FnOnce() -> OperationResult + Send + Sync>;
+/// What a speak operation says.
+enum SpeechInput {
+  Text(String),
+  Ssml(Ssml),
+  Annotated(AnnotatedText),
+  /// Text spelled one character at a time.
+  Spelled(String),
+  /// Text already spelled with the NATO phonetic alphabet, spoken as is.
+  Phonetic(String),
+  /// A key, and its name in the language of the speech.
+  Key {
+    key: KeyCombination,
+    text: String,
+  },
+}
+impl SpeechInput {
+  /// Replaces all text with the result of `map`, which also receives the language of the text,
+  /// or `language` for text that is not marked with another one.
+  fn map_text(self, language: Option<&str>, map: &dyn Fn(&str, Option<&str>) -> String) -> Self {
+    match self {
+      SpeechInput::Text(text) => SpeechInput::Text(map(&text, language)),
+      SpeechInput::Ssml(mut ssml) => {
+        ssml.map_text(language, map);
+        SpeechInput::Ssml(ssml)
+      }
+      SpeechInput::Annotated(mut text) => {
+        for span in &mut text.spans {
+          span.text = map(&span.text, span.language.as_deref().or(language));
+        }
+        SpeechInput::Annotated(text)
+      }
+      input @ (SpeechInput::Spelled(_) | SpeechInput::Phonetic(_) | SpeechInput::Key { .. }) => {
+        input
+      }
+    }
+  }
+  /// Applies the lexicon to the text sent to a synthesizer. `language` is the language of text
+  /// that is not marked with another one, if it is known.
+  fn apply_lexicon(self, synthesizer: &str, language: Option<&str>) -> Self {
+    LEXICON.with_borrow(|lexicon| {
+      if lexicon.is_empty() {
+        return self;
+      }
+      let native = NATIVE_LEXICON_BACKENDS.with_borrow(|backends| backends.contains(synthesizer));
+      self.map_text(language, &|text, language| {
+        lexicon.apply(text, synthesizer, language, native)
+      })
+    })
+  }
+  /// Sets the punctuation level of a backend, falling back to the level of the worker thread,
+  /// and replaces punctuation with its names if the backend cannot speak it itself.
+  fn apply_punctuation(
+    self,
+    backend: &dyn Backend,
+    punctuation: Option<&Punctuation>,
+  ) -> Result<Self, OutputError> {
+    PUNCTUATION.with_borrow(|default| {
+      let punctuation = punctuation.or(default.as_ref());
+      if backend.set_punctuation(punctuation)? {
+        return Ok(self);
+      }
+      Ok(match punctuation {
+        Some(punctuation) => self.map_text(None, &|text, _| punctuation.speak_names(text)),
+        None => self,
+      })
+    })
+  }
+  /// Sets how a backend indicates capital letters, falling back to the mode of the worker
+  /// thread, and emulates the mode if the backend cannot. `audio_data` is whether the input is
+  /// synthesized to audio data, in which case sound icons are requested by returning `true`
+  /// instead of being emulated with text.
+  fn apply_capitals(
+    self,
+    backend: &dyn Backend,
+    capitals: Option<Capitals>,
+    pitch: Option<u8>,
+    audio_data: bool,
+  ) -> Result<(Self, bool), OutputError> {
+    let capitals = capitals.or(CAPITALS.get());
+    if backend.set_capitals(capitals)? {
+      return Ok((self, false));
+    }
+    let say_cap = |input: Self| input.map_text(None, &|text, _| capitals::say_cap(text));
+    Ok(match (capitals, self) {
+      (None | Some(Capitals::None), input) => (input, false),
+      (Some(Capitals::Icon), input) if audio_data => (input, true),
+      (Some(Capitals::Pitch), SpeechInput::Text(text)) if !text.is_empty() => (
+        SpeechInput::Annotated(capitals::raise_pitch(
+          &AnnotatedText::new().text(&text),
+          pitch,
+        )),
+        false,
+      ),
+      (Some(Capitals::Pitch), SpeechInput::Annotated(text)) => (
+        SpeechInput::Annotated(capitals::raise_pitch(&text, pitch)),
+        false,
+      ),
+      (_, input) => (say_cap(input), false),
+    })
+  }
+  /// Validates the content of a request before it is sent to the worker thread.
+  fn from_request(whisprs: &Whisprs, request: &SpeechRequest) -> Result<Self, OutputError> {
+    Ok(match &request.content {
+      SpeechContent::Text(text) => whisprs.text_input(text, request.format, request.volume),
+      SpeechContent::Ssml(ssml) => SpeechInput::Ssml(Ssml::parse(ssml)?),
+      SpeechContent::Annotated(text) => {
+        Whisprs::check_annotated_text(text)?;
+        SpeechInput::Annotated(text.clone())
+      }
+      SpeechContent::Spelled {
+        text,
+        phonetic: false,
+      } => SpeechInput::Spelled(text.clone()),
+      SpeechContent::Spelled {
+        text,
+        phonetic: true,
+      } => SpeechInput::Phonetic(characters::spell(text, true)),
+      SpeechContent::Key(key) => SpeechInput::Key {
+        key: key.clone(),
+        text: whisprs.key_name(key, request.language.as_deref()),
+      },
+    })
+  }
+  fn speak_streaming(
+    &self,
+    synthesizer: &dyn SpeechSynthesizerToAudioData,
+    voice: Option<&str>,
+    language: Option<&str>,
+    rate: Option<u8>,
+    volume: Option<u8>,
+    pitch: Option<u8>,
+    on_chunk: &mut dyn FnMut(SpeechResult) -> bool,
+  ) -> Result<(), OutputError> {
+    match self {
+      SpeechInput::Text(text) | SpeechInput::Phonetic(text) | SpeechInput::Key { text, .. } => {
+        synthesizer.speak_streaming(voice, language, rate, volume, pitch, text, on_chunk)
+      }
+      SpeechInput::Ssml(ssml) => {
+        synthesizer.speak_ssml_streaming(voice, language, rate, volume, pitch, ssml, on_chunk)
+      }
+      SpeechInput::Annotated(text) => {
+        text.speak_streaming(synthesizer, voice, language, rate, volume, pitch, on_chunk)
+      }
+      SpeechInput::Spelled(text) => {
+        synthesizer.spell_streaming(voice, language, rate, volume, pitch, text, on_chunk)
+      }
+    }
+  }
+  fn speak(
+    &self,
+    synthesizer: &dyn SpeechSynthesizerToAudioOutput,
+    voice: Option<&str>,
+    language: Option<&str>,
+    rate: Option<u8>,
+    volume: Option<u8>,
+    pitch: Option<u8>,
+    interrupt: bool,
+    priority: Priority,
+    utterance: &Utterance,
+  ) -> Result<(), OutputError> {
+    match self {
+      SpeechInput::Text(text) | SpeechInput::Phonetic(text) => synthesizer.speak(
+        voice, language, rate, volume, pitch, text, interrupt, priority, utterance,
+      ),
+      SpeechInput::Ssml(ssml) => synthesizer.speak_ssml(
+        voice, language, rate, volume, pitch, ssml, interrupt, priority, utterance,
+      ),
+      SpeechInput::Annotated(text) => synthesizer.speak_ssml(
+        voice,
+        language,
+        rate,
+        volume,
+        pitch,
+        &Ssml::from_annotated_text(text),
+        interrupt,
+        priority,
+        utterance,
+      ),
+      SpeechInput::Spelled(text) => synthesizer.spell(
+        voice, language, rate, volume, pitch, text, interrupt, priority, utterance,
+      ),
+      SpeechInput::Key { key, text } => {
+        if !synthesizer.speak_key(
+          voice, language, rate, volume, pitch, key, interrupt, priority, utterance,
+        )? {
+          synthesizer.speak(
+            voice, language, rate, volume, pitch, text, interrupt, priority, utterance,
+          )?;
+        }
+        Ok(())
+      }
+    }
+  }
+}
+/// Counts calls to [`Whisprs::stop_speech`], so that speech requested before a stop is cancelled
+/// even if it is still waiting for the worker thread or being synthesized.
+#[derive(Default)]
+struct Stops {
+  generation: AtomicU64,
+  /// The generation after the last stop of each synthesizer, or of all of them under `None`.
+  stopped: Mutex<HashMap<Option<String>, u64>>,
+}
+impl Stops {
+  fn current(&self) -> u64 {
+    self.generation.load(Ordering::SeqCst)
+  }
+  fn stop(&self, synthesizer: Option<&str>) {
+    let mut stopped = self.stopped.lock().unwrap_or_else(PoisonError::into_inner);
+    let generation = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+    stopped.insert(synthesizer.map(std::borrow::ToOwned::to_owned), generation);
+  }
+  /// Whether `synthesizer` was stopped after speech was requested in `generation`.
+  fn is_stopped(&self, synthesizer: &str, generation: u64) -> bool {
+    let stopped = self.stopped.lock().unwrap_or_else(PoisonError::into_inner);
+    [None, Some(synthesizer.to_owned())]
+      .iter()
+      .any(|name| stopped.get(name).is_some_and(|stop| *stop > generation))
+  }
+}
 pub struct Whisprs {
   operation_tx: Mutex<mpsc::Sender<(Operation, mpsc::Sender<OperationResult>)>>,
   should_stop: Arc<AtomicBool>,
   thread_handle: Mutex<Cell<Option<thread::JoinHandle<()>>>>,
+  initialization_report: Vec<BackendInitialization>,
+  player: Arc<Player>,
+  events: Arc<EventBus>,
+  next_utterance_id: AtomicU64,
+  stops: Arc<Stops>,
+  minecraft_translations: RwLock<HashMap<String, String>>,
+  key_names: RwLock<HashMap<String, HashMap<String, String>>>,
+  #[cfg(feature = "profiles")]
+  profiles: ProfileStore,
 }
 impl Whisprs {
   pub fn new() -> Result<Self, OutputError> {
+    WhisprsBuilder::new().build()
+  }
+  fn from_builder(
+    #[cfg_attr(not(feature = "profiles"), allow(unused_mut))] mut builder: WhisprsBuilder,
+  ) -> Result<Self, OutputError> {
+    #[cfg(feature = "profiles")]
+    let profiles = ProfileStore::new(builder.profiles.take(), builder.profiles_file.take())?;
     let (operation_tx, operation_rx) =
       mpsc::channel::<(Operation, mpsc::Sender<OperationResult>)>();
     let should_stop = Arc::new(AtomicBool::new(false));
-    let (result_tx, result_rx) = mpsc::channel::<Result<(), OutputError>>();
+    let (result_tx, result_rx) = mpsc::channel::<Result<Vec<BackendInitialization>, OutputError>>();
     let thread_should_stop = should_stop.clone();
     let thread_handle = thread::spawn(move || {
       let closure = || {
-        let (output_stream, output_stream_handle) =
-          OutputStream::try_default().map_err(OutputError::into_initialize_failed)?;
-        let sink =
-          Sink::try_new(&output_stream_handle).map_err(OutputError::into_initialize_failed)?;
-        let _result = OUTPUT_STREAM.with(|cell| cell.set(output_stream));
-        let _result = SINK.with(|cell| cell.set(sink));
-        let mut backends: Vec<Result<Box<dyn Backend>, OutputError>> = Vec::new();
-        backends.push(EspeakNg::new().map(|value| Box::new(value) as Box<dyn Backend>));
-        #[cfg(windows)]
-        {
-          backends.push(Sapi::new().map(|value| Box::new(value) as Box<dyn Backend>));
-          backends.push(OneCore::new().map(|value| Box::new(value) as Box<dyn Backend>));
-          backends.push(Jaws::new().map(|value| Box::new(value) as Box<dyn Backend>));
-          backends.push(Nvda::new().map(|value| Box::new(value) as Box<dyn Backend>));
-        }
-        #[cfg(target_os = "linux")]
-        {
-          backends.push(SpeechDispatcher::new().map(|value| Box::new(value) as Box<dyn Backend>));
+        let WhisprsBuilder {
+          backends,
+          disabled,
+          priorities,
+          options,
+          headless,
+          #[cfg(feature = "language-detection")]
+          language_detection,
+          lexicon,
+          #[cfg(feature = "profiles")]
+            profiles: _,
+          #[cfg(feature = "profiles")]
+            profiles_file: _,
+        } = builder;
+        if !headless {
+          open_audio_output().map_err(OutputError::into_initialize_failed)?;
         }
-        #[cfg(target_os = "macos")]
-        {
-          backends
-            .push(AvSpeechSynthesizer::new().map(|value| Box::new(value) as Box<dyn Backend>));
+        let mut report = Vec::new();
+        for (name, constructor) in backends {
+          if disabled.contains(&name) {
+            continue;
+          }
+          let result =
+            constructor(&options.get(&name).cloned().unwrap_or_default()).and_then(|backend| {
+              if backend.name() != name {
+                return Err(OutputError::into_initialize_failed(anyhow!(
+                  "The backend added as {name} reports the name {}",
+                  backend.name()
+                )));
+              }
+              BACKENDS.with_borrow_mut(|backends| backends.insert(name.clone(), backend));
+              Ok(())
+            });
+          report.push(BackendInitialization { name, result });
         }
-        BACKENDS.set(
-          backends
-            .into_iter()
-            .flatten()
-            .map(|backend| (backend.name(), backend))
-            .collect(),
-        );
-        Ok(())
+        PRIORITIES.set(priorities);
+        Whisprs::install_lexicon(lexicon)?;
+        #[cfg(feature = "language-detection")]
+        LANGUAGE_DETECTION.set(language_detection);
+        Ok(report)
       };
       result_tx.send(closure()).unwrap();
-      for (operation, sender) in operation_rx {
-        sender.send(operation()).unwrap();
-        if thread_should_stop.load(Ordering::Relaxed) {
-          return;
+      loop {
+        match operation_rx.recv_timeout(POLL_INTERVAL) {
+          Ok((operation, sender)) => {
+            let _result = sender.send(operation());
+            if thread_should_stop.load(Ordering::Relaxed) {
+              return;
+            }
+          }
+          Err(mpsc::RecvTimeoutError::Timeout) => BACKENDS.with_borrow(|backends| {
+            for synthesizer in backends
+              .values()
+              .filter_map(|backend| backend.as_speech_synthesizer_to_audio_output())
+            {
+              let _result = synthesizer.poll();
+            }
+          }),
+          Err(mpsc::RecvTimeoutError::Disconnected) => return,
         }
       }
     });
-    result_rx
+    let initialization_report = result_rx
       .recv()
       .map_err(OutputError::into_initialize_failed)??;
     Ok(Whisprs {
       operation_tx: Mutex::new(operation_tx),
       should_stop,
       thread_handle: Mutex::new(Cell::new(Some(thread_handle))),
+      initialization_report,
+      player: Arc::new(Player::new()),
+      events: Arc::new(EventBus::new()),
+      next_utterance_id: AtomicU64::new(0),
+      stops: Arc::new(Stops::default()),
+      minecraft_translations: RwLock::new(HashMap::new()),
+      key_names: RwLock::new(HashMap::new()),
+      #[cfg(feature = "profiles")]
+      profiles,
     })
   }
-  fn perform_operation(&self, closure: Operation) -> OperationResult {
+  /// Reports which backends were initialized when this instance was built, and why the others
+  /// failed.
+  #[must_use]
+  pub fn initialization_report(&self) -> &[BackendInitialization] {
+    &self.initialization_report
+  }
+  /// Returns a receiver for the events of every utterance spoken by this instance from now on.
+  /// The subscription ends when the receiver is dropped.
+  #[must_use]
+  pub fn subscribe(&self) -> mpsc::Receiver<SpeechEvent> {
+    let (sender, receiver) = mpsc::channel();
+    self
+      .events
+      .subscribe(Box::new(move |event| sender.send(event.clone()).is_ok()));
+    receiver
+  }
+  /// Calls `callback` with every event of every utterance spoken by this instance from now on.
+  /// Callbacks run on a dedicated thread, one event at a time.
+  pub fn subscribe_with<F>(&self, mut callback: F)
+  where
+    F: FnMut(&SpeechEvent) + Send + 'static,
+  {
+    self.events.subscribe(Box::new(move |event| {
+      callback(event);
+      true
+    }));
+  }
+  fn backend_priority(name: &str) -> Option<u8> {
+    PRIORITIES.with_borrow(|priorities| priorities.get(name).copied())
+  }
+  /// Constructs a backend with `options` on the worker thread and makes it available to every
+  /// method of this instance. A backend already registered under the same name is replaced.
+  pub fn register_backend<T>(&self, options: &BackendOptions) -> Result<(), OutputError>
+  where
+    T: Backend + 'static,
+  {
+    let options = options.clone();
+    self.register_backend_with(move || {
+      T::new_with_options(&options).map(|value| Box::new(value) as Box<dyn Backend>)
+    })
+  }
+  /// Like [`Whisprs::register_backend`], but uses `constructor` to create the backend, so that it
+  /// can be configured before it is registered.
+  pub fn register_backend_with<F>(&self, constructor: F) -> Result<(), OutputError>
+  where
+    F: FnOnce() -> Result<Box<dyn Backend>, OutputError> + Send + Sync + 'static,
+  {
+    let closure = move || {
+      let backend = constructor()?;
+      let native = LEXICON.with_borrow(|lexicon| backend.set_lexicon(lexicon))?;
+      NATIVE_LEXICON_BACKENDS.with_borrow_mut(|backends| {
+        if native {
+          backends.insert(backend.name());
+        } else {
+          backends.remove(&backend.name());
+        }
+      });
+      BACKENDS.with_borrow_mut(|backends| backends.insert(backend.name(), backend));
+      Ok(Box::new(()) as OperationOk)
+    };
+    self.perform_operation(Box::new(closure))?;
+    Ok(())
+  }
+  /// Removes the backend registered under `name`, including built-in backends.
+  pub fn unregister_backend(&self, name: &str) -> Result<(), OutputError> {
+    let name = name.to_owned();
+    let closure = move || {
+      BACKENDS.with_borrow_mut(|backends| {
+        backends
+          .remove(&name)
+          .ok_or(OutputError::into_backend_not_found(&name))?;
+        NATIVE_LEXICON_BACKENDS.with_borrow_mut(|backends| backends.remove(&name));
+        Ok(Box::new(()) as OperationOk)
+      })
+    };
+    self.perform_operation(Box::new(closure))?;
+    Ok(())
+  }
+  pub fn list_backends(&self) -> Result<Vec<String>, OutputError> {
+    let closure = || {
+      BACKENDS.with_borrow(|backends| {
+        let mut names = backends.keys().cloned().collect::<Vec<String>>();
+        names.sort_unstable();
+        Ok(Box::new(names) as OperationOk)
+      })
+    };
+    let result = self
+      .perform_operation(Box::new(closure))?
+      .downcast()
+      .map_err(|_| {
+        OutputError::into_unknown(anyhow!("Failed to downcast received return value"))
+      })?;
+    Ok(*result)
+  }
+  fn send_operation(
+    &self,
+    closure: Operation,
+  ) -> Result<mpsc::Receiver<OperationResult>, OutputError> {
     let (result_tx, result_rx) = mpsc::channel();
     self
       .operation_tx
@@ -135,51 +544,78 @@ impl Whisprs {
       .map_err(|_| OutputError::into_unknown(anyhow!("Failed to lock operation_tx")))?
       .send((closure, result_tx))
       .map_err(OutputError::into_unknown)?;
-    result_rx.recv().map_err(OutputError::into_unknown)?
+    Ok(result_rx)
   }
-  fn internal_list_voices(
-    synthesizer: Option<&str>,
-    name: Option<&str>,
-    language: Option<&str>,
-    needs_audio_data: bool,
-  ) -> Result<Vec<Voice>, OutputError> {
+  fn perform_operation(&self, closure: Operation) -> OperationResult {
+    self
+      .send_operation(closure)?
+      .recv()
+      .map_err(OutputError::into_unknown)?
+  }
+  fn internal_list_voices(filter: &VoiceFilter) -> Result<Vec<Voice>, OutputError> {
     BACKENDS.with_borrow(|backends| {
       let mut voices = backends
         .values()
-        .filter(|backend| synthesizer.is_none_or(|synthesizer| backend.name() == synthesizer))
+        .filter(|backend| {
+          filter
+            .synthesizer
+            .as_ref()
+            .is_none_or(|synthesizer| backend.name() == *synthesizer)
+        })
         .filter(|synthesizer| {
-          !needs_audio_data || synthesizer.as_speech_synthesizer_to_audio_data().is_some()
+          !filter.needs_audio_data || synthesizer.as_speech_synthesizer_to_audio_data().is_some()
         })
         .flat_map(|backend| backend.list_voices())
         .flatten()
-        .filter(|voice| {
-          name.is_none_or(|name| voice.name == name)
-            && language.is_none_or(|name| {
-              voice.languages.is_empty() || voice.languages.iter().any(|language| language == name)
-            })
+        .filter(|voice| filter.matches(voice))
+        .filter_map(|voice| {
+          let distance = filter.language_distance(&voice)?;
+          let priority = Whisprs::backend_priority(&voice.synthesizer.name);
+          Some((priority.unwrap_or(voice.priority), distance, voice))
         })
-        .collect::<Vec<Voice>>();
-      voices.sort_unstable_by_key(|voice| (voice.priority, voice.name.clone()));
-      Ok(voices)
+        .collect::<Vec<(u8, u8, Voice)>>();
+      voices.sort_unstable_by_key(|(priority, distance, voice)| {
+        (*distance, *priority, voice.priority, voice.name.clone())
+      });
+      Ok(
+        voices
+          .into_iter()
+          .map(|(priority, _, voice)| Voice { priority, ..voice })
+          .collect(),
+      )
     })
   }
-  pub fn list_voices(
-    &self,
-    synthesizer: Option<&str>,
-    name: Option<&str>,
-    language: Option<&str>,
-    needs_audio_data: bool,
-  ) -> Result<Vec<Voice>, OutputError> {
+  pub fn list_voices(&self, filter: &VoiceFilter) -> Result<Vec<Voice>, OutputError> {
+    let operation = Whisprs::list_voices_operation(filter);
+    let result = self.perform_operation(operation)?.downcast().map_err(|_| {
+      OutputError::into_unknown(anyhow!("Failed to downcast received return value"))
+    })?;
+    Ok(*result)
+  }
+  fn list_voices_operation(filter: &VoiceFilter) -> Operation {
+    let filter = filter.clone();
+    let closure = move || Ok(Box::new(Whisprs::internal_list_voices(&filter)?) as OperationOk);
+    Box::new(closure)
+  }
+  /// Lists the variants that can be applied to the voices of a synthesizer, or of all
+  /// synthesizers, with the `variant` field of a [`SpeechRequest`].
+  pub fn list_variants(&self, synthesizer: Option<&str>) -> Result<Vec<VoiceVariant>, OutputError> {
     let synthesizer = synthesizer.map(std::borrow::ToOwned::to_owned);
-    let name = name.map(std::borrow::ToOwned::to_owned);
-    let language = language.map(std::borrow::ToOwned::to_owned);
     let closure = move || {
-      Ok(Box::new(Whisprs::internal_list_voices(
-        synthesizer.as_deref(),
-        name.as_deref(),
-        language.as_deref(),
-        needs_audio_data,
-      )?) as OperationOk)
+      BACKENDS.with_borrow(|backends| {
+        let mut variants = backends
+          .values()
+          .filter(|backend| {
+            synthesizer
+              .as_ref()
+              .is_none_or(|synthesizer| backend.name() == *synthesizer)
+          })
+          .flat_map(|backend| backend.list_variants())
+          .flatten()
+          .collect::<Vec<VoiceVariant>>();
+        variants.sort_unstable_by(|first, second| first.name.cmp(&second.name));
+        Ok(Box::new(variants) as OperationOk)
+      })
     };
     let result = self
       .perform_operation(Box::new(closure))?
@@ -234,6 +670,10 @@ impl Whisprs {
         let backends = backends
           .values()
           .filter_map(|backend| backend.braille_metadata())
+          .map(|backend| BrailleBackendMetadata {
+            priority: Whisprs::backend_priority(&backend.name).unwrap_or(backend.priority),
+            ..backend
+          })
           .collect::<Vec<BrailleBackendMetadata>>();
         Ok(Box::new(backends) as OperationOk)
       })
@@ -255,7 +695,26 @@ impl Whisprs {
     let synthesizer = match (synthesizer, voice, language) {
       (Some(synthesizer), _, _) => synthesizer.to_owned(),
       (None, voice_name, language) => {
-        let voices = Whisprs::internal_list_voices(None, voice_name, language, needs_audio_data)?;
+        let filter = VoiceFilter {
+          name: voice_name.map(std::borrow::ToOwned::to_owned),
+          language: language.map(std::borrow::ToOwned::to_owned),
+          needs_audio_data,
+          ..VoiceFilter::default()
+        };
+        let mut voices = Whisprs::internal_list_voices(&filter)?;
+        if let (true, Some((base, variant))) = (
+          voices.is_empty(),
+          voice_name.and_then(|voice| voice.rsplit_once('+')),
+        ) {
+          let filter = VoiceFilter {
+            name: Some(base.to_owned()),
+            ..filter
+          };
+          voices = Whisprs::internal_list_voices(&filter)?
+            .into_iter()
+            .filter(|voice| Whisprs::has_variant(&voice.synthesizer.name, variant))
+            .collect();
+        }
         voices
           .first()
           .ok_or(match (voice, language) {
@@ -270,6 +729,178 @@ impl Whisprs {
     };
     Ok(synthesizer)
   }
+  /// Chooses the best matching voice of a synthesizer when only a language is requested, so
+  /// that backends receive a voice that exists even if no voice has exactly that language.
+  fn resolve_voice(
+    synthesizer: &str,
+    voice: Option<&str>,
+    language: Option<&str>,
+    needs_audio_data: bool,
+  ) -> Result<Option<String>, OutputError> {
+    let (None, Some(language)) = (voice, language) else {
+      return Ok(voice.map(std::borrow::ToOwned::to_owned));
+    };
+    let filter = VoiceFilter {
+      synthesizer: Some(synthesizer.to_owned()),
+      language: Some(language.to_owned()),
+      needs_audio_data,
+      ..VoiceFilter::default()
+    };
+    let voices = Whisprs::internal_list_voices(&filter)?;
+    Ok(
+      voices
+        .into_iter()
+        .find(|voice| !voice.languages.is_empty())
+        .map(|voice| voice.name),
+    )
+  }
+  /// Chooses the synthesizer and the name of the voice passed to it for a speech request,
+  /// applying the requested variant to the voice.
+  fn select_voice(
+    synthesizer: Option<&str>,
+    voice: Option<&str>,
+    variant: Option<&str>,
+    language: Option<&str>,
+    needs_audio_data: bool,
+  ) -> Result<(String, Option<String>), OutputError> {
+    let synthesizer_name =
+      Whisprs::filter_synthesizers(synthesizer, voice, language, needs_audio_data)?;
+    let voice = Whisprs::resolve_voice(&synthesizer_name, voice, language, needs_audio_data)?;
+    let Some(variant) = variant else {
+      return Ok((synthesizer_name, voice));
+    };
+    BACKENDS.with_borrow(|backends| {
+      let backend = backends
+        .get(&synthesizer_name)
+        .ok_or(OutputError::into_backend_not_found(&synthesizer_name))?;
+      if !backend
+        .list_variants()?
+        .iter()
+        .any(|candidate| candidate.name == variant)
+      {
+        return Err(OutputError::into_variant_not_found(variant));
+      }
+      let voice = backend
+        .voice_with_variant(voice.as_deref(), variant)
+        .ok_or(OutputError::into_variant_not_found(variant))?;
+      Ok((synthesizer_name, Some(voice)))
+    })
+  }
+  /// Whether a synthesizer has a variant with the given name.
+  fn has_variant(synthesizer: &str, variant: &str) -> bool {
+    BACKENDS.with_borrow(|backends| {
+      backends
+        .get(synthesizer)
+        .and_then(|backend| backend.list_variants().ok())
+        .is_some_and(|variants| variants.iter().any(|candidate| candidate.name == variant))
+    })
+  }
+  /// Makes `lexicon` the lexicon of the worker thread and gives it to every backend.
+  fn install_lexicon(lexicon: Lexicon) -> Result<(), OutputError> {
+    let native = BACKENDS.with_borrow(|backends| {
+      backends
+        .values()
+        .filter_map(|backend| match backend.set_lexicon(&lexicon) {
+          Ok(true) => Some(Ok(backend.name())),
+          Ok(false) => None,
+          Err(error) => Some(Err(error)),
+        })
+        .collect::<Result<HashSet<String>, OutputError>>()
+    })?;
+    NATIVE_LEXICON_BACKENDS.set(native);
+    LEXICON.set(lexicon);
+    Ok(())
+  }
+  /// Replaces the pronunciation lexicon applied to all text spoken from now on.
+  pub fn set_lexicon(&self, lexicon: Lexicon) -> Result<(), OutputError> {
+    let closure = move || {
+      Whisprs::install_lexicon(lexicon)?;
+      Ok(Box::new(()) as OperationOk)
+    };
+    self.perform_operation(Box::new(closure))?;
+    Ok(())
+  }
+  /// Sets how much punctuation is spoken by default. Requests can override it with
+  /// [`SpeechRequest::punctuation`], and `None` leaves it to each backend.
+  pub fn set_punctuation(&self, punctuation: Option<Punctuation>) -> Result<(), OutputError> {
+    let closure = move || {
+      PUNCTUATION.set(punctuation);
+      Ok(Box::new(()) as OperationOk)
+    };
+    self.perform_operation(Box::new(closure))?;
+    Ok(())
+  }
+  /// Sets how capital letters are indicated by default. Requests can override it with
+  /// [`SpeechRequest::capitals`], and `None` leaves it to each backend.
+  pub fn set_capitals(&self, capitals: Option<Capitals>) -> Result<(), OutputError> {
+    let closure = move || {
+      CAPITALS.set(capitals);
+      Ok(Box::new(()) as OperationOk)
+    };
+    self.perform_operation(Box::new(closure))?;
+    Ok(())
+  }
+  /// The language used to choose lexicon entries: the requested language, or else the language
+  /// of the voice if any entry depends on it.
+  fn text_language(
+    synthesizer: &str,
+    voice: Option<&str>,
+    language: Option<&str>,
+  ) -> Option<String> {
+    if let Some(language) = language {
+      return Some(language.to_owned());
+    }
+    if !LEXICON.with_borrow(Lexicon::has_language_entries) {
+      return None;
+    }
+    let filter = VoiceFilter::new().synthesizer(synthesizer).name(voice?);
+    Whisprs::internal_list_voices(&filter)
+      .ok()?
+      .into_iter()
+      .next()?
+      .languages
+      .into_iter()
+      .next()
+  }
+  /// Sets the strings of the game's language used for translated and key binding components of
+  /// [`TextFormat::Minecraft`] text, keyed like the language files of Minecraft.
+  pub fn set_minecraft_translations(&self, translations: HashMap<String, String>) {
+    *self
+      .minecraft_translations
+      .write()
+      .unwrap_or_else(PoisonError::into_inner) = translations;
+  }
+  /// Interprets text passed in `format`. `volume` is the volume of the whole text.
+  fn text_input(&self, text: &str, format: TextFormat, volume: Option<u8>) -> SpeechInput {
+    match format {
+      TextFormat::Plain => SpeechInput::Text(text.to_owned()),
+      TextFormat::Minecraft => {
+        let translations = self
+          .minecraft_translations
+          .read()
+          .unwrap_or_else(PoisonError::into_inner);
+        let text = MinecraftText::parse(text, &translations).to_annotated_text(volume);
+        if text.spans.is_empty() {
+          SpeechInput::Text(String::new())
+        } else {
+          SpeechInput::Annotated(text)
+        }
+      }
+    }
+  }
+  /// The text shown on a braille display for text passed in `format`.
+  fn braille_text(&self, text: &str, format: TextFormat) -> String {
+    match format {
+      TextFormat::Plain => text.to_owned(),
+      TextFormat::Minecraft => {
+        let translations = self
+          .minecraft_translations
+          .read()
+          .unwrap_or_else(PoisonError::into_inner);
+        MinecraftText::parse(text, &translations).to_plain_text()
+      }
+    }
+  }
   fn check_speech_parameters(
     rate: Option<u8>,
     volume: Option<u8>,
@@ -286,6 +917,17 @@ impl Whisprs {
     }
     Ok(())
   }
+  fn check_annotated_text(text: &AnnotatedText) -> Result<(), OutputError> {
+    if text.spans.is_empty() {
+      return Err(OutputError::into_invalid_parameter(anyhow!(
+        "Annotated text has no spans"
+      )));
+    }
+    text
+      .spans
+      .iter()
+      .try_for_each(|span| Whisprs::check_speech_parameters(span.rate, span.volume, span.pitch))
+  }
   pub fn speak_to_audio_data(
     &self,
     synthesizer: Option<&str>,
@@ -296,47 +938,167 @@ impl Whisprs {
     pitch: Option<u8>,
     text: &str,
   ) -> Result<SpeechResult, OutputError> {
-    Whisprs::check_speech_parameters(rate, volume, pitch)?;
-    let synthesizer = synthesizer.map(std::borrow::ToOwned::to_owned);
-    let voice = voice.map(std::borrow::ToOwned::to_owned);
-    let language = language.map(std::borrow::ToOwned::to_owned);
-    let text = text.to_owned();
-    let closure = move || {
-      BACKENDS.with_borrow(|backends| {
-        let synthesizer_name = Whisprs::filter_synthesizers(
-          synthesizer.as_deref(),
+    self.speak_request_to_audio_data(&Whisprs::positional_request(
+      synthesizer,
+      voice,
+      language,
+      rate,
+      volume,
+      pitch,
+      SpeechContent::Text(text.to_owned()),
+    ))
+  }
+  /// Like [`Whisprs::speak_to_audio_data`], but speaks annotated text span by span and returns
+  /// the concatenated audio.
+  pub fn speak_annotated_to_audio_data(
+    &self,
+    synthesizer: Option<&str>,
+    voice: Option<&str>,
+    language: Option<&str>,
+    rate: Option<u8>,
+    volume: Option<u8>,
+    pitch: Option<u8>,
+    text: &AnnotatedText,
+  ) -> Result<SpeechResult, OutputError> {
+    self.speak_request_to_audio_data(&Whisprs::positional_request(
+      synthesizer,
+      voice,
+      language,
+      rate,
+      volume,
+      pitch,
+      SpeechContent::Annotated(text.clone()),
+    ))
+  }
+  /// The request made of the arguments shared by the speak methods.
+  fn positional_request(
+    synthesizer: Option<&str>,
+    voice: Option<&str>,
+    language: Option<&str>,
+    rate: Option<u8>,
+    volume: Option<u8>,
+    pitch: Option<u8>,
+    content: SpeechContent,
+  ) -> SpeechRequest {
+    SpeechRequest {
+      synthesizer: synthesizer.map(std::borrow::ToOwned::to_owned),
+      voice: voice.map(std::borrow::ToOwned::to_owned),
+      language: language.map(std::borrow::ToOwned::to_owned),
+      rate,
+      volume,
+      pitch,
+      ..SpeechRequest::new(content)
+    }
+  }
+  /// Applies the profile of a request, if any, and validates it before it is sent to the worker
+  /// thread.
+  fn prepare_request(
+    &self,
+    request: &SpeechRequest,
+  ) -> Result<(SpeechRequest, SpeechInput), OutputError> {
+    #[cfg(feature = "profiles")]
+    let request = &self.profiles.apply(request)?;
+    Whisprs::check_speech_parameters(request.rate, request.volume, request.pitch)?;
+    let input = SpeechInput::from_request(self, request)?;
+    Ok((request.clone(), input))
+  }
+  /// Selects the voice for speaking a request to audio data and prepares its input with the same
+  /// lexicon, punctuation and capitals stages as speech sent to the audio output, then passes the
+  /// synthesizer name, the synthesizer, the voice and the input to `speak`.
+  fn with_audio_data_synthesizer<T, F>(
+    request: &SpeechRequest,
+    input: SpeechInput,
+    speak: F,
+  ) -> Result<T, OutputError>
+  where
+    F: FnOnce(
+      &str,
+      &dyn SpeechSynthesizerToAudioData,
+      Option<&str>,
+      SpeechInput,
+    ) -> Result<T, OutputError>,
+  {
+    BACKENDS.with_borrow(|backends| {
+      let (synthesizer_name, voice) = Whisprs::select_voice(
+        request.synthesizer.as_deref(),
+        request.voice.as_deref(),
+        request.variant.as_deref(),
+        request.language.as_deref(),
+        true,
+      )?;
+      let text_language = Whisprs::text_language(
+        &synthesizer_name,
+        voice.as_deref(),
+        request.language.as_deref(),
+      );
+      let backend = backends
+        .get(&synthesizer_name)
+        .ok_or(OutputError::into_backend_not_found(&synthesizer_name))?;
+      let synthesizer = backend.as_speech_synthesizer_to_audio_data().ok_or(
+        OutputError::into_audio_data_not_supported(&synthesizer_name),
+      )?;
+      let (input, capital_icons) = input
+        .apply_lexicon(&synthesizer_name, text_language.as_deref())
+        .apply_punctuation(backend.as_ref(), request.punctuation.as_ref())?
+        .apply_capitals(backend.as_ref(), request.capitals, request.pitch, true)?;
+      if capital_icons {
+        speak(
+          &synthesizer_name,
+          &CapitalIcons { synthesizer },
           voice.as_deref(),
-          language.as_deref(),
-          true,
-        )?;
-        let synthesizer = backends
-          .get(&synthesizer_name)
-          .ok_or(OutputError::into_backend_not_found(&synthesizer_name))?;
-        let result = match synthesizer.as_speech_synthesizer_to_audio_data() {
-          None => Err(OutputError::into_audio_data_not_supported(
-            &synthesizer_name,
-          ))?,
-          Some(synthesizer) => synthesizer.speak(
-            voice.as_deref(),
-            language.as_deref(),
-            rate,
-            volume,
-            pitch,
-            &text,
-          )?,
-        };
-        Ok(Box::new(result) as OperationOk)
-      })
+          input,
+        )
+      } else {
+        speak(&synthesizer_name, synthesizer, voice.as_deref(), input)
+      }
+    })
+  }
+  fn speak_to_audio_data_operation(
+    &self,
+    request: &SpeechRequest,
+  ) -> Result<Operation, OutputError> {
+    let (request, input) = self.prepare_request(request)?;
+    let closure = move || {
+      let language = request.language.as_deref();
+      let (rate, volume, pitch) = (request.rate, request.volume, request.pitch);
+      let result = Whisprs::with_audio_data_synthesizer(
+        &request,
+        input,
+        |synthesizer_name, synthesizer, voice, input| match &input {
+          SpeechInput::Text(text) => synthesizer.speak(voice, language, rate, volume, pitch, text),
+          input => {
+            let mut result: Option<SpeechResult> = None;
+            input.speak_streaming(
+              synthesizer,
+              voice,
+              language,
+              rate,
+              volume,
+              pitch,
+              &mut |chunk| {
+                match &mut result {
+                  Some(result) => result.append(chunk),
+                  None => result = Some(chunk),
+                }
+                true
+              },
+            )?;
+            result.ok_or(OutputError::into_speak_failed(
+              synthesizer_name,
+              voice.unwrap_or(language.unwrap_or("default")),
+              anyhow!("No audio was synthesized"),
+            ))
+          }
+        },
+      )?;
+      Ok(Box::new(result) as OperationOk)
     };
-    let result = self
-      .perform_operation(Box::new(closure))?
-      .downcast()
-      .map_err(|_| {
-        OutputError::into_unknown(anyhow!("Failed to downcast received return value"))
-      })?;
-    Ok(*result)
+    Ok(Box::new(closure))
   }
-  pub fn speak_to_audio_output(
+  /// Like [`Whisprs::speak_to_audio_data`], but returns immediately with a stream that yields
+  /// audio while it is being synthesized. Errors that happen during synthesis, including an
+  /// unknown voice or language, are yielded by the stream.
+  pub fn speak_to_audio_stream(
     &self,
     synthesizer: Option<&str>,
     voice: Option<&str>,
@@ -345,52 +1107,485 @@ impl Whisprs {
     volume: Option<u8>,
     pitch: Option<u8>,
     text: &str,
-    interrupt: bool,
-  ) -> Result<(), OutputError> {
-    Whisprs::check_speech_parameters(rate, volume, pitch)?;
-    let synthesizer = synthesizer.map(std::borrow::ToOwned::to_owned);
-    let voice = voice.map(std::borrow::ToOwned::to_owned);
-    let language = language.map(std::borrow::ToOwned::to_owned);
-    let text = text.to_owned();
-    let closure = move || {
-      BACKENDS.with_borrow(|backends| {
-        let synthesizer_name = Whisprs::filter_synthesizers(
-          synthesizer.as_deref(),
-          voice.as_deref(),
-          language.as_deref(),
-          false,
+  ) -> Result<SpeechStream, OutputError> {
+    self.speak_request_to_audio_stream(&Whisprs::positional_request(
+      synthesizer,
+      voice,
+      language,
+      rate,
+      volume,
+      pitch,
+      SpeechContent::Text(text.to_owned()),
+    ))
+  }
+  /// Speaks a [`SpeechRequest`] to a stream of audio data, like
+  /// [`Whisprs::speak_to_audio_stream`], ignoring its interrupt flag and priority.
+  pub fn speak_request_to_audio_stream(
+    &self,
+    request: &SpeechRequest,
+  ) -> Result<SpeechStream, OutputError> {
+    let (request, input) = self.prepare_request(request)?;
+    let (sender, receiver) = mpsc::channel();
+    let closure = move || {
+      let result =
+        Whisprs::with_audio_data_synthesizer(&request, input, |_, synthesizer, voice, input| {
+          input.speak_streaming(
+            synthesizer,
+            voice,
+            request.language.as_deref(),
+            request.rate,
+            request.volume,
+            request.pitch,
+            &mut |chunk| sender.send(Ok(chunk)).is_ok(),
+          )
+        });
+      if let Err(error) = result {
+        let _result = sender.send(Err(error));
+      }
+      Ok(Box::new(()) as OperationOk)
+    };
+    self.send_operation(Box::new(closure))?;
+    Ok(SpeechStream { receiver })
+  }
+  pub fn speak_to_audio_output(
+    &self,
+    synthesizer: Option<&str>,
+    voice: Option<&str>,
+    language: Option<&str>,
+    rate: Option<u8>,
+    volume: Option<u8>,
+    pitch: Option<u8>,
+    text: &str,
+    interrupt: bool,
+  ) -> Result<Utterance, OutputError> {
+    let mut request = Whisprs::positional_request(
+      synthesizer,
+      voice,
+      language,
+      rate,
+      volume,
+      pitch,
+      SpeechContent::Text(text.to_owned()),
+    );
+    request.interrupt = interrupt;
+    self.speak_request_to_audio_output(&request)
+  }
+  /// Like [`Whisprs::speak_to_audio_output`], but returns as soon as the speech parameters are
+  /// validated instead of waiting for the worker thread. Errors that happen later cancel the
+  /// returned utterance.
+  pub fn speak_to_audio_output_nonblocking(
+    &self,
+    synthesizer: Option<&str>,
+    voice: Option<&str>,
+    language: Option<&str>,
+    rate: Option<u8>,
+    volume: Option<u8>,
+    pitch: Option<u8>,
+    text: &str,
+    interrupt: bool,
+  ) -> Result<Utterance, OutputError> {
+    let mut request = Whisprs::positional_request(
+      synthesizer,
+      voice,
+      language,
+      rate,
+      volume,
+      pitch,
+      SpeechContent::Text(text.to_owned()),
+    );
+    request.interrupt = interrupt;
+    self.speak_request_to_audio_output_nonblocking(&request)
+  }
+  /// Sends an operation without waiting for it, and reports its error as an event of `utterance`.
+  fn send_reporting_errors(
+    &self,
+    operation: Operation,
+    utterance: &Utterance,
+  ) -> Result<(), OutputError> {
+    let utterance = utterance.clone();
+    let closure = move || {
+      let result = operation();
+      if let Err(error) = &result {
+        utterance.emit(SpeechEventKind::Error(error.to_string()));
+      }
+      result
+    };
+    self.send_operation(Box::new(closure))?;
+    Ok(())
+  }
+  /// Like [`Whisprs::speak_to_audio_output`], but speaks an SSML document, which is validated
+  /// before it is sent to the worker thread. See [`Ssml`] for the supported elements.
+  pub fn speak_ssml_to_audio_output(
+    &self,
+    synthesizer: Option<&str>,
+    voice: Option<&str>,
+    language: Option<&str>,
+    rate: Option<u8>,
+    volume: Option<u8>,
+    pitch: Option<u8>,
+    ssml: &str,
+    interrupt: bool,
+  ) -> Result<Utterance, OutputError> {
+    let mut request = Whisprs::positional_request(
+      synthesizer,
+      voice,
+      language,
+      rate,
+      volume,
+      pitch,
+      SpeechContent::Ssml(ssml.to_owned()),
+    );
+    request.interrupt = interrupt;
+    self.speak_request_to_audio_output(&request)
+  }
+  /// Like [`Whisprs::speak_to_audio_output`], but speaks annotated text. Synthesizers that return
+  /// audio data speak it span by span, and the others receive the equivalent SSML.
+  pub fn speak_annotated_to_audio_output(
+    &self,
+    synthesizer: Option<&str>,
+    voice: Option<&str>,
+    language: Option<&str>,
+    rate: Option<u8>,
+    volume: Option<u8>,
+    pitch: Option<u8>,
+    text: &AnnotatedText,
+    interrupt: bool,
+  ) -> Result<Utterance, OutputError> {
+    let mut request = Whisprs::positional_request(
+      synthesizer,
+      voice,
+      language,
+      rate,
+      volume,
+      pitch,
+      SpeechContent::Annotated(text.clone()),
+    );
+    request.interrupt = interrupt;
+    self.speak_request_to_audio_output(&request)
+  }
+  /// Like [`Whisprs::speak_to_audio_output`], but spells the text one character at a time, with
+  /// the character mode of the synthesizer if it has one. Letters are spoken with the names of
+  /// the NATO phonetic alphabet if `phonetic` is set.
+  pub fn spell(
+    &self,
+    synthesizer: Option<&str>,
+    voice: Option<&str>,
+    language: Option<&str>,
+    rate: Option<u8>,
+    volume: Option<u8>,
+    pitch: Option<u8>,
+    text: &str,
+    phonetic: bool,
+    interrupt: bool,
+  ) -> Result<Utterance, OutputError> {
+    let mut request = Whisprs::positional_request(
+      synthesizer,
+      voice,
+      language,
+      rate,
+      volume,
+      pitch,
+      SpeechContent::Spelled {
+        text: text.to_owned(),
+        phonetic,
+      },
+    );
+    request.interrupt = interrupt;
+    self.speak_request_to_audio_output(&request)
+  }
+  /// Speaks a single character, like [`Whisprs::spell`].
+  pub fn speak_character(
+    &self,
+    synthesizer: Option<&str>,
+    voice: Option<&str>,
+    language: Option<&str>,
+    rate: Option<u8>,
+    volume: Option<u8>,
+    pitch: Option<u8>,
+    character: char,
+    phonetic: bool,
+    interrupt: bool,
+  ) -> Result<Utterance, OutputError> {
+    self.spell(
+      synthesizer,
+      voice,
+      language,
+      rate,
+      volume,
+      pitch,
+      &character.to_string(),
+      phonetic,
+      interrupt,
+    )
+  }
+  /// Sets the names of keys spoken by [`Whisprs::speak_key`] for a BCP 47 language, keyed by
+  /// their SSIP names such as `control` or `prior`. Keys without a name here use the built-in
+  /// names for German, Spanish, French, Italian and Portuguese, or are spoken in English.
+  pub fn set_key_names(&self, language: &str, names: HashMap<String, String>) {
+    self
+      .key_names
+      .write()
+      .unwrap_or_else(PoisonError::into_inner)
+      .insert(language.to_owned(), names);
+  }
+  /// The name of a key in `language`, from the names set with [`Whisprs::set_key_names`] for
+  /// the closest match of `language` or the built-in names.
+  fn key_name(&self, key: &KeyCombination, language: Option<&str>) -> String {
+    let key_names = self
+      .key_names
+      .read()
+      .unwrap_or_else(PoisonError::into_inner);
+    let names = language.and_then(|language| {
+      key_names
+        .iter()
+        .filter_map(|(tag, names)| {
+          language_distance(language, tag).map(|distance| (distance, names))
+        })
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, names)| names)
+    });
+    key.render(names.unwrap_or(&HashMap::new()), language)
+  }
+  /// Like [`Whisprs::speak_to_audio_output`], but speaks a key. Speech Dispatcher names single
+  /// keys itself; otherwise, the names set with [`Whisprs::set_key_names`] for the closest match
+  /// of `language` are used.
+  pub fn speak_key(
+    &self,
+    synthesizer: Option<&str>,
+    voice: Option<&str>,
+    language: Option<&str>,
+    rate: Option<u8>,
+    volume: Option<u8>,
+    pitch: Option<u8>,
+    key: &KeyCombination,
+    interrupt: bool,
+  ) -> Result<Utterance, OutputError> {
+    let mut request = Whisprs::positional_request(
+      synthesizer,
+      voice,
+      language,
+      rate,
+      volume,
+      pitch,
+      SpeechContent::Key(key.clone()),
+    );
+    request.interrupt = interrupt;
+    self.speak_request_to_audio_output(&request)
+  }
+  /// Speaks a [`SpeechRequest`] through the audio output, like the other
+  /// `speak_*_to_audio_output` methods.
+  pub fn speak_request_to_audio_output(
+    &self,
+    request: &SpeechRequest,
+  ) -> Result<Utterance, OutputError> {
+    let (operation, utterance) = self.speak_operation(request)?;
+    self.perform_operation(operation)?;
+    Ok(utterance)
+  }
+  /// Like [`Whisprs::speak_request_to_audio_output`], but does not wait for the worker thread,
+  /// like [`Whisprs::speak_to_audio_output_nonblocking`].
+  pub fn speak_request_to_audio_output_nonblocking(
+    &self,
+    request: &SpeechRequest,
+  ) -> Result<Utterance, OutputError> {
+    let (operation, utterance) = self.speak_operation(request)?;
+    self.send_reporting_errors(operation, &utterance)?;
+    Ok(utterance)
+  }
+  /// Speaks a [`SpeechRequest`] to audio data, ignoring its interrupt flag and priority.
+  pub fn speak_request_to_audio_data(
+    &self,
+    request: &SpeechRequest,
+  ) -> Result<SpeechResult, OutputError> {
+    let operation = self.speak_to_audio_data_operation(request)?;
+    let result = self.perform_operation(operation)?.downcast().map_err(|_| {
+      OutputError::into_unknown(anyhow!("Failed to downcast received return value"))
+    })?;
+    Ok(*result)
+  }
+  #[allow(clippy::too_many_lines)]
+  fn speak_operation(
+    &self,
+    request: &SpeechRequest,
+  ) -> Result<(Operation, Utterance), OutputError> {
+    let (request, input) = self.prepare_request(request)?;
+    let SpeechRequest {
+      synthesizer,
+      voice,
+      variant,
+      language,
+      rate,
+      volume,
+      pitch,
+      punctuation,
+      capitals,
+      interrupt,
+      priority,
+      ..
+    } = request;
+    let player = self.player.clone();
+    let utterance = Utterance::pending(
+      self.next_utterance_id.fetch_add(1, Ordering::Relaxed),
+      &self.events,
+    );
+    let stops = self.stops.clone();
+    let generation = stops.current();
+    let operation_utterance = utterance.clone();
+    let closure = move || {
+      let utterance = operation_utterance;
+      let result = BACKENDS.with_borrow(|backends| {
+        let (synthesizer_name, voice) = Whisprs::select_voice(
+          synthesizer.as_deref(),
+          voice.as_deref(),
+          variant.as_deref(),
+          language.as_deref(),
+          false,
         )?;
         let synthesizer = backends
           .get(&synthesizer_name)
           .ok_or(OutputError::into_backend_not_found(&synthesizer_name))?;
+        utterance.set_synthesizer(&synthesizer_name);
+        let is_stopped = || {
+          utterance.state() == UtteranceState::Cancelled
+            || stops.is_stopped(&synthesizer_name, generation)
+        };
+        if is_stopped() {
+          utterance.set_state(UtteranceState::Cancelled);
+          return Ok(Box::new(()) as OperationOk);
+        }
+        #[cfg(feature = "language-detection")]
+        let input = match input {
+          SpeechInput::Text(text)
+            if LANGUAGE_DETECTION.get() && voice.is_none() && language.is_none() =>
+          {
+            let voices =
+              Whisprs::internal_list_voices(&VoiceFilter::new().synthesizer(&synthesizer_name))?;
+            let (text, detected) = language_detection::annotate_languages(&text, &voices);
+            for detected in detected {
+              utterance.emit(SpeechEventKind::LanguageDetected {
+                offset: detected.offset,
+                length: detected.length,
+                language: detected.language,
+              });
+            }
+            SpeechInput::Annotated(text)
+          }
+          input => input,
+        };
+        let text_language =
+          Whisprs::text_language(&synthesizer_name, voice.as_deref(), language.as_deref());
+        let audio_data = synthesizer
+          .as_speech_synthesizer_to_audio_output()
+          .is_none();
+        let (input, capital_icons) = input
+          .apply_lexicon(&synthesizer_name, text_language.as_deref())
+          .apply_punctuation(synthesizer.as_ref(), punctuation.as_ref())?
+          .apply_capitals(synthesizer.as_ref(), capitals, pitch, audio_data)?;
+        let icons;
         match (
           synthesizer.as_speech_synthesizer_to_audio_data(),
           synthesizer.as_speech_synthesizer_to_audio_output(),
         ) {
           (None, None) => Err(OutputError::into_speech_not_supported(&synthesizer_name))?,
           (Some(synthesizer), None) => {
-            let result = synthesizer.speak(
+            let synthesizer: &dyn SpeechSynthesizerToAudioData = if capital_icons {
+              icons = CapitalIcons { synthesizer };
+              &icons
+            } else {
+              synthesizer
+            };
+            if interrupt {
+              player.stop();
+            }
+            let mut started = false;
+            let mut playback_result = Ok(());
+            let mut on_chunk = |chunk: SpeechResult| {
+              if is_stopped() {
+                return false;
+              }
+              if started {
+                return player.append(&utterance, &chunk);
+              }
+              started = true;
+              playback_result = play_audio(&player, &utterance, &chunk, priority);
+              playback_result.is_ok()
+            };
+            let result = input.speak_streaming(
+              synthesizer,
               voice.as_deref(),
               language.as_deref(),
               rate,
               volume,
               pitch,
-              &text,
-            )?;
-            if interrupt {
-              stop_audio()?;
+              &mut on_chunk,
+            );
+            player.finish(&utterance);
+            if !started {
+              utterance.set_state(if is_stopped() {
+                UtteranceState::Cancelled
+              } else {
+                UtteranceState::Finished
+              });
             }
-            play_audio(&result)?;
+            result?;
+            playback_result?;
+          }
+          (_, Some(synthesizer)) => {
+            let interrupt = interrupt
+              || (!synthesizer.supports_priorities()
+                && Whisprs::emulated_interrupt(&synthesizer_name, priority));
+            input.speak(
+              synthesizer,
+              voice.as_deref(),
+              language.as_deref(),
+              rate,
+              volume,
+              pitch,
+              interrupt,
+              priority,
+              &utterance,
+            )?;
           }
-          (_, Some(synthesizer)) => synthesizer.speak(
-            voice.as_deref(),
-            language.as_deref(),
-            rate,
-            volume,
-            pitch,
-            &text,
-            interrupt,
-          )?,
+        }
+        Ok(Box::new(()) as OperationOk)
+      });
+      if result.is_err() {
+        utterance.set_state(UtteranceState::Cancelled);
+      }
+      result
+    };
+    Ok((Box::new(closure), utterance))
+  }
+  /// Decides whether a message must interrupt the speech of a backend that does not queue by
+  /// priority, based on the priority of the last message sent to it.
+  fn emulated_interrupt(synthesizer: &str, priority: Priority) -> bool {
+    LAST_MESSAGE_PRIORITIES.with_borrow_mut(|priorities| {
+      priorities
+        .insert(synthesizer.to_owned(), priority)
+        .is_some_and(|last| priority.preempts(last) != Preemption::Keep)
+    })
+  }
+  /// Cancels one utterance, whether it is still queued or already being spoken. Cancelling an
+  /// utterance that is already done has no effect.
+  pub fn cancel_utterance(&self, utterance: &Utterance) -> Result<(), OutputError> {
+    if utterance.state().is_done() || self.player.cancel(utterance) {
+      return Ok(());
+    }
+    // Cancelling the utterance right away aborts its synthesis if it is in progress, and skips it
+    // if it is still waiting for the worker thread.
+    utterance.set_state(UtteranceState::Cancelled);
+    let utterance = utterance.clone();
+    let player = self.player.clone();
+    let closure = move || {
+      BACKENDS.with_borrow(|backends| {
+        let synthesizer_name = utterance.synthesizer();
+        let Some(synthesizer) = backends.get(synthesizer_name) else {
+          return Ok(Box::new(()) as OperationOk);
+        };
+        if let Some(synthesizer) = synthesizer.as_speech_synthesizer_to_audio_output() {
+          synthesizer.cancel(&utterance)?;
+        } else {
+          player.cancel(&utterance);
         }
         Ok(Box::new(()) as OperationOk)
       })
@@ -398,8 +1593,20 @@ impl Whisprs {
     self.perform_operation(Box::new(closure))?;
     Ok(())
   }
+  /// Stops speech, either of one synthesizer or of all of them. Synthesis in progress is aborted,
+  /// and speech requested before this call but not started yet is cancelled. Stopping all speech
+  /// also cuts off audio that is playing without waiting for the worker thread.
   pub fn stop_speech(&self, synthesizer: Option<&str>) -> Result<(), OutputError> {
+    self.perform_operation(self.stop_speech_operation(synthesizer))?;
+    Ok(())
+  }
+  fn stop_speech_operation(&self, synthesizer: Option<&str>) -> Operation {
+    self.stops.stop(synthesizer);
+    if synthesizer.is_none() {
+      self.player.stop();
+    }
     let synthesizer = synthesizer.map(std::borrow::ToOwned::to_owned);
+    let player = self.player.clone();
     let closure = move || {
       BACKENDS.with_borrow(|backends| {
         if let Some(synthesizer_name) = synthesizer {
@@ -411,11 +1618,11 @@ impl Whisprs {
             synthesizer.as_speech_synthesizer_to_audio_output(),
           ) {
             (None, None) => Err(OutputError::into_speech_not_supported(&synthesizer_name))?,
-            (Some(_), None) => stop_audio()?,
+            (Some(_), None) => player.stop(),
             (_, Some(synthesizer)) => synthesizer.stop_speech()?,
           }
         } else {
-          stop_audio()?;
+          player.stop();
           for synthesizer in backends
             .iter()
             .filter_map(|backend| backend.1.as_speech_synthesizer_to_audio_output())
@@ -426,10 +1633,161 @@ impl Whisprs {
         Ok(Box::new(()) as OperationOk)
       })
     };
+    Box::new(closure)
+  }
+  fn sink_is_speaking(include_paused: bool) -> bool {
+    SINK.with(|cell| {
+      cell
+        .get()
+        .is_some_and(|sink| !sink.empty() && (include_paused || !sink.is_paused()))
+    })
+  }
+  /// Whether one synthesizer, or any synthesizer if none is given, is still speaking. Paused
+  /// speech is not considered to be speaking. Screen readers cannot report this and are never
+  /// considered to be speaking.
+  pub fn is_speaking(&self, synthesizer: Option<&str>) -> Result<bool, OutputError> {
+    self.has_speech(synthesizer, false)
+  }
+  /// Whether speech is still going on, counting paused speech if `include_paused` is set.
+  fn has_speech(
+    &self,
+    synthesizer: Option<&str>,
+    include_paused: bool,
+  ) -> Result<bool, OutputError> {
+    let synthesizer = synthesizer.map(std::borrow::ToOwned::to_owned);
+    let output_is_speaking = move |synthesizer: &dyn SpeechSynthesizerToAudioOutput| {
+      synthesizer.poll()?;
+      Ok::<_, OutputError>(
+        synthesizer.is_speaking()? || (include_paused && synthesizer.is_paused()?),
+      )
+    };
+    let closure = move || {
+      BACKENDS.with_borrow(|backends| {
+        let is_speaking = if let Some(synthesizer_name) = synthesizer {
+          let synthesizer = backends
+            .get(&synthesizer_name)
+            .ok_or(OutputError::into_backend_not_found(&synthesizer_name))?;
+          match (
+            synthesizer.as_speech_synthesizer_to_audio_data(),
+            synthesizer.as_speech_synthesizer_to_audio_output(),
+          ) {
+            (None, None) => Err(OutputError::into_speech_not_supported(&synthesizer_name))?,
+            (Some(_), None) => Whisprs::sink_is_speaking(include_paused),
+            (_, Some(synthesizer)) => output_is_speaking(synthesizer)?,
+          }
+        } else {
+          let mut is_speaking = Whisprs::sink_is_speaking(include_paused);
+          for synthesizer in backends
+            .values()
+            .filter_map(|backend| backend.as_speech_synthesizer_to_audio_output())
+          {
+            is_speaking |= output_is_speaking(synthesizer).unwrap_or(false);
+          }
+          is_speaking
+        };
+        Ok(Box::new(is_speaking) as OperationOk)
+      })
+    };
+    let result = self
+      .perform_operation(Box::new(closure))?
+      .downcast()
+      .map_err(|_| {
+        OutputError::into_unknown(anyhow!("Failed to downcast received return value"))
+      })?;
+    Ok(*result)
+  }
+  /// Blocks until speech is done or `timeout` has passed, and returns whether speech finished in
+  /// time. Paused speech is not done, so this keeps waiting until it is resumed and finishes;
+  /// without a timeout, it blocks until another thread calls [`Whisprs::resume_speech`].
+  pub fn wait_until_done(
+    &self,
+    synthesizer: Option<&str>,
+    timeout: Option<Duration>,
+  ) -> Result<bool, OutputError> {
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    while self.has_speech(synthesizer, true)? {
+      if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+        return Ok(false);
+      }
+      thread::sleep(POLL_INTERVAL);
+    }
+    Ok(true)
+  }
+  /// Pauses speech, either of one synthesizer or of all synthesizers that support pausing.
+  /// Speech queued while paused is held back until [`Whisprs::resume_speech`] is called.
+  pub fn pause_speech(&self, synthesizer: Option<&str>) -> Result<(), OutputError> {
+    self.set_paused(synthesizer, true)
+  }
+  pub fn resume_speech(&self, synthesizer: Option<&str>) -> Result<(), OutputError> {
+    self.set_paused(synthesizer, false)
+  }
+  fn set_paused(&self, synthesizer: Option<&str>, paused: bool) -> Result<(), OutputError> {
+    let synthesizer = synthesizer.map(std::borrow::ToOwned::to_owned);
+    let set_output_paused = move |synthesizer: &dyn SpeechSynthesizerToAudioOutput| {
+      if paused {
+        synthesizer.pause()
+      } else {
+        synthesizer.resume()
+      }
+    };
+    let set_sink_paused = move || {
+      SINK.with(|cell| {
+        if let Some(sink) = cell.get() {
+          if paused {
+            sink.pause();
+          } else {
+            sink.play();
+          }
+        }
+      });
+    };
+    let closure = move || {
+      BACKENDS.with_borrow(|backends| {
+        if let Some(synthesizer_name) = synthesizer {
+          let synthesizer = backends
+            .get(&synthesizer_name)
+            .ok_or(OutputError::into_backend_not_found(&synthesizer_name))?;
+          match (
+            synthesizer.as_speech_synthesizer_to_audio_data(),
+            synthesizer.as_speech_synthesizer_to_audio_output(),
+          ) {
+            (None, None) => Err(OutputError::into_speech_not_supported(&synthesizer_name))?,
+            (Some(_), None) => set_sink_paused(),
+            (_, Some(synthesizer)) if synthesizer.supports_pausing() => {
+              set_output_paused(synthesizer)?;
+            }
+            (_, Some(_)) => Err(OutputError::into_pausing_not_supported(&synthesizer_name))?,
+          }
+        } else {
+          set_sink_paused();
+          for synthesizer in backends
+            .values()
+            .filter_map(|backend| backend.as_speech_synthesizer_to_audio_output())
+            .filter(|synthesizer| synthesizer.supports_pausing())
+          {
+            let _result = set_output_paused(synthesizer);
+          }
+        }
+        Ok(Box::new(()) as OperationOk)
+      })
+    };
     self.perform_operation(Box::new(closure))?;
     Ok(())
   }
   pub fn braille(&self, backend: Option<&str>, text: &str) -> Result<(), OutputError> {
+    self.perform_operation(Whisprs::braille_operation(backend, text))?;
+    Ok(())
+  }
+  /// Like [`Whisprs::braille`], but shows text passed in `format`.
+  pub fn braille_with_format(
+    &self,
+    backend: Option<&str>,
+    text: &str,
+    format: TextFormat,
+  ) -> Result<(), OutputError> {
+    self.braille(backend, &self.braille_text(text, format))
+  }
+  fn braille_operation(backend: Option<&str>, text: &str) -> Operation {
     let backend = backend.map(std::borrow::ToOwned::to_owned);
     let text = text.to_owned();
     let closure = move || {
@@ -444,19 +1802,27 @@ impl Whisprs {
         } else {
           let mut braille_backends = backends
             .iter()
-            .filter_map(|backend| backend.1.as_braille_backend())
-            .collect::<Vec<&dyn BrailleBackend>>();
-          braille_backends.sort_unstable_by_key(|backend| backend.priority());
+            .filter_map(|backend| {
+              backend.1.as_braille_backend().map(|braille_backend| {
+                let priority = Whisprs::backend_priority(backend.0);
+                (
+                  priority.unwrap_or(braille_backend.priority()),
+                  braille_backend,
+                )
+              })
+            })
+            .collect::<Vec<(u8, &dyn BrailleBackend)>>();
+          braille_backends.sort_unstable_by_key(|backend| backend.0);
           braille_backends
             .first()
             .ok_or(OutputError::NoBrailleBackends)?
+            .1
             .braille(&text)?;
         }
         Ok(Box::new(()) as OperationOk)
       })
     };
-    self.perform_operation(Box::new(closure))?;
-    Ok(())
+    Box::new(closure)
   }
   pub fn output(
     &self,
@@ -469,26 +1835,95 @@ impl Whisprs {
     braille_backend: Option<&str>,
     text: &str,
     interrupt: bool,
-  ) -> Result<(), OutputError> {
-    let speech_result = self.speak_to_audio_output(
+  ) -> Result<Option<Utterance>, OutputError> {
+    let mut request = Whisprs::positional_request(
       synthesizer,
       voice,
       language,
       rate,
       volume,
       pitch,
-      text,
-      interrupt,
+      SpeechContent::Text(text.to_owned()),
     );
-    let braille_result = self.braille(braille_backend, text);
+    request.interrupt = interrupt;
+    self.output_request(&request, braille_backend)
+  }
+  /// Like [`Whisprs::output`], but speaks a [`SpeechRequest`] and shows its text.
+  pub fn output_request(
+    &self,
+    request: &SpeechRequest,
+    braille_backend: Option<&str>,
+  ) -> Result<Option<Utterance>, OutputError> {
+    let speech_result = self.speak_request_to_audio_output(request);
+    let braille_result = self
+      .braille_request_text(request)
+      .and_then(|text| self.braille(braille_backend, &text));
     match (speech_result, braille_result) {
       (Err(OutputError::NoVoices), Err(OutputError::NoBrailleBackends)) => {
         Err(OutputError::NoBackends)
       }
-      (Err(OutputError::NoVoices) | Ok(()), right) => right,
-      (left, _) => left,
+      (Err(OutputError::NoVoices), right) => right.map(|()| None),
+      (Ok(utterance), right) => right.map(|()| Some(utterance)),
+      (Err(error), _) => Err(error),
     }
   }
+  /// The text shown on a braille display for a request.
+  fn braille_request_text(&self, request: &SpeechRequest) -> Result<String, OutputError> {
+    Ok(match &request.content {
+      SpeechContent::Text(text) => self.braille_text(text, request.format),
+      SpeechContent::Spelled { text, .. } => text.clone(),
+      SpeechContent::Ssml(ssml) => Ssml::parse(ssml)?.to_plain_text(),
+      SpeechContent::Annotated(text) => text.to_plain_text(),
+      SpeechContent::Key(key) => self.key_name(key, request.language.as_deref()),
+    })
+  }
+  /// Like [`Whisprs::output`], but returns without waiting for the worker thread. Errors that
+  /// happen after the speech parameters are validated are sent as [`SpeechEventKind::Error`]
+  /// events of the returned utterance, which is cancelled if no voice could speak the text.
+  pub fn output_nonblocking(
+    &self,
+    synthesizer: Option<&str>,
+    voice: Option<&str>,
+    language: Option<&str>,
+    rate: Option<u8>,
+    volume: Option<u8>,
+    pitch: Option<u8>,
+    braille_backend: Option<&str>,
+    text: &str,
+    interrupt: bool,
+  ) -> Result<Utterance, OutputError> {
+    let mut request = Whisprs::positional_request(
+      synthesizer,
+      voice,
+      language,
+      rate,
+      volume,
+      pitch,
+      SpeechContent::Text(text.to_owned()),
+    );
+    request.interrupt = interrupt;
+    self.output_request_nonblocking(&request, braille_backend)
+  }
+  /// Like [`Whisprs::output_request`], but does not wait for the worker thread, like
+  /// [`Whisprs::output_nonblocking`].
+  pub fn output_request_nonblocking(
+    &self,
+    request: &SpeechRequest,
+    braille_backend: Option<&str>,
+  ) -> Result<Utterance, OutputError> {
+    let (speech_operation, utterance) = self.speak_operation(request)?;
+    let braille_operation =
+      Whisprs::braille_operation(braille_backend, &self.braille_request_text(request)?);
+    let closure = move || match (speech_operation(), braille_operation()) {
+      (Err(OutputError::NoVoices), Err(OutputError::NoBrailleBackends)) => {
+        Err(OutputError::NoBackends)
+      }
+      (Err(OutputError::NoVoices) | Ok(_), right) => right,
+      (Err(error), _) => Err(error),
+    };
+    self.send_reporting_errors(Box::new(closure), &utterance)?;
+    Ok(utterance)
+  }
 }
 impl Drop for Whisprs {
   fn drop(&mut self) {
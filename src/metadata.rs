@@ -1,19 +1,309 @@
-#[derive(Debug)]
+use crate::language::best_language_distance;
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SpeechSynthesizerMetadata {
   pub name: String,
   pub supports_speaking_to_audio_data: bool,
   pub supports_speech_parameters: bool,
+  pub supports_pausing: bool,
 }
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BrailleBackendMetadata {
   pub name: String,
   pub priority: u8,
 }
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Gender {
+  Male,
+  Female,
+  Neutral,
+}
+/// The age group a voice sounds like, using the groups of SAPI voice attributes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AgeGroup {
+  Child,
+  Teen,
+  Adult,
+  Senior,
+}
+impl AgeGroup {
+  /// The age group of a voice whose age is given in years, as eSpeak NG does.
+  pub(crate) fn from_years(years: u8) -> Self {
+    match years {
+      0..=12 => AgeGroup::Child,
+      13..=19 => AgeGroup::Teen,
+      20..=59 => AgeGroup::Adult,
+      _ => AgeGroup::Senior,
+    }
+  }
+}
+/// How a voice is synthesized. `Default`, `Enhanced` and `Premium` follow the quality levels of
+/// `AVSpeechSynthesizer`, and `Formant` is used for formant synthesizers such as eSpeak NG.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum VoiceQuality {
+  Formant,
+  Default,
+  Enhanced,
+  Premium,
+}
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Voice {
   pub synthesizer: SpeechSynthesizerMetadata,
+  /// An identifier that is unique across synthesizers and stays the same between runs, so it
+  /// can be saved in settings.
+  pub id: String,
   pub display_name: String,
   pub name: String,
   pub languages: Vec<String>,
+  pub gender: Option<Gender>,
+  pub age: Option<AgeGroup>,
+  pub variant: Option<String>,
+  pub quality: Option<VoiceQuality>,
+  pub supports_audio_data: bool,
+  /// The sample rate the voice synthesizes at, if it is known before speaking.
+  pub sample_rate: Option<u32>,
   pub priority: u8,
 }
+/// A modification that can be applied to any voice of a synthesizer, such as the eSpeak NG
+/// variants that change its pitch range and timbre.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VoiceVariant {
+  pub synthesizer: SpeechSynthesizerMetadata,
+  pub id: String,
+  pub display_name: String,
+  pub name: String,
+  pub gender: Option<Gender>,
+  pub age: Option<AgeGroup>,
+}
+/// Criteria for [`crate::Whisprs::list_voices`]. Criteria that are not set match every voice, and
+/// voices that do not report a property do not match a criterion on it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VoiceFilter {
+  pub synthesizer: Option<String>,
+  pub name: Option<String>,
+  pub id: Option<String>,
+  /// A BCP 47 language tag. Voices are also matched if they speak a compatible language, and are
+  /// sorted by how closely it matches before their priority. Voices that report no languages
+  /// never match.
+  pub language: Option<String>,
+  pub gender: Option<Gender>,
+  pub age: Option<AgeGroup>,
+  pub variant: Option<String>,
+  pub quality: Option<VoiceQuality>,
+  pub needs_audio_data: bool,
+}
+impl VoiceFilter {
+  #[must_use]
+  pub fn new() -> Self {
+    VoiceFilter::default()
+  }
+  #[must_use]
+  pub fn synthesizer(mut self, synthesizer: &str) -> Self {
+    self.synthesizer = Some(synthesizer.to_owned());
+    self
+  }
+  #[must_use]
+  pub fn name(mut self, name: &str) -> Self {
+    self.name = Some(name.to_owned());
+    self
+  }
+  #[must_use]
+  pub fn id(mut self, id: &str) -> Self {
+    self.id = Some(id.to_owned());
+    self
+  }
+  #[must_use]
+  pub fn language(mut self, language: &str) -> Self {
+    self.language = Some(language.to_owned());
+    self
+  }
+  #[must_use]
+  pub fn gender(mut self, gender: Gender) -> Self {
+    self.gender = Some(gender);
+    self
+  }
+  #[must_use]
+  pub fn age(mut self, age: AgeGroup) -> Self {
+    self.age = Some(age);
+    self
+  }
+  #[must_use]
+  pub fn variant(mut self, variant: &str) -> Self {
+    self.variant = Some(variant.to_owned());
+    self
+  }
+  #[must_use]
+  pub fn quality(mut self, quality: VoiceQuality) -> Self {
+    self.quality = Some(quality);
+    self
+  }
+  #[must_use]
+  pub fn needs_audio_data(mut self) -> Self {
+    self.needs_audio_data = true;
+    self
+  }
+  /// Whether a voice matches every criterion except the synthesizer, the audio data requirement
+  /// and the language, which are checked separately so that language matches can be ranked.
+  pub(crate) fn matches(&self, voice: &Voice) -> bool {
+    self.name.as_ref().is_none_or(|name| voice.name == *name)
+      && self.id.as_ref().is_none_or(|id| voice.id == *id)
+      && self
+        .gender
+        .is_none_or(|gender| voice.gender == Some(gender))
+      && self.age.is_none_or(|age| voice.age == Some(age))
+      && self
+        .variant
+        .as_ref()
+        .is_none_or(|variant| voice.variant.as_ref() == Some(variant))
+      && self
+        .quality
+        .is_none_or(|quality| voice.quality == Some(quality))
+  }
+  /// How closely a voice matches the language criterion, with 0 for an exact match or no
+  /// criterion, or `None` if it speaks no compatible language.
+  pub(crate) fn language_distance(&self, voice: &Voice) -> Option<u8> {
+    match &self.language {
+      None => Some(0),
+      Some(language) => best_language_distance(language, &voice.languages),
+    }
+  }
+}
+#[cfg(test)]
+mod tests {
+  use super::*;
+  fn voice() -> Voice {
+    Voice {
+      synthesizer: SpeechSynthesizerMetadata {
+        name: "test".to_owned(),
+        supports_speaking_to_audio_data: true,
+        supports_speech_parameters: true,
+        supports_pausing: false,
+      },
+      id: "test:anna".to_owned(),
+      display_name: "Anna".to_owned(),
+      name: "anna".to_owned(),
+      languages: vec!["en-GB".to_owned(), "fr".to_owned()],
+      gender: Some(Gender::Female),
+      age: Some(AgeGroup::Adult),
+      variant: None,
+      quality: Some(VoiceQuality::Enhanced),
+      supports_audio_data: true,
+      sample_rate: None,
+      priority: 0,
+    }
+  }
+  #[test]
+  fn empty_filter_matches_every_voice() {
+    let voice = voice();
+    assert!(VoiceFilter::new().matches(&voice));
+    assert_eq!(VoiceFilter::new().language_distance(&voice), Some(0));
+    let unknown = Voice {
+      languages: Vec::new(),
+      gender: None,
+      age: None,
+      quality: None,
+      ..voice
+    };
+    assert!(VoiceFilter::new().matches(&unknown));
+    assert_eq!(VoiceFilter::new().language_distance(&unknown), Some(0));
+  }
+  #[test]
+  fn matches_gender() {
+    let voice = voice();
+    assert!(VoiceFilter::new().gender(Gender::Female).matches(&voice));
+    assert!(!VoiceFilter::new().gender(Gender::Male).matches(&voice));
+    let unknown = Voice {
+      gender: None,
+      ..voice
+    };
+    assert!(!VoiceFilter::new().gender(Gender::Female).matches(&unknown));
+  }
+  #[test]
+  fn matches_age() {
+    let voice = voice();
+    assert!(VoiceFilter::new().age(AgeGroup::Adult).matches(&voice));
+    assert!(!VoiceFilter::new().age(AgeGroup::Child).matches(&voice));
+    let unknown = Voice { age: None, ..voice };
+    assert!(!VoiceFilter::new().age(AgeGroup::Adult).matches(&unknown));
+  }
+  #[test]
+  fn matches_every_criterion() {
+    let voice = voice();
+    let filter = VoiceFilter::new()
+      .name("anna")
+      .gender(Gender::Female)
+      .age(AgeGroup::Adult);
+    assert!(filter.matches(&voice));
+    assert!(!filter.clone().age(AgeGroup::Senior).matches(&voice));
+    assert!(!filter.clone().name("bob").matches(&voice));
+    assert!(!filter.variant("klatt").matches(&voice));
+  }
+  #[test]
+  fn ranks_compatible_languages() {
+    let voice = voice();
+    assert_eq!(
+      VoiceFilter::new().language("fr").language_distance(&voice),
+      Some(0)
+    );
+    assert_eq!(
+      VoiceFilter::new()
+        .language("en-GB")
+        .language_distance(&voice),
+      Some(0)
+    );
+    assert!(VoiceFilter::new()
+      .language("en-US")
+      .language_distance(&voice)
+      .is_some_and(|distance| distance > 0));
+    assert_eq!(
+      VoiceFilter::new().language("de").language_distance(&voice),
+      None
+    );
+    let unknown = Voice {
+      languages: Vec::new(),
+      ..voice
+    };
+    assert_eq!(
+      VoiceFilter::new()
+        .language("en")
+        .language_distance(&unknown),
+      None
+    );
+  }
+  #[cfg(feature = "serde")]
+  #[test]
+  fn round_trips_through_serde() {
+    let voice = voice();
+    let json = serde_json::to_string(&voice).unwrap();
+    assert_eq!(serde_json::from_str::<Voice>(&json).unwrap(), voice);
+    let variant = VoiceVariant {
+      synthesizer: voice.synthesizer,
+      id: "test:klatt".to_owned(),
+      display_name: "Klatt".to_owned(),
+      name: "klatt".to_owned(),
+      gender: Some(Gender::Neutral),
+      age: Some(AgeGroup::Senior),
+    };
+    let json = serde_json::to_string(&variant).unwrap();
+    assert_eq!(
+      serde_json::from_str::<VoiceVariant>(&json).unwrap(),
+      variant
+    );
+    let filter = VoiceFilter::new()
+      .synthesizer("test")
+      .language("en-GB")
+      .gender(Gender::Female)
+      .age(AgeGroup::Teen)
+      .quality(VoiceQuality::Premium)
+      .needs_audio_data();
+    let json = serde_json::to_string(&filter).unwrap();
+    assert_eq!(serde_json::from_str::<VoiceFilter>(&json).unwrap(), filter);
+  }
+}
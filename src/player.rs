@@ -0,0 +1,404 @@
+use crate::audio::{SampleFormat, SpeechMarker, SpeechResult};
+use crate::priority::{Preemption, Priority};
+use crate::utterance::{Utterance, UtteranceState};
+use rodio::Source;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+fn to_samples(result: &SpeechResult) -> Vec<f32> {
+  match result.sample_format {
+    SampleFormat::S16 => result
+      .pcm
+      .chunks_exact(2)
+      .map(|chunk| f32::from(i16::from_le_bytes([chunk[0], chunk[1]])) / 32768.0)
+      .collect(),
+    SampleFormat::F32 => result
+      .pcm
+      .chunks_exact(4)
+      .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+      .collect(),
+  }
+}
+struct Track {
+  utterance: Utterance,
+  samples: Vec<f32>,
+  sample_rate: u32,
+  position: usize,
+  markers: Vec<SpeechMarker>,
+  next_marker: usize,
+  priority: Priority,
+  /// Whether synthesis has finished, so that no more samples will be appended.
+  complete: bool,
+}
+impl Track {
+  fn remaining(&self) -> usize {
+    self.samples.len() - self.position
+  }
+  fn is_exhausted(&self) -> bool {
+    self.complete && self.remaining() == 0
+  }
+  fn rewind(&mut self) {
+    self.position = 0;
+    self.next_marker = 0;
+    self.utterance.set_state(UtteranceState::Queued);
+  }
+}
+struct PlayerQueue {
+  current: Option<Track>,
+  pending: VecDeque<Track>,
+  playing: bool,
+}
+impl PlayerQueue {
+  fn track_mut(&mut self, utterance: &Utterance) -> Option<&mut Track> {
+    self
+      .current
+      .iter_mut()
+      .chain(self.pending.iter_mut())
+      .find(|track| track.utterance.id() == utterance.id())
+  }
+  fn advance(&mut self) {
+    if let Some(track) = self.current.take() {
+      track.utterance.set_state(UtteranceState::Finished);
+    }
+    self.current = self.pending.pop_front();
+  }
+  /// Inserts a track after every pending track of the same or a more urgent class.
+  fn insert(&mut self, track: Track) {
+    let index = self
+      .pending
+      .iter()
+      .position(|pending| pending.priority.rank() > track.priority.rank())
+      .unwrap_or(self.pending.len());
+    self.pending.insert(index, track);
+  }
+  /// Applies the effect of a new message of priority `priority` to the queue and to the speaking
+  /// track.
+  fn preempt(&mut self, priority: Priority) {
+    self.pending.retain(|track| {
+      if priority.preempts(track.priority) == Preemption::Cancel {
+        track.utterance.set_state(UtteranceState::Cancelled);
+        false
+      } else {
+        true
+      }
+    });
+    let preemption = self
+      .current
+      .as_ref()
+      .map_or(Preemption::Keep, |track| priority.preempts(track.priority));
+    match preemption {
+      Preemption::Keep => {}
+      Preemption::Postpone => {
+        if let Some(mut track) = self.current.take() {
+          track.rewind();
+          let index = self
+            .pending
+            .iter()
+            .position(|pending| pending.priority.rank() >= track.priority.rank())
+            .unwrap_or(self.pending.len());
+          self.pending.insert(index, track);
+        }
+      }
+      Preemption::Cancel => {
+        if let Some(track) = self.current.take() {
+          track.utterance.set_state(UtteranceState::Cancelled);
+        }
+      }
+    }
+  }
+}
+/// Plays the speech of every synthesizer that returns audio data, one utterance after another,
+/// through a single source appended to the sink.
+pub(crate) struct Player {
+  queue: Mutex<PlayerQueue>,
+}
+impl Player {
+  pub(crate) fn new() -> Self {
+    Player {
+      queue: Mutex::new(PlayerQueue {
+        current: None,
+        pending: VecDeque::new(),
+        playing: false,
+      }),
+    }
+  }
+  /// Queues the first chunk of speech for playback according to its priority. Returns `true` if
+  /// nothing was playing, in which case a new [`PlayerSource`] has to be appended to the sink.
+  /// Further chunks are added with [`Player::append`] until [`Player::finish`] is called.
+  pub(crate) fn enqueue(
+    &self,
+    utterance: &Utterance,
+    result: &SpeechResult,
+    priority: Priority,
+  ) -> bool {
+    let mut markers = result.markers.clone();
+    markers.sort_by_key(|marker| marker.sample);
+    let track = Track {
+      utterance: utterance.clone(),
+      samples: to_samples(result),
+      sample_rate: result.sample_rate,
+      position: 0,
+      markers,
+      next_marker: 0,
+      priority,
+      complete: false,
+    };
+    let mut queue = self.queue.lock().unwrap_or_else(PoisonError::into_inner);
+    if priority.is_discarded_when_busy() && (queue.current.is_some() || !queue.pending.is_empty()) {
+      utterance.set_state(UtteranceState::Cancelled);
+      return false;
+    }
+    queue.preempt(priority);
+    queue.insert(track);
+    if queue.current.is_none() {
+      queue.advance();
+    }
+    let needs_source = !queue.playing;
+    queue.playing = true;
+    needs_source
+  }
+  /// Adds a chunk of speech to a queued utterance. Returns `false` if the utterance is no longer
+  /// queued, for example because it was cancelled.
+  pub(crate) fn append(&self, utterance: &Utterance, result: &SpeechResult) -> bool {
+    let mut queue = self.queue.lock().unwrap_or_else(PoisonError::into_inner);
+    let Some(track) = queue.track_mut(utterance) else {
+      return false;
+    };
+    let offset = track.samples.len();
+    track
+      .markers
+      .extend(result.markers.iter().map(|marker| SpeechMarker {
+        sample: marker.sample + offset,
+        kind: marker.kind.clone(),
+      }));
+    track.markers[track.next_marker..].sort_by_key(|marker| marker.sample);
+    track.samples.extend(to_samples(result));
+    true
+  }
+  /// Marks the speech of an utterance as complete, so that playback moves on once it is played.
+  pub(crate) fn finish(&self, utterance: &Utterance) {
+    let mut queue = self.queue.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(track) = queue.track_mut(utterance) {
+      track.complete = true;
+    }
+  }
+  /// Removes an utterance from the queue, or stops it if it is playing. Returns `false` if the
+  /// utterance is not known to the player.
+  pub(crate) fn cancel(&self, utterance: &Utterance) -> bool {
+    let mut queue = self.queue.lock().unwrap_or_else(PoisonError::into_inner);
+    if queue
+      .current
+      .as_ref()
+      .is_some_and(|track| track.utterance.id() == utterance.id())
+    {
+      utterance.set_state(UtteranceState::Cancelled);
+      queue.advance();
+      return true;
+    }
+    let length = queue.pending.len();
+    queue.pending.retain(|track| {
+      if track.utterance.id() == utterance.id() {
+        track.utterance.set_state(UtteranceState::Cancelled);
+        false
+      } else {
+        true
+      }
+    });
+    length != queue.pending.len()
+  }
+  pub(crate) fn stop(&self) {
+    let mut queue = self.queue.lock().unwrap_or_else(PoisonError::into_inner);
+    for track in queue
+      .current
+      .take()
+      .into_iter()
+      .chain(queue.pending.drain(..))
+    {
+      track.utterance.set_state(UtteranceState::Cancelled);
+    }
+  }
+}
+pub(crate) struct PlayerSource {
+  pub(crate) player: Arc<Player>,
+}
+impl Iterator for PlayerSource {
+  type Item = f32;
+  fn next(&mut self) -> Option<f32> {
+    let mut queue = self
+      .player
+      .queue
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner);
+    while queue.current.as_ref().is_some_and(Track::is_exhausted) {
+      queue.advance();
+    }
+    let Some(track) = queue.current.as_mut() else {
+      queue.playing = false;
+      return None;
+    };
+    if track.remaining() == 0 {
+      // Synthesis has not caught up with playback yet.
+      return Some(0.0);
+    }
+    if track.position == 0 {
+      track.utterance.set_state(UtteranceState::Speaking);
+    }
+    while let Some(marker) = track
+      .markers
+      .get(track.next_marker)
+      .filter(|marker| marker.sample <= track.position)
+    {
+      track.utterance.emit(marker.kind.clone());
+      track.next_marker += 1;
+    }
+    let sample = track.samples[track.position];
+    track.position += 1;
+    if track.is_exhausted() {
+      queue.advance();
+    }
+    Some(sample)
+  }
+}
+impl Source for PlayerSource {
+  fn current_frame_len(&self) -> Option<usize> {
+    let queue = self
+      .player
+      .queue
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner);
+    queue
+      .current
+      .as_ref()
+      .map(Track::remaining)
+      .filter(|remaining| *remaining > 0)
+      .or(Some(1))
+  }
+  fn channels(&self) -> u16 {
+    1
+  }
+  fn sample_rate(&self) -> u32 {
+    let queue = self
+      .player
+      .queue
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner);
+    queue
+      .current
+      .as_ref()
+      .or(queue.pending.front())
+      .map_or(22050, |track| track.sample_rate)
+  }
+  fn total_duration(&self) -> Option<Duration> {
+    None
+  }
+}
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::events::EventBus;
+  const PRIORITIES: [Priority; 5] = [
+    Priority::Important,
+    Priority::Message,
+    Priority::Text,
+    Priority::Notification,
+    Priority::Progress,
+  ];
+  /// Four samples of silence.
+  fn speech() -> SpeechResult {
+    SpeechResult {
+      pcm: vec![0; 8],
+      sample_format: SampleFormat::S16,
+      sample_rate: 1000,
+      markers: Vec::new(),
+    }
+  }
+  fn enqueue(player: &Player, events: &Arc<EventBus>, id: u64, priority: Priority) -> Utterance {
+    let utterance = Utterance::pending(id, events);
+    player.enqueue(&utterance, &speech(), priority);
+    player.finish(&utterance);
+    utterance
+  }
+  /// The identifiers of the queued utterances, starting with the one playing.
+  fn order(player: &Player) -> Vec<u64> {
+    let queue = player.queue.lock().unwrap();
+    queue
+      .current
+      .iter()
+      .chain(queue.pending.iter())
+      .map(|track| track.utterance.id())
+      .collect()
+  }
+  #[test]
+  fn new_messages_affect_speaking_utterances_by_priority() {
+    use UtteranceState::{Cancelled, Queued, Speaking};
+    // Rows are new messages and columns the speaking utterance, both in the order of PRIORITIES.
+    // Each cell holds the state of the speaking utterance and the resulting queue order.
+    let keep = (Speaking, &[1, 2][..]);
+    let postpone = (Queued, &[2, 1][..]);
+    let cancel = (Cancelled, &[2][..]);
+    let discard = (Speaking, &[1][..]);
+    let expected = [
+      [keep, postpone, postpone, cancel, cancel],
+      [keep, keep, cancel, cancel, cancel],
+      [keep, keep, keep, cancel, cancel],
+      [discard, discard, discard, discard, discard],
+      [keep, keep, keep, keep, cancel],
+    ];
+    let events = Arc::new(EventBus::new());
+    for (new, row) in PRIORITIES.iter().zip(expected) {
+      for (old, (state, queue)) in PRIORITIES.iter().zip(row) {
+        let player = Arc::new(Player::new());
+        let mut source = PlayerSource {
+          player: player.clone(),
+        };
+        let speaking = enqueue(&player, &events, 1, *old);
+        source.next();
+        let message = enqueue(&player, &events, 2, *new);
+        assert_eq!(speaking.state(), state, "{new:?} meeting {old:?}");
+        assert_eq!(order(&player), queue, "{new:?} meeting {old:?}");
+        if queue.contains(&2) {
+          assert_ne!(message.state(), Cancelled, "{new:?} meeting {old:?}");
+        } else {
+          assert_eq!(message.state(), Cancelled, "{new:?} meeting {old:?}");
+        }
+      }
+    }
+  }
+  #[test]
+  fn postponed_utterances_start_over() {
+    let events = Arc::new(EventBus::new());
+    let player = Arc::new(Player::new());
+    let mut source = PlayerSource {
+      player: player.clone(),
+    };
+    let text = enqueue(&player, &events, 1, Priority::Text);
+    source.next();
+    source.next();
+    let important = enqueue(&player, &events, 2, Priority::Important);
+    assert_eq!(source.by_ref().count(), 8);
+    assert_eq!(important.state(), UtteranceState::Finished);
+    assert_eq!(text.state(), UtteranceState::Finished);
+  }
+  #[test]
+  fn queues_by_priority_and_postpones_before_equal_priorities() {
+    let events = Arc::new(EventBus::new());
+    let player = Player::new();
+    enqueue(&player, &events, 1, Priority::Message);
+    enqueue(&player, &events, 2, Priority::Message);
+    enqueue(&player, &events, 3, Priority::Important);
+    enqueue(&player, &events, 4, Priority::Important);
+    enqueue(&player, &events, 5, Priority::Message);
+    assert_eq!(order(&player), [3, 4, 1, 2, 5]);
+  }
+  #[test]
+  fn progress_waits_for_the_queue_and_replaces_earlier_progress() {
+    let events = Arc::new(EventBus::new());
+    let player = Player::new();
+    enqueue(&player, &events, 1, Priority::Text);
+    let first = enqueue(&player, &events, 2, Priority::Progress);
+    assert_eq!(order(&player), [1, 2]);
+    enqueue(&player, &events, 3, Priority::Progress);
+    assert_eq!(first.state(), UtteranceState::Cancelled);
+    assert_eq!(order(&player), [1, 3]);
+  }
+}
@@ -0,0 +1,105 @@
+/// The class of a message, which decides how it is queued and what it interrupts. The classes
+/// follow the priorities of the Speech Synthesis Interface Protocol used by Speech Dispatcher:
+///
+/// - `Important` messages are spoken as soon as possible, after other important messages. They
+///   postpone `Message` and `Text` speech, which starts over afterwards, and cancel notifications
+///   and progress messages.
+/// - `Message` messages are queued after important messages and other messages. They cancel
+///   `Text`, `Notification` and `Progress` speech.
+/// - `Text` messages are queued after important messages, messages and other text. They cancel
+///   `Notification` and `Progress` speech.
+/// - `Notification` messages are only spoken when nothing else is queued or speaking, and are
+///   discarded otherwise.
+/// - `Progress` messages behave like notifications, except that they wait for the queue to
+///   empty instead of being discarded. Each progress message cancels the previous one.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Priority {
+  Important,
+  Message,
+  #[default]
+  Text,
+  Notification,
+  Progress,
+}
+/// What happens to queued or speaking speech when a new message arrives.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Preemption {
+  Keep,
+  Postpone,
+  Cancel,
+}
+impl Priority {
+  /// The position of the class in the queue; lower ranks are spoken first.
+  pub(crate) fn rank(self) -> u8 {
+    match self {
+      Priority::Important => 0,
+      Priority::Message => 1,
+      Priority::Text => 2,
+      Priority::Notification | Priority::Progress => 3,
+    }
+  }
+  /// How a new message of this priority affects speech of priority `other`.
+  pub(crate) fn preempts(self, other: Priority) -> Preemption {
+    match (self, other) {
+      (_, Priority::Important)
+      | (Priority::Notification, _)
+      | (Priority::Text, Priority::Message | Priority::Text)
+      | (Priority::Message, Priority::Message)
+      | (Priority::Progress, Priority::Message | Priority::Text | Priority::Notification) => {
+        Preemption::Keep
+      }
+      (Priority::Important, Priority::Message | Priority::Text) => Preemption::Postpone,
+      (Priority::Progress, Priority::Progress)
+      | (Priority::Important | Priority::Message, _)
+      | (Priority::Text, Priority::Notification | Priority::Progress) => Preemption::Cancel,
+    }
+  }
+  /// Whether a new message of this priority is discarded when other speech is queued or
+  /// speaking.
+  pub(crate) fn is_discarded_when_busy(self) -> bool {
+    self == Priority::Notification
+  }
+}
+#[cfg(test)]
+mod tests {
+  use super::*;
+  const PRIORITIES: [Priority; 5] = [
+    Priority::Important,
+    Priority::Message,
+    Priority::Text,
+    Priority::Notification,
+    Priority::Progress,
+  ];
+  #[test]
+  fn ranks_follow_queue_order() {
+    let ranks: Vec<u8> = PRIORITIES.iter().map(|priority| priority.rank()).collect();
+    assert_eq!(ranks, [0, 1, 2, 3, 3]);
+  }
+  #[test]
+  fn preempts_according_to_ssip() {
+    use Preemption::{Cancel, Keep, Postpone};
+    // Rows are new messages and columns the speech they meet, both in the order of PRIORITIES.
+    let expected = [
+      [Keep, Postpone, Postpone, Cancel, Cancel],
+      [Keep, Keep, Cancel, Cancel, Cancel],
+      [Keep, Keep, Keep, Cancel, Cancel],
+      [Keep, Keep, Keep, Keep, Keep],
+      [Keep, Keep, Keep, Keep, Cancel],
+    ];
+    for (new, row) in PRIORITIES.iter().zip(expected) {
+      for (old, preemption) in PRIORITIES.iter().zip(row) {
+        assert_eq!(new.preempts(*old), preemption, "{new:?} meeting {old:?}");
+      }
+    }
+  }
+  #[test]
+  fn only_notifications_are_discarded_when_busy() {
+    for priority in PRIORITIES {
+      assert_eq!(
+        priority.is_discarded_when_busy(),
+        priority == Priority::Notification
+      );
+    }
+  }
+}
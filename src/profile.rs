@@ -0,0 +1,416 @@
+use crate::capitals::Capitals;
+use crate::error::OutputError;
+use crate::language::language_distance;
+use crate::priority::Priority;
+use crate::punctuation::Punctuation;
+use crate::request::{SpeechContent, SpeechRequest};
+use crate::utterance::Utterance;
+use crate::Whisprs;
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError, RwLock};
+use std::time::SystemTime;
+/// Speech settings stored under a name, such as `chat`, `ui` or `narration`. Settings that are
+/// not set fall back to the defaults of whisp-rs, and settings passed with a request take
+/// precedence over the profile.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpeechProfile {
+  pub synthesizer: Option<String>,
+  pub voice: Option<String>,
+  pub variant: Option<String>,
+  pub language: Option<String>,
+  pub rate: Option<u8>,
+  pub volume: Option<u8>,
+  pub pitch: Option<u8>,
+  pub punctuation: Option<Punctuation>,
+  pub capitals: Option<Capitals>,
+  /// Voices used instead of `voice` for text in other languages, keyed by BCP 47 language tag.
+  pub language_voices: HashMap<String, String>,
+}
+impl SpeechProfile {
+  /// The override for the language that matches it most closely.
+  fn voice_for_language(&self, language: &str) -> Option<String> {
+    self
+      .language_voices
+      .iter()
+      .filter_map(|(tag, voice)| language_distance(language, tag).map(|distance| (distance, voice)))
+      .min_by_key(|(distance, _)| *distance)
+      .map(|(_, voice)| voice.clone())
+  }
+  /// Fills in the settings a request does not set. The voice of the profile is only used when
+  /// the request does not ask for another language than the profile.
+  fn apply(&self, request: &SpeechRequest) -> SpeechRequest {
+    let mut request = request.clone();
+    if request.voice.is_none() {
+      request.voice = match &request.language {
+        Some(language) if self.language.as_ref() != Some(language) => {
+          self.voice_for_language(language)
+        }
+        _ => self.voice.clone(),
+      };
+    }
+    request.synthesizer = request.synthesizer.or(self.synthesizer.clone());
+    request.variant = request.variant.or(self.variant.clone());
+    request.language = request.language.or(self.language.clone());
+    request.rate = request.rate.or(self.rate);
+    request.volume = request.volume.or(self.volume);
+    request.pitch = request.pitch.or(self.pitch);
+    request.punctuation = request.punctuation.or(self.punctuation.clone());
+    request.capitals = request.capitals.or(self.capitals);
+    if let SpeechContent::Annotated(text) = &mut request.content {
+      for span in &mut text.spans {
+        if let (None, Some(language)) = (&span.voice, &span.language) {
+          span.voice = self.voice_for_language(language);
+        }
+      }
+    }
+    request
+  }
+}
+/// A set of named [`SpeechProfile`]s. In TOML, each profile is a table:
+///
+/// ```toml
+/// [chat]
+/// voice = "English (America)"
+/// rate = 70
+///
+/// [chat.language_voices]
+/// de = "German"
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SpeechProfiles {
+  pub profiles: HashMap<String, SpeechProfile>,
+}
+impl SpeechProfiles {
+  #[must_use]
+  pub fn new() -> Self {
+    SpeechProfiles::default()
+  }
+  #[must_use]
+  pub fn profile(mut self, name: &str, profile: SpeechProfile) -> Self {
+    self.profiles.insert(name.to_owned(), profile);
+    self
+  }
+  #[must_use]
+  pub fn get(&self, name: &str) -> Option<&SpeechProfile> {
+    self.profiles.get(name)
+  }
+  pub fn from_toml(text: &str) -> Result<Self, OutputError> {
+    toml::from_str(text).map_err(OutputError::into_invalid_parameter)
+  }
+  pub fn from_json(text: &str) -> Result<Self, OutputError> {
+    serde_json::from_str(text).map_err(OutputError::into_invalid_parameter)
+  }
+  /// Reads profiles from a file, which is parsed as JSON if its extension is `json` and as TOML
+  /// otherwise.
+  pub fn load(path: &Path) -> Result<Self, OutputError> {
+    let text = std::fs::read_to_string(path).map_err(|err| {
+      OutputError::into_invalid_parameter(anyhow!(
+        "Failed to read speech profiles from {}: {err}",
+        path.display()
+      ))
+    })?;
+    if path
+      .extension()
+      .is_some_and(|extension| extension.eq_ignore_ascii_case("json"))
+    {
+      SpeechProfiles::from_json(&text)
+    } else {
+      SpeechProfiles::from_toml(&text)
+    }
+  }
+}
+/// The profiles of a [`Whisprs`] instance, and the file they are reloaded from when it changes.
+pub(crate) struct ProfileStore {
+  path: Option<PathBuf>,
+  modified: Mutex<Option<SystemTime>>,
+  profiles: RwLock<SpeechProfiles>,
+}
+impl ProfileStore {
+  pub(crate) fn new(
+    profiles: Option<SpeechProfiles>,
+    path: Option<PathBuf>,
+  ) -> Result<Self, OutputError> {
+    let store = ProfileStore {
+      path,
+      modified: Mutex::new(None),
+      profiles: RwLock::new(profiles.unwrap_or_default()),
+    };
+    if store.path.is_some() {
+      store.reload()?;
+    }
+    Ok(store)
+  }
+  fn modification_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+      .and_then(|metadata| metadata.modified())
+      .ok()
+  }
+  fn reload(&self) -> Result<(), OutputError> {
+    let Some(path) = &self.path else {
+      return Ok(());
+    };
+    let modified = ProfileStore::modification_time(path);
+    let profiles = SpeechProfiles::load(path)?;
+    *self
+      .profiles
+      .write()
+      .unwrap_or_else(PoisonError::into_inner) = profiles;
+    *self.modified.lock().unwrap_or_else(PoisonError::into_inner) = modified;
+    Ok(())
+  }
+  /// Reloads the file if it was modified since it was last read. A file that fails to parse,
+  /// for example while it is being saved, leaves the previous profiles in place.
+  fn reload_if_changed(&self) {
+    let Some(path) = &self.path else {
+      return;
+    };
+    let modified = ProfileStore::modification_time(path);
+    let changed = *self.modified.lock().unwrap_or_else(PoisonError::into_inner) != modified;
+    if changed {
+      let _result = self.reload();
+    }
+  }
+  /// Applies the profile a request names, if any.
+  pub(crate) fn apply(&self, request: &SpeechRequest) -> Result<SpeechRequest, OutputError> {
+    let Some(name) = &request.profile else {
+      return Ok(request.clone());
+    };
+    self.reload_if_changed();
+    let profiles = self.profiles.read().unwrap_or_else(PoisonError::into_inner);
+    let profile = profiles
+      .get(name)
+      .ok_or(OutputError::into_profile_not_found(name))?;
+    Ok(profile.apply(request))
+  }
+}
+/// Speech profiles, available with the `profiles` feature.
+impl Whisprs {
+  /// Speaks text with the settings of a profile.
+  pub fn speak_with_profile(
+    &self,
+    profile: &str,
+    text: &str,
+    interrupt: bool,
+    priority: Priority,
+  ) -> Result<Utterance, OutputError> {
+    let mut request = SpeechRequest::text(text)
+      .profile(profile)
+      .priority(priority);
+    request.interrupt = interrupt;
+    self.speak_request_to_audio_output(&request)
+  }
+  /// Returns a copy of the current profiles.
+  #[must_use]
+  pub fn profiles(&self) -> SpeechProfiles {
+    self.profiles.reload_if_changed();
+    self
+      .profiles
+      .profiles
+      .read()
+      .unwrap_or_else(PoisonError::into_inner)
+      .clone()
+  }
+  /// Replaces the profiles until the profile file, if one was given, changes again.
+  pub fn set_profiles(&self, profiles: SpeechProfiles) {
+    *self
+      .profiles
+      .profiles
+      .write()
+      .unwrap_or_else(PoisonError::into_inner) = profiles;
+  }
+  /// Reads the profile file again, even if it did not change. Does nothing if profiles were not
+  /// loaded from a file.
+  pub fn reload_profiles(&self) -> Result<(), OutputError> {
+    self.profiles.reload()
+  }
+}
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::annotated_text::{AnnotatedText, TextSpan};
+  use std::fs::File;
+  use std::time::Duration;
+  const TOML: &str = r#"
+[chat]
+voice = "English"
+language = "en"
+rate = 70
+punctuation = "Most"
+capitals = "SayCap"
+
+[chat.language_voices]
+de = "German"
+fr-CA = "Canadian French"
+"#;
+  fn chat() -> SpeechProfile {
+    SpeechProfile {
+      voice: Some("English".to_owned()),
+      language: Some("en".to_owned()),
+      rate: Some(70),
+      punctuation: Some(Punctuation::Most),
+      capitals: Some(Capitals::SayCap),
+      language_voices: HashMap::from([
+        ("de".to_owned(), "German".to_owned()),
+        ("fr-CA".to_owned(), "Canadian French".to_owned()),
+      ]),
+      ..SpeechProfile::default()
+    }
+  }
+  /// A profile file in the temporary directory, removed when the test ends.
+  struct ProfileFile(PathBuf);
+  impl ProfileFile {
+    fn new(name: &str, text: &str) -> Self {
+      let path = std::env::temp_dir().join(format!("whisp-rs-{}-{name}", std::process::id()));
+      std::fs::write(&path, text).unwrap();
+      ProfileFile(path)
+    }
+    /// Replaces the contents and moves the modification time forward, so that the change is
+    /// seen even on file systems with coarse timestamps.
+    fn write(&self, text: &str) {
+      let modified = ProfileStore::modification_time(&self.0).unwrap();
+      std::fs::write(&self.0, text).unwrap();
+      File::options()
+        .write(true)
+        .open(&self.0)
+        .unwrap()
+        .set_modified(modified + Duration::from_secs(2))
+        .unwrap();
+    }
+  }
+  impl Drop for ProfileFile {
+    fn drop(&mut self) {
+      let _result = std::fs::remove_file(&self.0);
+    }
+  }
+  #[test]
+  fn parses_toml_and_json() {
+    let expected = SpeechProfiles::new().profile("chat", chat());
+    assert_eq!(SpeechProfiles::from_toml(TOML).unwrap(), expected);
+    let json = r#"{
+      "chat": {
+        "voice": "English",
+        "language": "en",
+        "rate": 70,
+        "punctuation": "Most",
+        "capitals": "SayCap",
+        "language_voices": {"de": "German", "fr-CA": "Canadian French"}
+      }
+    }"#;
+    assert_eq!(SpeechProfiles::from_json(json).unwrap(), expected);
+    assert_eq!(
+      SpeechProfiles::from_toml("[ui]").unwrap().get("ui"),
+      Some(&SpeechProfile::default())
+    );
+  }
+  #[test]
+  fn rejects_invalid_profiles() {
+    for text in [
+      "[chat",
+      "[chat]\nrate = 300",
+      "[chat]\npunctuation = \"Loud\"",
+    ] {
+      assert!(
+        matches!(
+          SpeechProfiles::from_toml(text),
+          Err(OutputError::InvalidParameter(_))
+        ),
+        "{text:?}"
+      );
+    }
+    assert!(matches!(
+      SpeechProfiles::from_json("{\"chat\": {\"rate\": \"fast\"}}"),
+      Err(OutputError::InvalidParameter(_))
+    ));
+    let missing = std::env::temp_dir().join("whisp-rs-missing-profiles.toml");
+    assert!(matches!(
+      SpeechProfiles::load(&missing),
+      Err(OutputError::InvalidParameter(_))
+    ));
+    let store = ProfileStore::new(Some(SpeechProfiles::new()), None).unwrap();
+    assert!(matches!(
+      store.apply(&SpeechRequest::text("Hi").profile("chat")),
+      Err(OutputError::ProfileNotFound(name)) if name == "chat"
+    ));
+  }
+  #[test]
+  fn request_settings_take_precedence() {
+    let request = chat().apply(&SpeechRequest::text("Hi").rate(30).voice("Other"));
+    assert_eq!(request.rate, Some(30));
+    assert_eq!(request.voice.as_deref(), Some("Other"));
+    assert_eq!(request.language.as_deref(), Some("en"));
+    assert_eq!(request.punctuation, Some(Punctuation::Most));
+    assert_eq!(request.capitals, Some(Capitals::SayCap));
+  }
+  #[test]
+  fn uses_language_overrides() {
+    let profile = chat();
+    let voice = |request: SpeechRequest| profile.apply(&request).voice;
+    assert_eq!(voice(SpeechRequest::text("Hi")).as_deref(), Some("English"));
+    assert_eq!(
+      voice(SpeechRequest::text("Hi").language("en")).as_deref(),
+      Some("English")
+    );
+    assert_eq!(
+      voice(SpeechRequest::text("Hallo").language("de-AT")).as_deref(),
+      Some("German")
+    );
+    assert_eq!(
+      voice(SpeechRequest::text("Salut").language("fr")).as_deref(),
+      Some("Canadian French")
+    );
+    assert_eq!(voice(SpeechRequest::text("Ciao").language("it")), None);
+    let request = profile.apply(&SpeechRequest::annotated(
+      AnnotatedText::new()
+        .text("Hi ")
+        .span(TextSpan::new("Hallo").language("de"))
+        .span(TextSpan::new("Ciao").language("it"))
+        .span(TextSpan::new("Hola").language("de").voice("Spanish")),
+    ));
+    let SpeechContent::Annotated(text) = request.content else {
+      panic!("The content changed");
+    };
+    let voices: Vec<Option<&str>> = text
+      .spans
+      .iter()
+      .map(|span| span.voice.as_deref())
+      .collect();
+    assert_eq!(voices, [None, Some("German"), None, Some("Spanish")]);
+  }
+  #[test]
+  fn reloads_changed_files() {
+    let file = ProfileFile::new("profiles.toml", TOML);
+    let store = ProfileStore::new(None, Some(file.0.clone())).unwrap();
+    let request = SpeechRequest::text("Hi").profile("chat");
+    assert_eq!(store.apply(&request).unwrap().rate, Some(70));
+    file.write("[chat]\nrate = 40");
+    assert_eq!(store.apply(&request).unwrap().rate, Some(40));
+    file.write("[chat");
+    assert_eq!(store.apply(&request).unwrap().rate, Some(40));
+    assert!(matches!(
+      store.reload(),
+      Err(OutputError::InvalidParameter(_))
+    ));
+    file.write("[ui]");
+    assert!(matches!(
+      store.apply(&request),
+      Err(OutputError::ProfileNotFound(_))
+    ));
+  }
+  #[test]
+  fn loads_json_files_by_extension() {
+    let file = ProfileFile::new("profiles.json", r#"{"ui": {"rate": 90}}"#);
+    let profiles = SpeechProfiles::load(&file.0).unwrap();
+    assert_eq!(
+      profiles.get("ui").and_then(|profile| profile.rate),
+      Some(90)
+    );
+    let file = ProfileFile::new("other-profiles.JSON", "[ui]\nrate = 90");
+    assert!(SpeechProfiles::load(&file.0).is_err());
+    assert!(ProfileStore::new(None, Some(file.0.clone())).is_err());
+  }
+}
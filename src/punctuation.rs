@@ -0,0 +1,132 @@
+use std::borrow::Cow;
+/// Symbols spoken at every level above [`Punctuation::None`].
+const SOME: &str = "#$%&*+/<=>@\\^_|~";
+/// Punctuation added at [`Punctuation::Most`].
+const MOST: &str = "\"'()-:;[]{}`";
+/// Sentence punctuation, which most engines only turn into pauses and intonation, spoken at
+/// [`Punctuation::All`].
+const ALL: &str = "!,.?";
+/// The English names spoken for punctuation by backends that cannot speak it themselves.
+const NAMES: [(char, &str); 32] = [
+  ('!', "exclamation"),
+  ('"', "quote"),
+  ('#', "number"),
+  ('$', "dollar"),
+  ('%', "percent"),
+  ('&', "and"),
+  ('\'', "apostrophe"),
+  ('(', "left paren"),
+  (')', "right paren"),
+  ('*', "star"),
+  ('+', "plus"),
+  (',', "comma"),
+  ('-', "dash"),
+  ('.', "dot"),
+  ('/', "slash"),
+  (':', "colon"),
+  (';', "semicolon"),
+  ('<', "less"),
+  ('=', "equals"),
+  ('>', "greater"),
+  ('?', "question"),
+  ('@', "at"),
+  ('[', "left bracket"),
+  ('\\', "backslash"),
+  (']', "right bracket"),
+  ('^', "caret"),
+  ('_', "underline"),
+  ('`', "grave"),
+  ('{', "left brace"),
+  ('|', "bar"),
+  ('}', "right brace"),
+  ('~', "tilde"),
+];
+/// How much punctuation is spoken. Speech Dispatcher and eSpeak NG speak it themselves; for the
+/// other backends, the spoken characters are replaced with their names before synthesis, and
+/// [`Punctuation::None`] leaves the text as it is.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Punctuation {
+  None,
+  Some,
+  Most,
+  All,
+  /// Only the given characters.
+  Custom(String),
+}
+/// The English name of a punctuation character, if it has one.
+pub(crate) fn name(character: char) -> Option<&'static str> {
+  NAMES
+    .iter()
+    .find(|(named, _)| *named == character)
+    .map(|(_, name)| *name)
+}
+impl Punctuation {
+  /// The characters that are spoken.
+  #[must_use]
+  pub fn characters(&self) -> Cow<'_, str> {
+    match self {
+      Punctuation::None => Cow::Borrowed(""),
+      Punctuation::Some => Cow::Borrowed(SOME),
+      Punctuation::Most => Cow::Owned(format!("{SOME}{MOST}")),
+      Punctuation::All => Cow::Owned(format!("{SOME}{MOST}{ALL}")),
+      Punctuation::Custom(characters) => Cow::Borrowed(characters),
+    }
+  }
+  /// Replaces the spoken characters that have a name with it.
+  pub(crate) fn speak_names(&self, text: &str) -> String {
+    let characters = self.characters();
+    let mut output = String::with_capacity(text.len());
+    for character in text.chars() {
+      match name(character).filter(|_| characters.contains(character)) {
+        Some(name) => {
+          output.push(' ');
+          output.push_str(name);
+          output.push(' ');
+        }
+        None => output.push(character),
+      }
+    }
+    output
+  }
+}
+#[cfg(test)]
+mod tests {
+  use super::*;
+  #[test]
+  fn levels_include_lower_levels() {
+    assert_eq!(Punctuation::None.characters(), "");
+    for character in SOME.chars() {
+      assert!(Punctuation::Most.characters().contains(character));
+      assert!(Punctuation::All.characters().contains(character));
+    }
+    assert!(!Punctuation::Some.characters().contains('('));
+    assert!(Punctuation::Most.characters().contains('('));
+    assert!(!Punctuation::Most.characters().contains('.'));
+    assert!(Punctuation::All.characters().contains('.'));
+  }
+  #[test]
+  fn every_level_character_has_a_name() {
+    for character in Punctuation::All.characters().chars() {
+      assert!(name(character).is_some(), "{character:?} has no name");
+    }
+  }
+  #[test]
+  fn speaks_names_of_spoken_characters() {
+    let text = "Hi, a+b (c).";
+    assert_eq!(Punctuation::None.speak_names(text), text);
+    assert_eq!(Punctuation::Some.speak_names(text), "Hi, a plus b (c).");
+    assert_eq!(
+      Punctuation::Most.speak_names(text),
+      "Hi, a plus b  left paren c right paren ."
+    );
+    assert_eq!(
+      Punctuation::All.speak_names(text),
+      "Hi comma  a plus b  left paren c right paren  dot "
+    );
+    assert_eq!(
+      Punctuation::Custom(",é".to_owned()).speak_names("é, x"),
+      "é comma  x"
+    );
+  }
+}
@@ -0,0 +1,209 @@
+use crate::annotated_text::AnnotatedText;
+use crate::capitals::Capitals;
+use crate::keys::KeyCombination;
+use crate::priority::Priority;
+use crate::punctuation::Punctuation;
+use crate::text_format::TextFormat;
+/// What a [`SpeechRequest`] speaks.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SpeechContent {
+  /// Text interpreted in the [`SpeechRequest::format`] of the request.
+  Text(String),
+  /// An SSML document, which is parsed when the request is spoken.
+  Ssml(String),
+  Annotated(AnnotatedText),
+  /// Text spelled one character at a time, like [`crate::Whisprs::spell`].
+  Spelled {
+    text: String,
+    phonetic: bool,
+  },
+  /// A key, like [`crate::Whisprs::speak_key`].
+  Key(KeyCombination),
+}
+/// A complete speak call, which can be stored or sent to another process with the `serde`
+/// feature and spoken with [`crate::Whisprs::speak_request_to_audio_output`] or
+/// [`crate::Whisprs::speak_request_to_audio_data`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SpeechRequest {
+  /// A [`crate::profile::SpeechProfile`] whose settings are used for the ones this request does
+  /// not set.
+  #[cfg(feature = "profiles")]
+  pub profile: Option<String>,
+  pub synthesizer: Option<String>,
+  pub voice: Option<String>,
+  pub variant: Option<String>,
+  pub language: Option<String>,
+  pub rate: Option<u8>,
+  pub volume: Option<u8>,
+  pub pitch: Option<u8>,
+  /// Overrides the level set with [`crate::Whisprs::set_punctuation`].
+  pub punctuation: Option<Punctuation>,
+  /// Overrides the mode set with [`crate::Whisprs::set_capitals`].
+  pub capitals: Option<Capitals>,
+  pub content: SpeechContent,
+  /// How [`SpeechContent::Text`] is interpreted.
+  pub format: TextFormat,
+  /// Ignored when speaking to audio data.
+  pub interrupt: bool,
+  /// Ignored when speaking to audio data.
+  pub priority: Priority,
+}
+impl SpeechRequest {
+  #[must_use]
+  pub fn new(content: SpeechContent) -> Self {
+    SpeechRequest {
+      #[cfg(feature = "profiles")]
+      profile: None,
+      synthesizer: None,
+      voice: None,
+      variant: None,
+      language: None,
+      rate: None,
+      volume: None,
+      pitch: None,
+      punctuation: None,
+      capitals: None,
+      content,
+      format: TextFormat::default(),
+      interrupt: false,
+      priority: Priority::default(),
+    }
+  }
+  #[must_use]
+  pub fn text(text: &str) -> Self {
+    SpeechRequest::new(SpeechContent::Text(text.to_owned()))
+  }
+  #[must_use]
+  pub fn ssml(ssml: &str) -> Self {
+    SpeechRequest::new(SpeechContent::Ssml(ssml.to_owned()))
+  }
+  #[must_use]
+  pub fn annotated(text: AnnotatedText) -> Self {
+    SpeechRequest::new(SpeechContent::Annotated(text))
+  }
+  #[must_use]
+  pub fn spelled(text: &str, phonetic: bool) -> Self {
+    SpeechRequest::new(SpeechContent::Spelled {
+      text: text.to_owned(),
+      phonetic,
+    })
+  }
+  #[must_use]
+  pub fn key(key: KeyCombination) -> Self {
+    SpeechRequest::new(SpeechContent::Key(key))
+  }
+  #[cfg(feature = "profiles")]
+  #[must_use]
+  pub fn profile(mut self, profile: &str) -> Self {
+    self.profile = Some(profile.to_owned());
+    self
+  }
+  #[must_use]
+  pub fn synthesizer(mut self, synthesizer: &str) -> Self {
+    self.synthesizer = Some(synthesizer.to_owned());
+    self
+  }
+  #[must_use]
+  pub fn voice(mut self, voice: &str) -> Self {
+    self.voice = Some(voice.to_owned());
+    self
+  }
+  #[must_use]
+  pub fn variant(mut self, variant: &str) -> Self {
+    self.variant = Some(variant.to_owned());
+    self
+  }
+  #[must_use]
+  pub fn language(mut self, language: &str) -> Self {
+    self.language = Some(language.to_owned());
+    self
+  }
+  #[must_use]
+  pub fn rate(mut self, rate: u8) -> Self {
+    self.rate = Some(rate);
+    self
+  }
+  #[must_use]
+  pub fn volume(mut self, volume: u8) -> Self {
+    self.volume = Some(volume);
+    self
+  }
+  #[must_use]
+  pub fn pitch(mut self, pitch: u8) -> Self {
+    self.pitch = Some(pitch);
+    self
+  }
+  #[must_use]
+  pub fn punctuation(mut self, punctuation: Punctuation) -> Self {
+    self.punctuation = Some(punctuation);
+    self
+  }
+  #[must_use]
+  pub fn capitals(mut self, capitals: Capitals) -> Self {
+    self.capitals = Some(capitals);
+    self
+  }
+  #[must_use]
+  pub fn format(mut self, format: TextFormat) -> Self {
+    self.format = format;
+    self
+  }
+  #[must_use]
+  pub fn interrupt(mut self) -> Self {
+    self.interrupt = true;
+    self
+  }
+  #[must_use]
+  pub fn priority(mut self, priority: Priority) -> Self {
+    self.priority = priority;
+    self
+  }
+}
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+  use super::*;
+  use crate::annotated_text::TextSpan;
+  use crate::keys::{Key, Modifier};
+  fn round_trip(request: &SpeechRequest) {
+    let json = serde_json::to_string(request).unwrap();
+    assert_eq!(
+      &serde_json::from_str::<SpeechRequest>(&json).unwrap(),
+      request
+    );
+  }
+  #[test]
+  fn round_trips_every_content() {
+    round_trip(&SpeechRequest::text("Hello"));
+    round_trip(&SpeechRequest::ssml("<speak>Hello</speak>"));
+    round_trip(&SpeechRequest::annotated(
+      AnnotatedText::new()
+        .text("Hello ")
+        .span(TextSpan::new("world").language("fr").emphasis()),
+    ));
+    round_trip(&SpeechRequest::spelled("abc", true));
+    round_trip(&SpeechRequest::key(
+      KeyCombination::new(Key::Character('a')).modifier(Modifier::Control),
+    ));
+  }
+  #[test]
+  fn round_trips_every_setting() {
+    let request = SpeechRequest::text("Hello")
+      .synthesizer("espeak-ng")
+      .voice("en")
+      .variant("klatt")
+      .language("en-GB")
+      .rate(60)
+      .volume(80)
+      .pitch(40)
+      .punctuation(Punctuation::Custom(",;".to_owned()))
+      .capitals(Capitals::SayCap)
+      .format(TextFormat::Minecraft)
+      .interrupt()
+      .priority(Priority::Important);
+    #[cfg(feature = "profiles")]
+    let request = request.profile("reading");
+    round_trip(&request);
+  }
+}
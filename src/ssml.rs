@@ -0,0 +1,622 @@
+use crate::annotated_text::AnnotatedText;
+use crate::error::OutputError;
+use anyhow::anyhow;
+use std::fmt::{self, Display, Formatter, Write};
+use std::iter::once;
+const ELEMENTS: [&str; 8] = [
+  "speak", "break", "prosody", "say-as", "voice", "lang", "mark", "emphasis",
+];
+/// How deeply elements may be nested, which bounds the recursion of parsing and translation.
+const MAX_DEPTH: usize = 64;
+/// An SSML document, validated to contain only the elements that whisp-rs can translate for
+/// every backend: `speak`, `break`, `prosody`, `say-as`, `voice`, `lang`, `mark` and `emphasis`.
+///
+/// Backends that do not support markup speak its text content instead.
+#[derive(Clone, Debug)]
+pub struct Ssml {
+  root: Element,
+}
+#[derive(Clone, Debug)]
+struct Element {
+  name: String,
+  attributes: Vec<(String, String)>,
+  children: Vec<Node>,
+}
+#[derive(Clone, Debug)]
+enum Node {
+  Text(String),
+  Element(Element),
+}
+impl Element {
+  fn wrap(name: &str, attributes: Vec<(String, String)>, child: Node) -> Node {
+    Node::Element(Element {
+      name: name.to_owned(),
+      attributes,
+      children: vec![child],
+    })
+  }
+  fn attribute(&self, name: &str) -> Option<&str> {
+    self
+      .attributes
+      .iter()
+      .find(|(attribute, _)| attribute == name)
+      .map(|(_, value)| value.as_str())
+  }
+  fn validate(&self, is_root: bool) -> Result<(), String> {
+    if !ELEMENTS.contains(&self.name.as_str()) {
+      return Err(format!("unsupported element <{}>", self.name));
+    }
+    if is_root != (self.name == "speak") {
+      return Err("the document must have exactly one <speak> element at its root".to_owned());
+    }
+    if matches!(self.name.as_str(), "break" | "mark") && !self.children.is_empty() {
+      return Err(format!("<{}> must be empty", self.name));
+    }
+    let required = match self.name.as_str() {
+      "mark" => Some("name"),
+      "say-as" => Some("interpret-as"),
+      "lang" => Some("xml:lang"),
+      _ => None,
+    };
+    if let Some(required) = required {
+      if self.attribute(required).is_none() {
+        return Err(format!("<{}> needs the {required} attribute", self.name));
+      }
+    }
+    self.children.iter().try_for_each(|child| match child {
+      Node::Text(_) => Ok(()),
+      Node::Element(element) => element.validate(false),
+    })
+  }
+}
+struct Parser<'a> {
+  input: &'a str,
+  position: usize,
+}
+impl<'a> Parser<'a> {
+  fn rest(&self) -> &'a str {
+    &self.input[self.position..]
+  }
+  fn error(&self, message: &str) -> String {
+    format!("{message} at byte {}", self.position)
+  }
+  fn expect(&mut self, expected: &str) -> Result<(), String> {
+    if self.rest().starts_with(expected) {
+      self.position += expected.len();
+      Ok(())
+    } else {
+      Err(self.error(&format!("expected `{expected}`")))
+    }
+  }
+  fn skip_until(&mut self, end: &str) -> Result<&'a str, String> {
+    let length = self
+      .rest()
+      .find(end)
+      .ok_or_else(|| self.error(&format!("expected `{end}`")))?;
+    let skipped = &self.rest()[..length];
+    self.position += length + end.len();
+    Ok(skipped)
+  }
+  fn skip_whitespace(&mut self) {
+    self.position = self.input.len() - self.rest().trim_start().len();
+  }
+  fn skip_prolog_and_comments(&mut self) -> Result<(), String> {
+    loop {
+      self.skip_whitespace();
+      if self.rest().starts_with("<?") {
+        self.skip_until("?>")?;
+      } else if self.rest().starts_with("<!--") {
+        self.skip_until("-->")?;
+      } else if self.rest().starts_with("<!DOCTYPE") {
+        self.skip_until(">")?;
+      } else {
+        return Ok(());
+      }
+    }
+  }
+  fn parse_name(&mut self) -> Result<String, String> {
+    let length = self
+      .rest()
+      .find(|character: char| {
+        !(character.is_alphanumeric() || matches!(character, '-' | '_' | ':' | '.'))
+      })
+      .unwrap_or(self.rest().len());
+    if length == 0 {
+      return Err(self.error("expected a name"));
+    }
+    let name = self.rest()[..length].to_owned();
+    self.position += length;
+    Ok(name)
+  }
+  fn parse_attribute(&mut self) -> Result<(String, String), String> {
+    let name = self.parse_name()?;
+    self.skip_whitespace();
+    self.expect("=")?;
+    self.skip_whitespace();
+    let Some(quote @ ('"' | '\'')) = self.rest().chars().next() else {
+      return Err(self.error("expected a quoted attribute value"));
+    };
+    self.position += 1;
+    let value = self.skip_until(&quote.to_string())?;
+    Ok((name, unescape(value).map_err(|err| self.error(&err))?))
+  }
+  /// Parses an element nested in `depth` others.
+  fn parse_element(&mut self, depth: usize) -> Result<Element, String> {
+    if depth >= MAX_DEPTH {
+      return Err(self.error(&format!("elements are nested more than {MAX_DEPTH} deep")));
+    }
+    self.expect("<")?;
+    let name = self.parse_name()?;
+    let mut element = Element {
+      name,
+      attributes: Vec::new(),
+      children: Vec::new(),
+    };
+    loop {
+      let had_whitespace = self.rest().starts_with(char::is_whitespace);
+      self.skip_whitespace();
+      if self.rest().starts_with("/>") {
+        self.position += 2;
+        return Ok(element);
+      }
+      if self.rest().starts_with('>') {
+        self.position += 1;
+        break;
+      }
+      if !had_whitespace {
+        return Err(self.error("expected whitespace before an attribute"));
+      }
+      let (name, value) = self.parse_attribute()?;
+      if element.attribute(&name).is_some() {
+        return Err(self.error(&format!("duplicate attribute {name}")));
+      }
+      element.attributes.push((name, value));
+    }
+    loop {
+      if self.rest().starts_with("</") {
+        self.position += 2;
+        let name = self.parse_name()?;
+        if name != element.name {
+          return Err(self.error(&format!("expected </{}>", element.name)));
+        }
+        self.skip_whitespace();
+        self.expect(">")?;
+        return Ok(element);
+      } else if self.rest().starts_with("<!--") {
+        self.skip_until("-->")?;
+      } else if self.rest().starts_with("<![CDATA[") {
+        self.position += "<![CDATA[".len();
+        let text = self.skip_until("]]>")?;
+        element.children.push(Node::Text(text.to_owned()));
+      } else if self.rest().starts_with('<') {
+        element
+          .children
+          .push(Node::Element(self.parse_element(depth + 1)?));
+      } else {
+        let length = self
+          .rest()
+          .find('<')
+          .ok_or_else(|| self.error(&format!("expected </{}>", element.name)))?;
+        let text = unescape(&self.rest()[..length]).map_err(|err| self.error(&err))?;
+        self.position += length;
+        element.children.push(Node::Text(text));
+      }
+    }
+  }
+}
+fn unescape(text: &str) -> Result<String, String> {
+  let mut result = String::with_capacity(text.len());
+  let mut rest = text;
+  while let Some(start) = rest.find('&') {
+    result.push_str(&rest[..start]);
+    rest = &rest[start + 1..];
+    let end = rest
+      .find(';')
+      .ok_or_else(|| "unterminated character reference".to_owned())?;
+    let reference = &rest[..end];
+    let character = match reference {
+      "amp" => Some('&'),
+      "lt" => Some('<'),
+      "gt" => Some('>'),
+      "quot" => Some('"'),
+      "apos" => Some('\''),
+      _ => reference
+        .strip_prefix("#x")
+        .map(|hex| u32::from_str_radix(hex, 16))
+        .or_else(|| reference.strip_prefix('#').map(str::parse))
+        .and_then(Result::ok)
+        .and_then(char::from_u32),
+    };
+    result.push(character.ok_or_else(|| format!("unknown character reference &{reference};"))?);
+    rest = &rest[end + 1..];
+  }
+  result.push_str(rest);
+  Ok(result)
+}
+fn escape(text: &str) -> String {
+  text
+    .replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('"', "&quot;")
+}
+fn write_element(output: &mut String, element: &Element, rename: fn(&str) -> &str) {
+  let name = rename(&element.name);
+  output.push('<');
+  output.push_str(name);
+  for (attribute, value) in &element.attributes {
+    let _ = write!(output, " {attribute}=\"{}\"", escape(value));
+  }
+  if element.children.is_empty() {
+    output.push_str("/>");
+    return;
+  }
+  output.push('>');
+  for child in &element.children {
+    match child {
+      Node::Text(text) => output.push_str(&escape(text)),
+      Node::Element(element) => write_element(output, element, rename),
+    }
+  }
+  let _ = write!(output, "</{name}>");
+}
+/// Converts an SSML rate or pitch, either a keyword or a percentage, to a step between -10 and 10
+/// relative to the default.
+fn relative_steps(value: &str) -> Option<i32> {
+  let steps = match value {
+    "x-slow" | "x-low" => -6,
+    "slow" | "low" => -3,
+    "medium" | "default" => 0,
+    "fast" | "high" => 3,
+    "x-fast" | "x-high" => 6,
+    _ => {
+      let percentage: f64 = value.strip_suffix('%')?.parse().ok()?;
+      let change = if value.starts_with(['+', '-']) {
+        percentage
+      } else {
+        percentage - 100.0
+      };
+      #[allow(clippy::cast_possible_truncation)]
+      let steps = (change / 10.0).round() as i32;
+      steps
+    }
+  };
+  Some(steps.clamp(-10, 10))
+}
+/// Converts an SSML volume, either a keyword or a percentage, to a level between 0 and 100.
+fn volume_level(value: &str) -> Option<i32> {
+  let level = match value {
+    "silent" => 0,
+    "x-soft" => 20,
+    "soft" => 40,
+    "medium" => 60,
+    "loud" => 80,
+    "x-loud" | "default" => 100,
+    _ => {
+      let percentage: f64 = value.strip_suffix('%')?.parse().ok()?;
+      let percentage = if value.starts_with(['+', '-']) {
+        100.0 + percentage
+      } else {
+        percentage
+      };
+      #[allow(clippy::cast_possible_truncation)]
+      let level = percentage.round() as i32;
+      level
+    }
+  };
+  Some(level.clamp(0, 100))
+}
+/// Converts the `time` or `strength` of an SSML break to milliseconds.
+fn break_milliseconds(element: &Element) -> u32 {
+  let time = element.attribute("time").and_then(|time| {
+    if let Some(milliseconds) = time.strip_suffix("ms") {
+      milliseconds.trim().parse::<f64>().ok()
+    } else {
+      time
+        .strip_suffix('s')
+        .and_then(|seconds| seconds.trim().parse::<f64>().ok())
+        .map(|seconds| seconds * 1000.0)
+    }
+  });
+  match time {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    Some(milliseconds) => milliseconds.max(0.0).round() as u32,
+    None => match element.attribute("strength") {
+      Some("none") => 0,
+      Some("x-weak") => 100,
+      Some("weak") => 250,
+      Some("strong") => 1000,
+      Some("x-strong") => 2000,
+      _ => 500,
+    },
+  }
+}
+impl Ssml {
+  /// Parses and validates an SSML document.
+  pub fn parse(ssml: &str) -> Result<Self, OutputError> {
+    let mut parser = Parser {
+      input: ssml,
+      position: 0,
+    };
+    let mut parse = || {
+      parser.skip_prolog_and_comments()?;
+      let root = parser.parse_element(0)?;
+      parser.skip_prolog_and_comments()?;
+      if !parser.rest().is_empty() {
+        return Err(parser.error("unexpected content after the root element"));
+      }
+      root.validate(true)?;
+      Ok(root)
+    };
+    let root = parse()
+      .map_err(|err: String| OutputError::into_invalid_parameter(anyhow!("Invalid SSML: {err}")))?;
+    Ok(Ssml { root })
+  }
+  /// Builds the document equivalent to annotated text, with rates and pitches as percentages of
+  /// the default of 50 and volumes as percentages of the maximum.
+  pub(crate) fn from_annotated_text(text: &AnnotatedText) -> Self {
+    let children = text
+      .spans
+      .iter()
+      .flat_map(|span| {
+        let mut node = Node::Text(span.text.clone());
+        if span.emphasis {
+          node = Element::wrap("emphasis", Vec::new(), node);
+        }
+        let prosody = [
+          ("rate", span.rate.map(|rate| u32::from(rate) * 2)),
+          ("pitch", span.pitch.map(|pitch| u32::from(pitch) * 2)),
+          ("volume", span.volume.map(u32::from)),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| Some((name.to_owned(), format!("{}%", value?))))
+        .collect::<Vec<(String, String)>>();
+        if !prosody.is_empty() {
+          node = Element::wrap("prosody", prosody, node);
+        }
+        if let Some(language) = &span.language {
+          node = Element::wrap(
+            "lang",
+            vec![("xml:lang".to_owned(), language.clone())],
+            node,
+          );
+        }
+        if let Some(voice) = &span.voice {
+          node = Element::wrap("voice", vec![("name".to_owned(), voice.clone())], node);
+        }
+        let pause = span.pause_after.map(|pause| {
+          Node::Element(Element {
+            name: "break".to_owned(),
+            attributes: vec![("time".to_owned(), format!("{}ms", pause.as_millis()))],
+            children: Vec::new(),
+          })
+        });
+        once(node).chain(pause)
+      })
+      .collect();
+    Ssml {
+      root: Element {
+        name: "speak".to_owned(),
+        attributes: Vec::new(),
+        children,
+      },
+    }
+  }
+  /// A document that spells `text` with `say-as`.
+  pub(crate) fn characters(text: &str) -> Self {
+    Ssml {
+      root: Element {
+        name: "speak".to_owned(),
+        attributes: Vec::new(),
+        children: vec![Element::wrap(
+          "say-as",
+          vec![("interpret-as".to_owned(), "characters".to_owned())],
+          Node::Text(text.to_owned()),
+        )],
+      },
+    }
+  }
+  /// The text content of the document, which is spoken by backends that do not support markup.
+  #[must_use]
+  pub fn to_plain_text(&self) -> String {
+    fn write_text(output: &mut String, element: &Element) {
+      if element.name == "break" {
+        output.push(' ');
+      }
+      for child in &element.children {
+        match child {
+          Node::Text(text) => output.push_str(text),
+          Node::Element(element) => write_text(output, element),
+        }
+      }
+    }
+    let mut output = String::new();
+    write_text(&mut output, &self.root);
+    output
+  }
+  /// Replaces every text node with the result of `map`, which also receives the language of the
+  /// node from the nearest `xml:lang` attribute, or `language` outside of any.
+  pub(crate) fn map_text(
+    &mut self,
+    language: Option<&str>,
+    map: &dyn Fn(&str, Option<&str>) -> String,
+  ) {
+    fn map_element(
+      element: &mut Element,
+      language: Option<&str>,
+      map: &dyn Fn(&str, Option<&str>) -> String,
+    ) {
+      let language = element
+        .attribute("xml:lang")
+        .map(std::borrow::ToOwned::to_owned)
+        .or(language.map(std::borrow::ToOwned::to_owned));
+      for child in &mut element.children {
+        match child {
+          Node::Text(text) => *text = map(text, language.as_deref()),
+          Node::Element(element) => map_element(element, language.as_deref(), map),
+        }
+      }
+    }
+    map_element(&mut self.root, language, map);
+  }
+  /// The document as understood by eSpeak NG, which changes languages with `<voice>` instead of
+  /// `<lang>`.
+  pub(crate) fn to_espeak_ssml(&self) -> String {
+    let mut output = String::new();
+    write_element(&mut output, &self.root, |name| match name {
+      "lang" => "voice",
+      name => name,
+    });
+    output
+  }
+  /// The document as SAPI 5 XML. `lcid` converts a language tag to a Windows locale identifier,
+  /// without which `<lang>` elements are ignored.
+  #[cfg_attr(not(windows), allow(dead_code))]
+  pub(crate) fn to_sapi_xml(&self, lcid: &dyn Fn(&str) -> Option<u32>) -> String {
+    fn write_children(output: &mut String, element: &Element, lcid: &dyn Fn(&str) -> Option<u32>) {
+      for child in &element.children {
+        match child {
+          Node::Text(text) => output.push_str(&escape(text)),
+          Node::Element(element) => write_sapi(output, element, lcid),
+        }
+      }
+    }
+    fn write_sapi(output: &mut String, element: &Element, lcid: &dyn Fn(&str) -> Option<u32>) {
+      let mut closing = Vec::new();
+      match element.name.as_str() {
+        "break" => {
+          let _ = write!(
+            output,
+            "<silence msec=\"{}\"/>",
+            break_milliseconds(element)
+          );
+        }
+        "mark" => {
+          let _ = write!(
+            output,
+            "<bookmark mark=\"{}\"/>",
+            escape(element.attribute("name").unwrap_or_default())
+          );
+        }
+        "emphasis" => {
+          output.push_str("<emph>");
+          closing.push("</emph>");
+        }
+        "say-as"
+          if matches!(
+            element.attribute("interpret-as"),
+            Some("characters" | "spell-out")
+          ) =>
+        {
+          output.push_str("<spell>");
+          closing.push("</spell>");
+        }
+        "prosody" => {
+          if let Some(rate) = element.attribute("rate").and_then(relative_steps) {
+            let _ = write!(output, "<rate speed=\"{rate}\">");
+            closing.push("</rate>");
+          }
+          if let Some(pitch) = element.attribute("pitch").and_then(relative_steps) {
+            let _ = write!(output, "<pitch middle=\"{pitch}\">");
+            closing.push("</pitch>");
+          }
+          if let Some(volume) = element.attribute("volume").and_then(volume_level) {
+            let _ = write!(output, "<volume level=\"{volume}\">");
+            closing.push("</volume>");
+          }
+        }
+        "voice" | "lang" => {
+          if let Some(name) = element.attribute("name") {
+            let _ = write!(output, "<voice required=\"Name={}\">", escape(name));
+            closing.push("</voice>");
+          }
+          if let Some(langid) = element.attribute("xml:lang").and_then(lcid) {
+            let _ = write!(output, "<lang langid=\"{langid:x}\">");
+            closing.push("</lang>");
+          }
+        }
+        _ => {}
+      }
+      write_children(output, element, lcid);
+      for tag in closing.into_iter().rev() {
+        output.push_str(tag);
+      }
+    }
+    let mut output = String::new();
+    write_children(&mut output, &self.root, lcid);
+    output
+  }
+}
+impl Display for Ssml {
+  fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+    let mut output = String::new();
+    write_element(&mut output, &self.root, |name| name);
+    formatter.write_str(&output)
+  }
+}
+#[cfg(test)]
+mod tests {
+  use super::*;
+  fn error(ssml: &str) -> String {
+    Ssml::parse(ssml).unwrap_err().to_string()
+  }
+  #[test]
+  fn rejects_invalid_documents() {
+    assert!(error("<speak><audio src=\"a.wav\"/></speak>").contains("unsupported element <audio>"));
+    assert!(error("<prosody rate=\"slow\">Hi</prosody>").contains("exactly one <speak>"));
+    assert!(error("<speak><speak>Hi</speak></speak>").contains("exactly one <speak>"));
+    assert!(error("<speak><mark name=\"a\">Hi</mark></speak>").contains("<mark> must be empty"));
+    assert!(error("<speak><lang>Hi</lang></speak>").contains("needs the xml:lang attribute"));
+    assert!(error("<speak>Hi</prosody>").contains("expected </speak>"));
+    assert!(error("<speak>Hi &nbsp;</speak>").contains("unknown character reference &nbsp;"));
+    assert!(error("<speak>Hi</speak> there").contains("unexpected content"));
+    assert!(error("<speak a=\"1\" a=\"2\"/>").contains("duplicate attribute a"));
+  }
+  #[test]
+  fn limits_nesting() {
+    let nested = |depth: usize| {
+      format!(
+        "<speak>{}Hi{}</speak>",
+        "<emphasis>".repeat(depth),
+        "</emphasis>".repeat(depth)
+      )
+    };
+    assert!(Ssml::parse(&nested(MAX_DEPTH - 1)).is_ok());
+    assert!(error(&nested(MAX_DEPTH)).contains("nested more than"));
+    assert!(error(&"<speak>".repeat(100_000)).contains("nested more than"));
+  }
+  #[test]
+  fn translates_to_plain_text() {
+    let ssml = Ssml::parse(
+      "<?xml version=\"1.0\"?><!-- greeting --><speak>Hello<break/><emphasis>big</emphasis> \
+       &amp; <![CDATA[<small>]]> world</speak>",
+    )
+    .unwrap();
+    assert_eq!(ssml.to_plain_text(), "Hello big & <small> world");
+  }
+  #[test]
+  fn translates_to_espeak_ssml() {
+    let ssml =
+      Ssml::parse("<speak><lang xml:lang=\"de\">Hallo</lang> <mark name=\"m\"/>a &lt; b</speak>")
+        .unwrap();
+    assert_eq!(
+      ssml.to_espeak_ssml(),
+      "<speak><voice xml:lang=\"de\">Hallo</voice> <mark name=\"m\"/>a &lt; b</speak>"
+    );
+  }
+  #[test]
+  fn translates_to_sapi_xml() {
+    let ssml = Ssml::parse(
+      "<speak><prosody rate=\"fast\" volume=\"soft\">Quick</prosody><break time=\"1.5s\"/>\
+       <say-as interpret-as=\"characters\">ab</say-as><lang xml:lang=\"fr\">Oui</lang>\
+       <lang xml:lang=\"xx\">?</lang><emphasis>now</emphasis><mark name=\"end\"/></speak>",
+    )
+    .unwrap();
+    let lcid = |language: &str| (language == "fr").then_some(0x40c);
+    assert_eq!(
+      ssml.to_sapi_xml(&lcid),
+      "<rate speed=\"3\"><volume level=\"40\">Quick</volume></rate><silence msec=\"1500\"/>\
+       <spell>ab</spell><lang langid=\"40c\">Oui</lang>?<emph>now</emph><bookmark mark=\"end\"/>"
+    );
+  }
+}
@@ -0,0 +1,369 @@
+use crate::annotated_text::{AnnotatedText, TextSpan};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+/// Translations used for JSON text components when the application does not provide them. They
+/// follow the narration strings of Minecraft rather than the chat layout.
+const DEFAULT_TRANSLATIONS: [(&str, &str); 8] = [
+  ("chat.type.text", "%s says %s"),
+  ("chat.type.announcement", "%s says %s"),
+  ("chat.type.emote", "* %s %s"),
+  ("chat.type.admin", "%s: %s"),
+  ("chat.type.team.text", "%s, %s says %s"),
+  (
+    "commands.message.display.incoming",
+    "%s whispers to you: %s",
+  ),
+  ("multiplayer.player.joined", "%s joined the game"),
+  ("multiplayer.player.left", "%s left the game"),
+];
+/// How the text of a [`crate::request::SpeechRequest`] or of
+/// [`crate::Whisprs::braille_with_format`] is interpreted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TextFormat {
+  /// The text is spoken as it is.
+  #[default]
+  Plain,
+  /// Minecraft text: either a serialized JSON text component or a string with legacy `§`
+  /// formatting codes. Colours are stripped, bold and italic text is emphasized and obfuscated
+  /// text is spoken more quietly, where the backend supports markup.
+  Minecraft,
+}
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct Style {
+  bold: bool,
+  italic: bool,
+  obfuscated: bool,
+}
+impl Style {
+  /// The style of a component, which inherits what it does not set from its parent.
+  fn inherit(self, component: &Map<String, Value>) -> Self {
+    let flag = |name: &str, parent: bool| {
+      component
+        .get(name)
+        .and_then(Value::as_bool)
+        .unwrap_or(parent)
+    };
+    Style {
+      bold: flag("bold", self.bold),
+      italic: flag("italic", self.italic),
+      obfuscated: flag("obfuscated", self.obfuscated),
+    }
+  }
+}
+/// Minecraft text reduced to runs of text that share a style.
+pub(crate) struct MinecraftText<'a> {
+  translations: &'a HashMap<String, String>,
+  segments: Vec<(String, Style)>,
+}
+impl<'a> MinecraftText<'a> {
+  /// Parses a JSON text component if the text is one, and legacy formatting codes otherwise.
+  /// `translations` maps translation and key binding keys to the strings of the game's language.
+  pub(crate) fn parse(text: &str, translations: &'a HashMap<String, String>) -> Self {
+    let mut minecraft_text = MinecraftText {
+      translations,
+      segments: Vec::new(),
+    };
+    let trimmed = text.trim_start();
+    let component = if trimmed.starts_with(['{', '[', '"']) {
+      serde_json::from_str::<Value>(text).ok()
+    } else {
+      None
+    };
+    match component {
+      Some(component) => minecraft_text.push_component(&component, Style::default()),
+      None => minecraft_text.push_legacy(text, Style::default()),
+    }
+    minecraft_text
+  }
+  fn push(&mut self, text: &str, style: Style) {
+    if text.is_empty() {
+      return;
+    }
+    match self.segments.last_mut() {
+      Some((last, last_style)) if *last_style == style => last.push_str(text),
+      _ => self.segments.push((text.to_owned(), style)),
+    }
+  }
+  /// Adds text with `§` codes. Colour codes and `§r` go back to the style the text started with,
+  /// as they do in the game.
+  fn push_legacy(&mut self, text: &str, initial: Style) {
+    let mut style = initial;
+    let mut run = String::new();
+    let mut characters = text.chars();
+    while let Some(character) = characters.next() {
+      if character != '§' {
+        run.push(character);
+        continue;
+      }
+      let Some(code) = characters.next() else {
+        break;
+      };
+      let new_style = match code.to_ascii_lowercase() {
+        '0'..='9' | 'a'..='f' | 'r' => initial,
+        'k' => Style {
+          obfuscated: true,
+          ..style
+        },
+        'l' => Style {
+          bold: true,
+          ..style
+        },
+        'o' => Style {
+          italic: true,
+          ..style
+        },
+        'x' => {
+          let rest = characters.as_str();
+          let hex = rest
+            .char_indices()
+            .nth(12)
+            .map_or(rest.len(), |(index, _)| index);
+          characters = rest[hex..].chars();
+          initial
+        }
+        _ => style,
+      };
+      if new_style != style {
+        self.push(&run, style);
+        run.clear();
+        style = new_style;
+      }
+    }
+    self.push(&run, style);
+  }
+  fn push_component(&mut self, component: &Value, parent: Style) {
+    match component {
+      Value::String(text) => self.push_legacy(text, parent),
+      Value::Number(number) => self.push(&number.to_string(), parent),
+      Value::Bool(value) => self.push(&value.to_string(), parent),
+      Value::Array(components) => {
+        let Some((first, rest)) = components.split_first() else {
+          return;
+        };
+        self.push_component(first, parent);
+        let style = match first {
+          Value::Object(first) => parent.inherit(first),
+          _ => parent,
+        };
+        for component in rest {
+          self.push_component(component, style);
+        }
+      }
+      Value::Object(component) => {
+        let style = parent.inherit(component);
+        self.push_content(component, style);
+        if let Some(Value::Array(extra)) = component.get("extra") {
+          for child in extra {
+            self.push_component(child, style);
+          }
+        }
+      }
+      Value::Null => {}
+    }
+  }
+  fn push_content(&mut self, component: &Map<String, Value>, style: Style) {
+    let string = |name: &str| component.get(name).and_then(Value::as_str);
+    if let Some(text) = string("text") {
+      self.push_legacy(text, style);
+    } else if let Some(key) = string("translate") {
+      let arguments = match component.get("with") {
+        Some(Value::Array(arguments)) => arguments.as_slice(),
+        _ => &[],
+      };
+      self.push_translation(key, string("fallback"), arguments, style);
+    } else if let Some(key) = string("keybind") {
+      let name = self.translation(key).map_or_else(
+        || key.rsplit('.').next().unwrap_or(key).replace('_', " "),
+        str::to_owned,
+      );
+      self.push(&name, style);
+    } else if let Some(Value::Object(score)) = component.get("score") {
+      match score.get("value") {
+        Some(Value::String(value)) => self.push(value, style),
+        Some(value @ Value::Number(_)) => self.push(&value.to_string(), style),
+        _ => {}
+      }
+    } else if let Some(selector) = string("selector") {
+      self.push(selector, style);
+    }
+  }
+  fn translation(&self, key: &str) -> Option<&'a str> {
+    self.translations.get(key).map(String::as_str).or_else(|| {
+      DEFAULT_TRANSLATIONS
+        .iter()
+        .find(|(default_key, _)| *default_key == key)
+        .map(|(_, translation)| *translation)
+    })
+  }
+  /// Adds a translated component, substituting its arguments for `%s` and `%1$s` placeholders.
+  /// Without a translation, the arguments are spoken one after another, or the key if there are
+  /// none.
+  fn push_translation(
+    &mut self,
+    key: &str,
+    fallback: Option<&str>,
+    arguments: &[Value],
+    style: Style,
+  ) {
+    let Some(format) = self.translation(key).or(fallback) else {
+      if arguments.is_empty() {
+        self.push(key, style);
+      }
+      for (index, argument) in arguments.iter().enumerate() {
+        if index > 0 {
+          self.push(" ", style);
+        }
+        self.push_component(argument, style);
+      }
+      return;
+    };
+    let mut next_argument = 0;
+    let mut rest = format;
+    while let Some(start) = rest.find('%') {
+      self.push_legacy(&rest[..start], style);
+      rest = &rest[start + 1..];
+      if let Some(after) = rest.strip_prefix('%') {
+        self.push("%", style);
+        rest = after;
+        continue;
+      }
+      let digits = rest
+        .find(|character: char| !character.is_ascii_digit())
+        .unwrap_or(rest.len());
+      let (position, after) = match rest[digits..].strip_prefix('$') {
+        Some(after) if digits > 0 => (rest[..digits].parse::<usize>().ok(), after),
+        _ => (None, rest),
+      };
+      let Some(after) = after.strip_prefix(['s', 'd']) else {
+        self.push("%", style);
+        continue;
+      };
+      let index = if let Some(position) = position {
+        position.checked_sub(1)
+      } else {
+        next_argument += 1;
+        Some(next_argument - 1)
+      };
+      if let Some(argument) = index.and_then(|index| arguments.get(index)) {
+        self.push_component(argument, style);
+      }
+      rest = after;
+    }
+    self.push_legacy(rest, style);
+  }
+  pub(crate) fn to_plain_text(&self) -> String {
+    self
+      .segments
+      .iter()
+      .map(|(text, _)| text.as_str())
+      .collect()
+  }
+  /// Converts the text to spans, using `volume`, the volume of the rest of the text, to make
+  /// obfuscated text quieter.
+  pub(crate) fn to_annotated_text(&self, volume: Option<u8>) -> AnnotatedText {
+    let spans = self
+      .segments
+      .iter()
+      .map(|(text, style)| TextSpan {
+        emphasis: style.bold || style.italic,
+        volume: style.obfuscated.then(|| volume.unwrap_or(100) / 2),
+        ..TextSpan::new(text)
+      })
+      .collect();
+    AnnotatedText { spans }
+  }
+}
+#[cfg(test)]
+mod tests {
+  use super::*;
+  fn plain(text: &str) -> String {
+    MinecraftText::parse(text, &HashMap::new()).to_plain_text()
+  }
+  fn spans(text: &str, volume: Option<u8>) -> Vec<(String, bool, Option<u8>)> {
+    MinecraftText::parse(text, &HashMap::new())
+      .to_annotated_text(volume)
+      .spans
+      .into_iter()
+      .map(|span| (span.text, span.emphasis, span.volume))
+      .collect()
+  }
+  #[test]
+  fn strips_legacy_codes() {
+    assert_eq!(plain("§aHello §lworld§r!"), "Hello world!");
+    assert_eq!(
+      plain("§x§f§f§0§0§0§0red §fand§x§1§2§3§4§5§6 blue"),
+      "red and blue"
+    );
+    assert_eq!(plain("a§x§1§2"), "a");
+    assert_eq!(plain("dangling §"), "dangling ");
+  }
+  #[test]
+  fn maps_styles_to_spans() {
+    assert_eq!(
+      spans("§aHi §lthere§okk§r §kxyz", Some(80)),
+      [
+        ("Hi ".to_owned(), false, None),
+        ("there".to_owned(), true, None),
+        ("kk".to_owned(), true, None),
+        (" ".to_owned(), false, None),
+        ("xyz".to_owned(), false, Some(40)),
+      ]
+    );
+  }
+  #[test]
+  fn inherits_styles_from_parents() {
+    assert_eq!(
+      spans(
+        r#"{"text": "a", "bold": true, "extra": [{"text": "b"}, {"text": "c", "bold": false}]}"#,
+        None
+      ),
+      [("ab".to_owned(), true, None), ("c".to_owned(), false, None)]
+    );
+    assert_eq!(
+      spans(r#"[{"text": "a", "italic": true}, "§lb"]"#, None),
+      [("a".to_owned(), true, None), ("b".to_owned(), true, None)]
+    );
+  }
+  #[test]
+  fn substitutes_translation_arguments() {
+    assert_eq!(
+      plain(r#"{"translate": "chat.type.text", "with": ["Alice", {"text": "hi"}]}"#),
+      "Alice says hi"
+    );
+    let translations = HashMap::from([("test".to_owned(), "%2$s from %1$s, 100%%".to_owned())]);
+    let text = MinecraftText::parse(
+      r#"{"translate": "test", "with": ["Alice", "Bob"]}"#,
+      &translations,
+    );
+    assert_eq!(text.to_plain_text(), "Bob from Alice, 100%");
+    assert_eq!(
+      plain(r#"{"translate": "unknown", "with": ["a", "b"]}"#),
+      "a b"
+    );
+    assert_eq!(plain(r#"{"translate": "unknown"}"#), "unknown");
+    assert_eq!(
+      plain(r#"{"translate": "unknown", "fallback": "%s!", "with": ["a"]}"#),
+      "a!"
+    );
+  }
+  #[test]
+  fn reads_other_components() {
+    assert_eq!(
+      plain(r#"{"keybind": "key.sprint_toggle"}"#),
+      "sprint toggle"
+    );
+    let translations = HashMap::from([("key.jump".to_owned(), "Jump".to_owned())]);
+    assert_eq!(
+      MinecraftText::parse(r#"{"keybind": "key.jump"}"#, &translations).to_plain_text(),
+      "Jump"
+    );
+    assert_eq!(
+      plain(
+        r#"[{"score": {"name": "x", "objective": "y", "value": 12}}, " ", {"selector": "@p"}]"#
+      ),
+      "12 @p"
+    );
+    assert_eq!(plain("{not json"), "{not json");
+  }
+}
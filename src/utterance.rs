@@ -0,0 +1,143 @@
+use crate::events::{EventBus, SpeechEvent, SpeechEventKind};
+use std::sync::{Arc, Condvar, Mutex, OnceLock, PoisonError, Weak};
+use std::time::{Duration, Instant};
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum UtteranceState {
+  Queued,
+  Speaking,
+  /// The utterance was handed to a backend that does not report its progress, such as a screen
+  /// reader.
+  Delivered,
+  Finished,
+  Cancelled,
+}
+impl UtteranceState {
+  #[must_use]
+  pub fn is_done(self) -> bool {
+    matches!(
+      self,
+      UtteranceState::Delivered | UtteranceState::Finished | UtteranceState::Cancelled
+    )
+  }
+}
+#[derive(Debug)]
+struct UtteranceStatus {
+  state: Mutex<UtteranceState>,
+  changed: Condvar,
+}
+/// A handle to one piece of speech queued with [`crate::Whisprs::speak_to_audio_output`]. Handles
+/// are cheap to clone and can be queried from any thread.
+#[derive(Clone, Debug)]
+pub struct Utterance {
+  id: u64,
+  synthesizer: Arc<OnceLock<String>>,
+  status: Arc<UtteranceStatus>,
+  /// Weak, so that handles kept by the application do not keep the event thread running after
+  /// the instance that created them is dropped.
+  events: Weak<EventBus>,
+}
+impl Utterance {
+  /// Creates an utterance whose synthesizer is not chosen yet.
+  pub(crate) fn pending(id: u64, events: &Arc<EventBus>) -> Self {
+    Utterance {
+      id,
+      synthesizer: Arc::new(OnceLock::new()),
+      status: Arc::new(UtteranceStatus {
+        state: Mutex::new(UtteranceState::Queued),
+        changed: Condvar::new(),
+      }),
+      events: Arc::downgrade(events),
+    }
+  }
+  pub(crate) fn set_synthesizer(&self, synthesizer: &str) {
+    let _result = self.synthesizer.set(synthesizer.to_owned());
+  }
+  #[must_use]
+  pub fn id(&self) -> u64 {
+    self.id
+  }
+  /// The name of the synthesizer speaking the utterance. Empty until the worker thread has chosen
+  /// it, which may not have happened yet for utterances queued without waiting.
+  #[must_use]
+  pub fn synthesizer(&self) -> &str {
+    self.synthesizer.get().map_or("", String::as_str)
+  }
+  #[must_use]
+  pub fn state(&self) -> UtteranceState {
+    *self
+      .status
+      .state
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner)
+  }
+  /// Updates the state of the utterance and sends the matching begin, end or cancel event.
+  /// Backends call this as speech progresses; once an utterance is done, its state no longer
+  /// changes.
+  pub fn set_state(&self, state: UtteranceState) {
+    let mut current = self
+      .status
+      .state
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner);
+    if current.is_done() || *current == state {
+      return;
+    }
+    let kind = match (*current, state) {
+      (UtteranceState::Queued, UtteranceState::Speaking) => Some(SpeechEventKind::Begin),
+      (_, UtteranceState::Finished) => Some(SpeechEventKind::End),
+      (_, UtteranceState::Cancelled) => Some(SpeechEventKind::Cancel),
+      _ => None,
+    };
+    *current = state;
+    self.status.changed.notify_all();
+    drop(current);
+    if let Some(kind) = kind {
+      self.emit(kind);
+    }
+  }
+  /// Sends an event about this utterance to the subscribers of the instance that created it. Does
+  /// nothing once that instance has been dropped.
+  pub fn emit(&self, kind: SpeechEventKind) {
+    let Some(events) = self.events.upgrade() else {
+      return;
+    };
+    events.send(SpeechEvent {
+      utterance: self.id,
+      synthesizer: self.synthesizer().to_owned(),
+      kind,
+    });
+  }
+  /// Blocks until the utterance is done or `timeout` has passed, and returns its last state.
+  #[must_use]
+  pub fn wait(&self, timeout: Option<Duration>) -> UtteranceState {
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    let mut state = self
+      .status
+      .state
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner);
+    while !state.is_done() {
+      state = match deadline {
+        None => self
+          .status
+          .changed
+          .wait(state)
+          .unwrap_or_else(PoisonError::into_inner),
+        Some(deadline) => {
+          let remaining = deadline.saturating_duration_since(Instant::now());
+          if remaining.is_zero() {
+            break;
+          }
+          self
+            .status
+            .changed
+            .wait_timeout(state, remaining)
+            .unwrap_or_else(PoisonError::into_inner)
+            .0
+        }
+      };
+    }
+    *state
+  }
+}